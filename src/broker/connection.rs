@@ -59,7 +59,7 @@ pub fn spawn_connection(
     stream: UnixStream,
     conn_id: ConnectionId,
     cmd_tx: mpsc::UnboundedSender<BrokerCommand>,
-    inject_rx: mpsc::UnboundedReceiver<Message>,
+    inject_rx: mpsc::Receiver<Message>,
     disconnect_tx: mpsc::UnboundedSender<DisconnectNotice>,
 ) {
     tokio::spawn(async move {
@@ -77,7 +77,7 @@ async fn handle_connection(
     stream: UnixStream,
     conn_id: ConnectionId,
     cmd_tx: mpsc::UnboundedSender<BrokerCommand>,
-    mut inject_rx: mpsc::UnboundedReceiver<Message>,
+    mut inject_rx: mpsc::Receiver<Message>,
 ) -> Result<(), ConnectionError> {
     let mut framed = Framed::new(stream, FrameCodec::new());
 
@@ -89,7 +89,7 @@ async fn handle_connection(
         .map_err(ConnectionError::Codec)?;
 
     let first_msg = match decode_frame(&first_frame) {
-        DecodeResult::Ok(msg @ Message::Hello { .. }) => msg,
+        DecodeResult::Ok(msg) if matches!(*msg, Message::Hello { .. }) => *msg,
         DecodeResult::Ok(_non_hello) => {
             // First message is a valid message but not Hello — reject.
             // We can't echo a meaningful id without parsing, but the
@@ -127,8 +127,13 @@ async fn handle_connection(
                     None => return Ok(()), // Clean disconnect.
                 };
                 match decode_frame(&raw) {
+                    DecodeResult::Ok(msg) if matches!(*msg, Message::Output { .. }) => {
+                        // Tapped output is a notification: answering every
+                        // chunk would double the wrapper's traffic.
+                        notify(&cmd_tx, *msg, conn_id)?;
+                    }
                     DecodeResult::Ok(msg) => {
-                        let response = send_command(&cmd_tx, msg, conn_id).await?;
+                        let response = send_command(&cmd_tx, *msg, conn_id).await?;
                         framed.send(response).await.map_err(ConnectionError::Codec)?;
                     }
                     DecodeResult::UnknownType(envelope) => {
@@ -147,6 +152,9 @@ async fn handle_connection(
                             interrupted: None,
                             truncated: None,
                             turns: None,
+                            registers: None,
+                            results: None,
+                            routes: None,
                         };
                         framed.send(response).await.map_err(ConnectionError::Codec)?;
                     }
@@ -190,6 +198,22 @@ async fn send_command(
         .map_err(|_| ConnectionError::ResponseDropped)
 }
 
+/// Send a notification to the broker loop. Its response is discarded.
+fn notify(
+    cmd_tx: &mpsc::UnboundedSender<BrokerCommand>,
+    request: Message,
+    conn_id: ConnectionId,
+) -> Result<(), ConnectionError> {
+    let (response_tx, _) = oneshot::channel();
+    cmd_tx
+        .send(BrokerCommand {
+            request,
+            response_tx,
+            connection_id: conn_id,
+        })
+        .map_err(|_| ConnectionError::BrokerGone)
+}
+
 fn is_error_hello_ack(msg: &Message) -> bool {
     matches!(
         msg,
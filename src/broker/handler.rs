@@ -6,21 +6,40 @@
 //!
 //! See CONTRACT_BROKER.md §Request / Response.
 
-use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, Status, TurnDescriptor};
+use std::collections::HashSet;
 
-use super::state::{BrokerState, ConnectionId, SinkMetadata};
+use crate::ipc::protocol::{
+    DeliveryResult, MAX_PAYLOAD_SIZE, Message, PROTOCOL_VERSION, RegisterDescriptor, Role,
+    RouteDescriptor, SectionStyle, Status, TurnContext, TurnDescriptor,
+};
+use crate::turn::Turn;
+
+use super::registry::TurnRecord;
+use super::snapshot::Snapshot;
+use super::state::{
+    BrokerState, CaptureResult, ConnectionId, SessionMeta, SinkMetadata, validate_register,
+};
 
 /// An inject command that the broker loop must send to a wrapper.
 ///
 /// Produced by the paste handler when the relay buffer is successfully
-/// read. The broker loop routes this to the target wrapper's inject
-/// channel.
+/// read, and by the session control handlers. The broker loop routes
+/// this to the target wrapper's inject channel.
 #[derive(Debug)]
 pub struct InjectAction {
     pub target_connection: ConnectionId,
     pub message: Message,
 }
 
+/// One target of a multi-target paste or deliver.
+#[derive(Debug)]
+pub enum Delivery {
+    /// Inject into the wrapper on this connection.
+    Inject(ConnectionId),
+    Clipboard,
+    File(String),
+}
+
 /// Side effects produced by message handlers.
 ///
 /// The broker loop executes these after (or instead of) sending the
@@ -29,7 +48,8 @@ pub struct InjectAction {
 /// optimistic ok response with an error on failure.
 #[derive(Debug)]
 pub enum SideEffect {
-    /// Route content to a wrapper's PTY (existing paste behavior).
+    /// Route a message to a wrapper: injected content (paste) or a
+    /// session control command.
     Inject {
         action: InjectAction,
         request_id: u32,
@@ -47,6 +67,26 @@ pub enum SideEffect {
         metadata: SinkMetadata,
         request_id: u32,
     },
+    /// Send a query to a wrapper and hold the client's response until
+    /// the matching [`QueryReply`](Self::QueryReply) or a timeout.
+    WrapperQuery { action: InjectAction, token: u32 },
+    /// Answer the client request held for query `token`.
+    QueryReply { token: u32, response: Message },
+    /// Deliver content to several targets (multi-target paste or
+    /// deliver). The broker loop routes each pending delivery, records
+    /// its outcome and answers with every target's result.
+    Deliveries {
+        content: Vec<u8>,
+        metadata: SinkMetadata,
+        /// Each target's result, with its delivery unless it already
+        /// failed.
+        targets: Vec<(DeliveryResult, Option<Delivery>)>,
+        request_id: u32,
+    },
+    /// Route messages to several connections (output tap fan-out,
+    /// relay routes). Best-effort: a failed delivery does not affect
+    /// the response.
+    Broadcast { actions: Vec<InjectAction> },
 }
 
 /// Dispatch a request message to the appropriate handler.
@@ -74,20 +114,45 @@ pub fn handle_message(
             id,
             session,
             pid,
-            pattern: _,
+            pattern,
+            name,
+            role,
+            labels,
+            command,
+            started_at,
         } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
             }
-            let response = handle_register(state, id, session, pid, connection_id);
+            // Older wrappers omit started_at; fall back to receipt time.
+            let started_at = if started_at == 0 {
+                crate::turn::epoch_millis()
+            } else {
+                started_at
+            };
+            let meta = SessionMeta {
+                name,
+                role,
+                labels,
+                command,
+                pattern,
+                started_at,
+            };
+            let response = handle_register(state, id, session, pid, meta, connection_id);
             (response, None)
         }
-        Message::Deregister { id, session } => {
+        Message::Deregister {
+            id,
+            session,
+            exit_code,
+            reason,
+        } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
             }
-            let response = handle_deregister(state, id, &session);
-            (response, None)
+            let subscribers = state.take_subscribers(&session);
+            let response = handle_deregister(state, id, &session, exit_code, reason);
+            (response, output_end(&session, subscribers))
         }
         Message::TurnCompleted {
             id,
@@ -95,6 +160,9 @@ pub fn handle_message(
             content,
             interrupted,
             timestamp,
+            seq,
+            context,
+            replayed,
         } => {
             if !is_wrapper(state, connection_id) {
                 return (error_response(id, "unknown_type"), None);
@@ -106,17 +174,197 @@ pub fn handle_message(
             } else {
                 timestamp
             };
-            let response = handle_turn_completed(state, id, &session, content, interrupted, ts);
+            let turn = Turn {
+                content,
+                interrupted,
+                timestamp: ts,
+            };
+            let response = handle_turn_completed(state, id, &session, turn, seq, context);
+            if !matches!(
+                response,
+                Message::Response {
+                    status: Status::Ok,
+                    ..
+                }
+            ) || replayed
+            {
+                // A replayed turn was routed when it first completed.
+                return (response, None);
+            }
+            (response, route_injects(state.route_turn(&session)))
+        }
+        Message::InputActivity {
+            id,
+            session,
+            timestamp,
+        } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            let response = match state.record_input(&session, timestamp) {
+                Ok(()) => ok_response(id),
+                Err(reason) => error_response(id, reason),
+            };
+            (response, None)
+        }
+        Message::SessionState {
+            id,
+            session,
+            state: agent_state,
+        } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            let response = match state.set_agent_state(&session, agent_state) {
+                Ok(()) => ok_response(id),
+                Err(reason) => error_response(id, reason),
+            };
+            let delivery = state.take_route_delivery(&session);
+            (response, route_injects(delivery.into_iter().collect()))
+        }
+        Message::SessionTitle { id, session, title } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            let response = match state.set_title(&session, title) {
+                Ok(()) => ok_response(id),
+                Err(reason) => error_response(id, reason),
+            };
             (response, None)
         }
         // -- Any role --
-        Message::Capture { id, session } => {
-            let response = handle_capture(state, id, &session);
+        Message::Capture {
+            id,
+            session,
+            register,
+        } => {
+            let response = handle_capture(state, id, &session, register.as_deref());
             (response, None)
         }
-        Message::Paste { id, session } => handle_paste(state, id, &session),
-        Message::ListSessions { id } => {
-            let response = handle_list_sessions(state, id);
+        Message::Paste {
+            id,
+            session,
+            sessions,
+            role,
+            register,
+            template,
+        } => {
+            if sessions.is_empty() && role.is_none() {
+                return handle_paste(
+                    state,
+                    id,
+                    &session,
+                    register.as_deref(),
+                    template.as_deref(),
+                );
+            }
+            let targets = Some(&session)
+                .filter(|s| !s.is_empty())
+                .into_iter()
+                .chain(&sessions)
+                .map(String::as_str)
+                .collect();
+            let request = DeliveryRequest {
+                sinks: vec!["inject"],
+                sessions: targets,
+                role: role.as_deref(),
+                path: None,
+                source: ContentSource {
+                    turn: None,
+                    register: register.as_deref(),
+                    template: template.as_deref(),
+                },
+            };
+            handle_deliver_many(state, id, request)
+        }
+        Message::CaptureLines {
+            id,
+            session,
+            last,
+            grep,
+            register,
+        } => handle_capture_lines(state, id, session, last, grep, register),
+        Message::ScrollbackLines {
+            id, query, content, ..
+        } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            handle_scrollback_lines(state, id, query, content, connection_id)
+        }
+        // -- Output tap --
+        Message::Subscribe { id, session } => handle_subscribe(state, id, &session, connection_id),
+        Message::Output {
+            id,
+            session,
+            content,
+        } => {
+            if !is_wrapper(state, connection_id) {
+                return (error_response(id, "unknown_type"), None);
+            }
+            handle_output(state, id, session, content, connection_id)
+        }
+        // -- Session control (any role) --
+        Message::Signal {
+            id,
+            session,
+            signal,
+        } => {
+            if signal.parse::<nix::sys::signal::Signal>().is_err() {
+                return (error_response(id, "invalid_signal"), None);
+            }
+            let message = Message::Signal {
+                id: 0,
+                session: session.clone(),
+                signal,
+            };
+            handle_control(state, id, &session, message)
+        }
+        Message::Kill { id, session } => {
+            let message = Message::Kill {
+                id: 0,
+                session: session.clone(),
+            };
+            handle_control(state, id, &session, message)
+        }
+        Message::Resize {
+            id,
+            session,
+            cols,
+            rows,
+        } => {
+            if cols == 0 || rows == 0 {
+                return (error_response(id, "invalid_size"), None);
+            }
+            let message = Message::Resize {
+                id: 0,
+                session: session.clone(),
+                cols,
+                rows,
+            };
+            handle_control(state, id, &session, message)
+        }
+        Message::RecordStart { id, session, path } => {
+            // The wrapper's working directory is not the client's.
+            if !std::path::Path::new(&path).is_absolute() {
+                return (error_response(id, "invalid_path"), None);
+            }
+            let message = Message::RecordStart {
+                id: 0,
+                session: session.clone(),
+                path,
+            };
+            handle_control(state, id, &session, message)
+        }
+        Message::RecordStop { id, session } => {
+            let message = Message::RecordStop {
+                id: 0,
+                session: session.clone(),
+            };
+            handle_control(state, id, &session, message)
+        }
+        Message::ListSessions { id, all } => {
+            let response = handle_list_sessions(state, id, all);
             (response, None)
         }
         // -- Turn registry queries (v1, any role) --
@@ -128,21 +376,171 @@ pub fn handle_message(
             let response = handle_list_turns(state, id, &session, limit);
             (response, None)
         }
-        Message::CaptureByID { id, turn_id } => {
-            let response = handle_capture_by_id(state, id, &turn_id);
+        Message::CaptureByID {
+            id,
+            turn_id,
+            turn_ids,
+            separator,
+            register,
+        } => {
+            let mut references = vec![turn_id.as_str()];
+            references.extend(turn_ids.iter().map(String::as_str));
+            let response = capture_response(
+                id,
+                state.capture_by_id(&references, separator.as_deref(), register.as_deref()),
+            );
+            (response, None)
+        }
+        // -- Snapshots (any role) --
+        Message::SnapshotSave { id, sessions } => {
+            let response = handle_snapshot_save(state, id, &sessions);
             (response, None)
         }
+        Message::SnapshotLoad { id, content } => {
+            let response = handle_snapshot_load(state, id, &content);
+            (response, None)
+        }
+        Message::Replay {
+            id,
+            session,
+            from,
+            snapshot,
+            last,
+            frame,
+        } => {
+            let source = match snapshot {
+                Some(ref data) => ReplaySource::Snapshot {
+                    data,
+                    session: from.as_deref(),
+                },
+                None => match from.as_deref() {
+                    Some(from) => ReplaySource::Session(from),
+                    None => return (error_response(id, "missing_field"), None),
+                },
+            };
+            handle_replay(state, id, &session, source, last, frame)
+        }
         // -- Sink delivery (v1, any role) --
         Message::Deliver {
             id,
             sink,
+            sinks,
             session,
+            sessions,
+            role,
             path,
-        } => handle_deliver(state, id, &sink, session.as_deref(), path.as_deref()),
+            turn_id,
+            separator,
+            register,
+            template,
+        } => {
+            let source = ContentSource {
+                turn: turn_id.as_deref().map(|t| (t, separator.as_deref())),
+                register: register.as_deref(),
+                template: template.as_deref(),
+            };
+            if sinks.is_empty() && sessions.is_empty() && role.is_none() {
+                return handle_deliver(
+                    state,
+                    id,
+                    &sink,
+                    session.as_deref(),
+                    path.as_deref(),
+                    source,
+                );
+            }
+            let request = DeliveryRequest {
+                sinks: std::iter::once(&sink)
+                    .chain(&sinks)
+                    .map(String::as_str)
+                    .collect(),
+                sessions: session
+                    .iter()
+                    .chain(&sessions)
+                    .map(String::as_str)
+                    .collect(),
+                role: role.as_deref(),
+                path: path.as_deref(),
+                source,
+            };
+            handle_deliver_many(state, id, request)
+        }
+        // -- Routes (any role) --
+        Message::RouteAdd {
+            id,
+            route,
+            disabled,
+        } => {
+            let result = state.add_route(route, !disabled, crate::turn::epoch_millis());
+            (routes_response(id, result.map(|r| vec![r])), None)
+        }
+        Message::RouteList { id } => (routes_response(id, Ok(state.list_routes())), None),
+        Message::RouteRemove { id, route } => {
+            let response = match state.remove_route(route) {
+                Ok(()) => ok_response(id),
+                Err(reason) => error_response(id, reason),
+            };
+            (response, None)
+        }
+        Message::RouteEnable { id, route, enabled } => {
+            let result = state.enable_route(route, enabled);
+            (routes_response(id, result.map(|r| vec![r])), None)
+        }
+        Message::ListRegisters { id } => {
+            let response = registers_response(id, state.list_registers());
+            (response, None)
+        }
+        // -- Relay buffer (any role) --
+        Message::RelayShow { id, register } => {
+            let response = handle_relay_show(state, id, register.as_deref());
+            (response, None)
+        }
+        Message::RelaySet {
+            id,
+            content,
+            register,
+            source,
+        } => {
+            let source = source
+                .as_deref()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("external");
+            let now = crate::turn::epoch_millis();
+            let result = state.set_relay(register.as_deref(), content, source, now);
+            (capture_response(id, result), None)
+        }
+        Message::RelayClear { id, register } => {
+            let response = match state.clear_relay(register.as_deref()) {
+                Ok(()) => ok_response(id),
+                Err(reason) => error_response(id, reason),
+            };
+            (response, None)
+        }
+        Message::RelayUndo { id, register } => {
+            let result = state.undo_relay(register.as_deref());
+            (capture_response(id, result), None)
+        }
+        Message::RelayHistory { id } => {
+            let response = registers_response(id, state.relay_history());
+            (response, None)
+        }
+        Message::Gather {
+            id,
+            turn_ids,
+            style,
+            into_relay,
+            register,
+        } => {
+            let response =
+                handle_gather(state, id, &turn_ids, style, into_relay, register.as_deref());
+            (response, None)
+        }
         // Server-originated messages should never be sent by clients.
         Message::HelloAck { id, .. }
         | Message::Response { id, .. }
-        | Message::Inject { id, .. } => (error_response(id, "unknown_type"), None),
+        | Message::Inject { id, .. }
+        | Message::OutputTap { id, .. }
+        | Message::OutputEnd { id, .. } => (error_response(id, "unknown_type"), None),
     }
 }
 
@@ -184,16 +582,24 @@ fn handle_register(
     id: u32,
     session: String,
     pid: u32,
+    meta: SessionMeta,
     connection_id: ConnectionId,
 ) -> Message {
-    match state.register_session(session, connection_id, pid) {
+    match state.register_session(session, connection_id, pid, meta) {
         Ok(()) => ok_response(id),
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_deregister(state: &mut BrokerState, id: u32, session: &str) -> Message {
-    state.deregister_session(session);
+fn handle_deregister(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    exit_code: Option<i32>,
+    reason: Option<String>,
+) -> Message {
+    let now = crate::turn::epoch_millis();
+    state.deregister_session(session, exit_code, reason, now);
     ok_response(id)
 }
 
@@ -201,11 +607,16 @@ fn handle_turn_completed(
     state: &mut BrokerState,
     id: u32,
     session: &str,
-    content: Vec<u8>,
-    interrupted: bool,
-    timestamp: u64,
+    turn: Turn,
+    seq: Option<u64>,
+    context: TurnContext,
 ) -> Message {
-    match state.store_turn(session, content, interrupted, timestamp) {
+    let Turn {
+        content,
+        interrupted,
+        timestamp,
+    } = turn;
+    match state.store_turn(session, content, interrupted, timestamp, seq, context) {
         Ok(turn_id) => Message::Response {
             id,
             status: Status::Ok,
@@ -219,13 +630,26 @@ fn handle_turn_completed(
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         },
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_capture(state: &mut BrokerState, id: u32, session: &str) -> Message {
-    match state.capture(session) {
+fn handle_capture(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    register: Option<&str>,
+) -> Message {
+    capture_response(id, state.capture(session, register))
+}
+
+/// Build the response to a capture-style request.
+fn capture_response(id: u32, result: Result<CaptureResult, &'static str>) -> Message {
+    match result {
         Ok(result) => Message::Response {
             id,
             status: Status::Ok,
@@ -239,13 +663,250 @@ fn handle_capture(state: &mut BrokerState, id: u32, session: &str) -> Message {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         },
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_paste(state: &mut BrokerState, id: u32, session: &str) -> (Message, Option<SideEffect>) {
-    match state.paste_content(session) {
+/// Forward a line capture to the session's wrapper as a query. The
+/// client's response comes from the wrapper's `scrollback_lines` reply
+/// (see [`handle_scrollback_lines`]).
+fn handle_capture_lines(
+    state: &mut BrokerState,
+    id: u32,
+    session: String,
+    last: u32,
+    grep: Option<String>,
+    register: Option<String>,
+) -> (Message, Option<SideEffect>) {
+    if last == 0 {
+        return (error_response(id, "invalid_count"), None);
+    }
+    if let Some(Err(reason)) = register.as_deref().map(validate_register) {
+        return (error_response(id, reason), None);
+    }
+    if let Some(ref pattern) = grep
+        && regex::Regex::new(pattern).is_err()
+    {
+        return (error_response(id, "invalid_regex"), None);
+    }
+    match state.start_query(&session, id, register) {
+        Ok((token, target_connection)) => {
+            let action = InjectAction {
+                target_connection,
+                message: Message::CaptureLines {
+                    id: token,
+                    session,
+                    last,
+                    grep,
+                    register: None,
+                },
+            };
+            (
+                ok_response(id),
+                Some(SideEffect::WrapperQuery { action, token }),
+            )
+        }
+        Err(reason) => (error_response(id, reason), None),
+    }
+}
+
+/// Complete a pending line capture with the wrapper's lines.
+///
+/// The wrapper gets a plain ack; the client response is returned as a
+/// [`SideEffect::QueryReply`]. Replies to unknown or timed-out queries,
+/// or from a connection other than the one queried, are ignored.
+fn handle_scrollback_lines(
+    state: &mut BrokerState,
+    id: u32,
+    query: u32,
+    content: Vec<u8>,
+    connection_id: ConnectionId,
+) -> (Message, Option<SideEffect>) {
+    let Some(pending) = state.take_query(query) else {
+        tracing::debug!(query, "reply to unknown or expired query");
+        return (ok_response(id), None);
+    };
+    if pending.connection_id != connection_id {
+        return (error_response(id, "unknown_query"), None);
+    }
+    let now = crate::turn::epoch_millis();
+    let result = state.capture_lines(&pending.session, content, now, pending.register.as_deref());
+    let response = capture_response(pending.request_id, result);
+    (
+        ok_response(id),
+        Some(SideEffect::QueryReply {
+            token: query,
+            response,
+        }),
+    )
+}
+
+/// Subscribe a connection to a session's output. The first subscriber
+/// enables the wrapper's output tap.
+fn handle_subscribe(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    connection_id: ConnectionId,
+) -> (Message, Option<SideEffect>) {
+    match state.subscribe(session, connection_id) {
+        Ok(Some(target_connection)) => (
+            ok_response(id),
+            Some(SideEffect::Inject {
+                action: output_tap(target_connection, session, true),
+                request_id: id,
+            }),
+        ),
+        Ok(None) => (ok_response(id), None),
+        Err(reason) => (error_response(id, reason), None),
+    }
+}
+
+/// Fan a chunk of wrapper output out to the session's subscribers.
+///
+/// Output for a session nobody follows (the last subscriber left while
+/// the chunk was in flight) is dropped and the tap is disabled again.
+/// `output` is a notification: the connection task discards the
+/// response.
+fn handle_output(
+    state: &BrokerState,
+    id: u32,
+    session: String,
+    content: Vec<u8>,
+    connection_id: ConnectionId,
+) -> (Message, Option<SideEffect>) {
+    let subscribers = state.subscribers(&session);
+    if subscribers.is_empty() {
+        let action = output_tap(connection_id, &session, false);
+        return (
+            ok_response(id),
+            Some(SideEffect::Broadcast {
+                actions: vec![action],
+            }),
+        );
+    }
+    let actions = subscribers
+        .into_iter()
+        .map(|target_connection| InjectAction {
+            target_connection,
+            message: Message::Output {
+                id: 0,
+                session: session.clone(),
+                content: content.clone(),
+            },
+        })
+        .collect();
+    (ok_response(id), Some(SideEffect::Broadcast { actions }))
+}
+
+/// Clean up after a closed connection: end the output of sessions it
+/// owned, drop its subscriptions, then remove it from the state.
+///
+/// Returns the messages the broker loop must route: `output_end` to
+/// the subscribers of the departed wrapper's sessions, and a disabling
+/// `output_tap` to wrappers left without subscribers.
+pub fn handle_disconnect(
+    state: &mut BrokerState,
+    connection_id: ConnectionId,
+    now: u64,
+) -> Vec<InjectAction> {
+    let mut actions = Vec::new();
+    for session in state.live_sessions_of(connection_id) {
+        let subscribers = state.take_subscribers(&session);
+        if let Some(SideEffect::Broadcast { actions: ends }) = output_end(&session, subscribers) {
+            actions.extend(ends);
+        }
+    }
+    actions.extend(drop_subscriber(state, connection_id));
+    state.remove_connection(connection_id, now);
+    actions
+}
+
+/// Drop a connection's output subscriptions, disabling the taps of
+/// sessions left without subscribers.
+pub fn drop_subscriber(state: &mut BrokerState, connection_id: ConnectionId) -> Vec<InjectAction> {
+    state
+        .unsubscribe(connection_id)
+        .into_iter()
+        .map(|(session, wrapper)| output_tap(wrapper, &session, false))
+        .collect()
+}
+
+fn output_tap(target_connection: ConnectionId, session: &str, enabled: bool) -> InjectAction {
+    InjectAction {
+        target_connection,
+        message: Message::OutputTap {
+            id: 0,
+            session: session.to_string(),
+            enabled,
+        },
+    }
+}
+
+/// Tell a finished session's subscribers that no more output follows.
+fn output_end(session: &str, subscribers: Vec<ConnectionId>) -> Option<SideEffect> {
+    if subscribers.is_empty() {
+        return None;
+    }
+    let actions = subscribers
+        .into_iter()
+        .map(|target_connection| InjectAction {
+            target_connection,
+            message: Message::OutputEnd {
+                id: 0,
+                session: session.to_string(),
+            },
+        })
+        .collect();
+    Some(SideEffect::Broadcast { actions })
+}
+
+/// Route a session control message to the session's wrapper.
+///
+/// Like paste, delivery is fire-and-forget: `ok` means the message was
+/// queued to the wrapper connection.
+fn handle_control(
+    state: &BrokerState,
+    id: u32,
+    session: &str,
+    message: Message,
+) -> (Message, Option<SideEffect>) {
+    match state.wrapper_connection(session) {
+        Ok(target_connection) => (
+            ok_response(id),
+            Some(SideEffect::Inject {
+                action: InjectAction {
+                    target_connection,
+                    message,
+                },
+                request_id: id,
+            }),
+        ),
+        Err(reason) => (error_response(id, reason), None),
+    }
+}
+
+fn handle_paste(
+    state: &mut BrokerState,
+    id: u32,
+    session: &str,
+    register: Option<&str>,
+    template: Option<&str>,
+) -> (Message, Option<SideEffect>) {
+    let paste = state
+        .paste_content(session, register)
+        .and_then(|(content, conn)| match template {
+            Some(name) => {
+                let (_, metadata) = state.relay_content(register)?;
+                Ok((state.apply_template(name, &content, &metadata)?, conn))
+            }
+            None => Ok((content, conn)),
+        });
+    match paste {
         Ok((content, target_conn)) => {
             let action = InjectAction {
                 target_connection: target_conn,
@@ -263,8 +924,14 @@ fn handle_paste(state: &mut BrokerState, id: u32, session: &str) -> (Message, Op
     }
 }
 
-fn handle_list_sessions(state: &BrokerState, id: u32) -> Message {
-    let sessions = state.list_sessions();
+fn handle_list_sessions(state: &BrokerState, id: u32, all: bool) -> Message {
+    let mut sessions = state.list_sessions(all);
+    // The working directory changes under the child, so it is read at
+    // query time rather than stored. An ended session's PID may have
+    // been reused, so tombstones have none.
+    for s in sessions.iter_mut().filter(|s| s.ended_at.is_none()) {
+        s.cwd = process_cwd(s.pid);
+    }
     Message::Response {
         id,
         status: Status::Ok,
@@ -278,9 +945,21 @@ fn handle_list_sessions(state: &BrokerState, id: u32) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        registers: None,
+        results: None,
+        routes: None,
     }
 }
 
+/// Read a process's current working directory from `/proc/<pid>/cwd`.
+///
+/// Returns `None` if the process has exited or is not readable.
+fn process_cwd(pid: u32) -> Option<String> {
+    std::fs::read_link(format!("/proc/{pid}/cwd"))
+        .ok()
+        .map(|p| p.display().to_string())
+}
+
 fn handle_get_turn(state: &BrokerState, id: u32, turn_id: &str) -> Message {
     match state.get_turn(turn_id) {
         Ok(record) => Message::Response {
@@ -296,6 +975,9 @@ fn handle_get_turn(state: &BrokerState, id: u32, turn_id: &str) -> Message {
             interrupted: Some(record.interrupted),
             truncated: Some(record.truncated),
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         },
         Err(reason) => error_response(id, reason),
     }
@@ -305,16 +987,7 @@ fn handle_list_turns(state: &BrokerState, id: u32, session: &str, limit: Option<
     let limit = limit.map(|n| n as usize);
     match state.list_turns(session, limit) {
         Ok(records) => {
-            let turns: Vec<TurnDescriptor> = records
-                .into_iter()
-                .map(|r| TurnDescriptor {
-                    turn_id: r.turn_id.clone(),
-                    timestamp: r.timestamp,
-                    byte_length: r.byte_length,
-                    interrupted: r.interrupted,
-                    truncated: r.truncated,
-                })
-                .collect();
+            let turns: Vec<TurnDescriptor> = records.into_iter().map(turn_descriptor).collect();
             Message::Response {
                 id,
                 status: Status::Ok,
@@ -328,81 +1001,497 @@ fn handle_list_turns(state: &BrokerState, id: u32, session: &str, limit: Option<
                 interrupted: None,
                 truncated: None,
                 turns: Some(turns),
+                registers: None,
+                results: None,
+                routes: None,
             }
         }
         Err(reason) => error_response(id, reason),
     }
 }
 
-fn handle_capture_by_id(state: &mut BrokerState, id: u32, turn_id: &str) -> Message {
-    match state.capture_by_id(turn_id) {
-        Ok(result) => Message::Response {
-            id,
-            status: Status::Ok,
-            error: None,
-            size: Some(result.size),
-            sessions: None,
-            turn_id: Some(result.turn_id),
-            content: None,
-            timestamp: None,
-            byte_length: None,
-            interrupted: None,
-            truncated: None,
-            turns: None,
-        },
-        Err(reason) => error_response(id, reason),
+/// A turn record's metadata as sent in `list_turns`.
+fn turn_descriptor(record: &TurnRecord) -> TurnDescriptor {
+    TurnDescriptor {
+        turn_id: record.turn_id.clone(),
+        timestamp: record.timestamp,
+        byte_length: record.byte_length,
+        interrupted: record.interrupted,
+        truncated: record.truncated,
+        context: record.context.clone(),
     }
 }
 
+/// Show a relay register like a `get_turn` response, with `size` set
+/// to the content length.
+fn handle_relay_show(state: &BrokerState, id: u32, register: Option<&str>) -> Message {
+    match state.relay_content(register) {
+        Ok((content, metadata)) => content_response(id, content, metadata),
+        Err(reason) => error_response(id, reason),
+    }
+}
+
+/// Build a gathered payload (CONTRACT_REGISTRY.md §Gather) and, with
+/// `into_relay`, store it in a register.
+fn handle_gather(
+    state: &mut BrokerState,
+    id: u32,
+    turn_ids: &[String],
+    style: SectionStyle,
+    into_relay: bool,
+    register: Option<&str>,
+) -> Message {
+    if let Some(Err(reason)) = register.map(validate_register) {
+        return error_response(id, reason);
+    }
+    let references: Vec<&str> = turn_ids.iter().map(String::as_str).collect();
+    let (content, metadata) = match state.gather(&references, style) {
+        Ok(pair) => pair,
+        Err(reason) => return error_response(id, reason),
+    };
+    if content.len() + FRAME_OVERHEAD > MAX_PAYLOAD_SIZE {
+        return error_response(id, "payload_too_large");
+    }
+    if into_relay
+        && let Err(reason) = state.store_relay(register, content.clone(), metadata.clone())
+    {
+        return error_response(id, reason);
+    }
+    content_response(id, content, metadata)
+}
+
+/// Build a `get_turn`-shaped response for relay content, with `size`
+/// set to the content length.
+fn content_response(id: u32, content: Vec<u8>, metadata: SinkMetadata) -> Message {
+    Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: Some(content.len() as u32),
+        sessions: None,
+        turn_id: Some(metadata.turn_id),
+        content: Some(content),
+        timestamp: Some(metadata.timestamp),
+        byte_length: Some(metadata.byte_length),
+        interrupted: Some(metadata.interrupted),
+        truncated: Some(metadata.truncated),
+        turns: None,
+        registers: None,
+        results: None,
+        routes: None,
+    }
+}
+
+/// Build a response listing relay registers or history entries.
+fn registers_response(id: u32, registers: Vec<RegisterDescriptor>) -> Message {
+    Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: None,
+        sessions: None,
+        turn_id: None,
+        content: None,
+        timestamp: None,
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: None,
+        registers: Some(registers),
+        results: None,
+        routes: None,
+    }
+}
+
+fn routes_response(id: u32, result: Result<Vec<RouteDescriptor>, &'static str>) -> Message {
+    match result {
+        Ok(routes) => Message::Response {
+            id,
+            status: Status::Ok,
+            error: None,
+            size: None,
+            sessions: None,
+            turn_id: None,
+            content: None,
+            timestamp: None,
+            byte_length: None,
+            interrupted: None,
+            truncated: None,
+            turns: None,
+            registers: None,
+            results: None,
+            routes: Some(routes),
+        },
+        Err(reason) => error_response(id, reason),
+    }
+}
+
+/// Inject route deliveries into their targets' wrappers.
+fn route_injects(injects: Vec<(ConnectionId, Vec<u8>)>) -> Option<SideEffect> {
+    if injects.is_empty() {
+        return None;
+    }
+    let actions = injects
+        .into_iter()
+        .map(|(target_connection, content)| InjectAction {
+            target_connection,
+            message: Message::Inject { id: 0, content },
+        })
+        .collect();
+    Some(SideEffect::Broadcast { actions })
+}
+
+/// Room left in a frame for everything but its bulk content.
+const FRAME_OVERHEAD: usize = 1024;
+
+fn handle_snapshot_save(state: &BrokerState, id: u32, sessions: &[String]) -> Message {
+    let snapshot = match state.snapshot(sessions, crate::turn::epoch_millis()) {
+        Ok(snapshot) => snapshot,
+        Err(reason) => return error_response(id, reason),
+    };
+    let content = snapshot.encode();
+    if content.len() + FRAME_OVERHEAD > MAX_PAYLOAD_SIZE {
+        return error_response(id, "snapshot_too_large");
+    }
+    Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: Some(content.len() as u32),
+        sessions: None,
+        turn_id: None,
+        content: Some(content),
+        timestamp: Some(snapshot.created_at),
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: None,
+        registers: None,
+        results: None,
+        routes: None,
+    }
+}
+
+fn handle_snapshot_load(state: &mut BrokerState, id: u32, content: &[u8]) -> Message {
+    let restored = match Snapshot::decode(content).and_then(|s| state.restore(s)) {
+        Ok(restored) => restored,
+        Err(reason) => return error_response(id, reason),
+    };
+    let sessions = state
+        .list_sessions(true)
+        .into_iter()
+        .filter(|s| restored.contains(&s.session))
+        .collect();
+    Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: None,
+        sessions: Some(sessions),
+        turn_id: None,
+        content: None,
+        timestamp: None,
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: None,
+        registers: None,
+        results: None,
+        routes: None,
+    }
+}
+
+/// Where `replay` takes its turns from.
+enum ReplaySource<'a> {
+    /// A session in the broker (live, ended or archived).
+    Session(&'a str),
+    /// A session in an encoded snapshot; `None` picks its only session.
+    Snapshot {
+        data: &'a [u8],
+        session: Option<&'a str>,
+    },
+}
+
+fn handle_replay(
+    state: &BrokerState,
+    id: u32,
+    target: &str,
+    source: ReplaySource,
+    last: u32,
+    frame: bool,
+) -> (Message, Option<SideEffect>) {
+    if last == 0 {
+        return (error_response(id, "invalid_count"), None);
+    }
+    let target_conn = match state.wrapper_connection(target) {
+        Ok(conn) => conn,
+        Err(reason) => return (error_response(id, reason), None),
+    };
+    // Turns as descriptors plus content, oldest first.
+    let turns: Vec<(TurnDescriptor, Vec<u8>)> = match source {
+        ReplaySource::Session(from) => match state.list_turns(from, Some(last as usize)) {
+            Ok(records) => records
+                .into_iter()
+                .rev()
+                .map(|r| (turn_descriptor(r), r.content.clone()))
+                .collect(),
+            Err(reason) => return (error_response(id, reason), None),
+        },
+        ReplaySource::Snapshot { data, session } => {
+            let snapshot = match Snapshot::decode(data) {
+                Ok(snapshot) => snapshot,
+                Err(reason) => return (error_response(id, reason), None),
+            };
+            let session = match snapshot.session(session) {
+                Ok(session) => session,
+                Err(reason) => return (error_response(id, reason), None),
+            };
+            let skip = session.turns.len().saturating_sub(last as usize);
+            session.turns[skip..]
+                .iter()
+                .map(|t| (t.descriptor(), t.content.clone()))
+                .collect()
+        }
+    };
+    if turns.is_empty() {
+        return (error_response(id, "no_turn"), None);
+    }
+    let content = replay_content(&turns, frame);
+    if content.len() + FRAME_OVERHEAD > MAX_PAYLOAD_SIZE {
+        return (error_response(id, "payload_too_large"), None);
+    }
+    let response = Message::Response {
+        id,
+        status: Status::Ok,
+        error: None,
+        size: Some(content.len() as u32),
+        sessions: None,
+        turn_id: None,
+        content: None,
+        timestamp: None,
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: Some(turns.into_iter().map(|(d, _)| d).collect()),
+        registers: None,
+        results: None,
+        routes: None,
+    };
+    let action = InjectAction {
+        target_connection: target_conn,
+        message: Message::Inject { id: 0, content },
+    };
+    (
+        response,
+        Some(SideEffect::Inject {
+            action,
+            request_id: id,
+        }),
+    )
+}
+
+/// Join replayed turns into one paste. Each turn ends with a newline
+/// and turns are separated by a blank line; with `frame`, each turn is
+/// preceded by a `--- <turn_id> ---` header.
+fn replay_content(turns: &[(TurnDescriptor, Vec<u8>)], frame: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (i, (descriptor, content)) in turns.iter().enumerate() {
+        if i > 0 {
+            out.push(b'\n');
+        }
+        if frame {
+            out.extend_from_slice(format!("--- {} ---\n", descriptor.turn_id).as_bytes());
+        }
+        out.extend_from_slice(content);
+        if !content.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+/// What a deliver sends: a register's content, or with `turn` (a
+/// reference and the separator for ranges) a turn's, rendered through
+/// `template` if one is named.
+struct ContentSource<'a> {
+    turn: Option<(&'a str, Option<&'a str>)>,
+    register: Option<&'a str>,
+    template: Option<&'a str>,
+}
+
+impl ContentSource<'_> {
+    fn read(&self, state: &BrokerState) -> Result<(Vec<u8>, SinkMetadata), &'static str> {
+        let (content, metadata) = match self.turn {
+            Some((reference, separator)) => state.turn_content(reference, separator),
+            None => state.relay_content(self.register),
+        }?;
+        match self.template {
+            Some(name) => Ok((state.apply_template(name, &content, &metadata)?, metadata)),
+            None => Ok((content, metadata)),
+        }
+    }
+}
+
+/// Deliver content from `source` to a sink.
 fn handle_deliver(
     state: &mut BrokerState,
     id: u32,
     sink: &str,
     session: Option<&str>,
     path: Option<&str>,
+    source: ContentSource,
 ) -> (Message, Option<SideEffect>) {
-    match sink {
-        "inject" => {
-            let session = match session {
-                Some(s) => s,
-                None => return (error_response(id, "missing_field"), None),
-            };
-            handle_paste(state, id, session)
+    match (sink, session, path) {
+        ("inject", None, _) | ("file", _, None) => {
+            return (error_response(id, "missing_field"), None);
         }
-        "clipboard" => {
-            let (content, metadata) = match state.relay_content() {
-                Some(pair) => pair,
-                None => return (error_response(id, "buffer_empty"), None),
+        ("inject" | "clipboard" | "file", _, _) => {}
+        _ => return (error_response(id, "unknown_sink"), None),
+    }
+    let (content, metadata) = match source.read(state) {
+        Ok(pair) => pair,
+        Err(reason) => return (error_response(id, reason), None),
+    };
+    let effect = match (sink, session, path) {
+        ("inject", Some(session), _) => {
+            let target_connection = match state.wrapper_connection(session) {
+                Ok(conn) => conn,
+                Err(reason) => return (error_response(id, reason), None),
             };
-            (
-                ok_response(id),
-                Some(SideEffect::Clipboard {
-                    content,
-                    metadata,
-                    request_id: id,
-                }),
-            )
+            SideEffect::Inject {
+                action: InjectAction {
+                    target_connection,
+                    message: Message::Inject { id: 0, content },
+                },
+                request_id: id,
+            }
         }
-        "file" => {
-            let path = match path {
-                Some(p) => p,
-                None => return (error_response(id, "missing_field"), None),
-            };
-            let (content, metadata) = match state.relay_content() {
-                Some(pair) => pair,
-                None => return (error_response(id, "buffer_empty"), None),
-            };
-            (
-                ok_response(id),
-                Some(SideEffect::FileWrite {
-                    path: path.to_string(),
-                    content,
-                    metadata,
-                    request_id: id,
-                }),
-            )
+        ("file", _, Some(path)) => SideEffect::FileWrite {
+            path: path.to_string(),
+            content,
+            metadata,
+            request_id: id,
+        },
+        _ => SideEffect::Clipboard {
+            content,
+            metadata,
+            request_id: id,
+        },
+    };
+    (ok_response(id), Some(effect))
+}
+
+/// The targets of a multi-target paste or deliver: every sink, and
+/// for `inject` every session named or matching `role`.
+struct DeliveryRequest<'a> {
+    sinks: Vec<&'a str>,
+    sessions: Vec<&'a str>,
+    role: Option<&'a str>,
+    path: Option<&'a str>,
+    source: ContentSource<'a>,
+}
+
+/// Deliver the same content to several targets. Malformed requests
+/// and unreadable content fail as a whole; a target that cannot be
+/// reached fails on its own (CONTRACT_REGISTRY.md §Multi-target
+/// delivery).
+fn handle_deliver_many(
+    state: &BrokerState,
+    id: u32,
+    request: DeliveryRequest,
+) -> (Message, Option<SideEffect>) {
+    let mut sinks = request.sinks;
+    let mut seen = HashSet::new();
+    sinks.retain(|sink| seen.insert(*sink));
+    for sink in &sinks {
+        match (*sink, request.path) {
+            ("file", None) => return (error_response(id, "missing_field"), None),
+            ("inject" | "clipboard" | "file", _) => {}
+            _ => return (error_response(id, "unknown_sink"), None),
+        }
+    }
+    let sessions = if sinks.contains(&"inject") {
+        match state.inject_targets(&request.sessions, request.role) {
+            Ok(sessions) if sessions.is_empty() => {
+                return (error_response(id, "missing_field"), None);
+            }
+            Ok(sessions) => sessions,
+            Err(reason) => return (error_response(id, reason), None),
+        }
+    } else {
+        Vec::new()
+    };
+    let (content, metadata) = match request.source.read(state) {
+        Ok(pair) => pair,
+        Err(reason) => return (error_response(id, reason), None),
+    };
+
+    let mut targets = Vec::new();
+    for sink in sinks {
+        match (sink, request.path) {
+            ("inject", _) => {
+                for session in &sessions {
+                    let target = format!("inject:{session}");
+                    targets.push(match state.wrapper_connection(session) {
+                        Ok(conn) => (delivery_result(target, None), Some(Delivery::Inject(conn))),
+                        Err(reason) => (delivery_result(target, Some(reason)), None),
+                    });
+                }
+            }
+            ("file", Some(path)) => targets.push((
+                delivery_result(format!("file:{path}"), None),
+                Some(Delivery::File(path.to_string())),
+            )),
+            _ => targets.push((
+                delivery_result("clipboard".into(), None),
+                Some(Delivery::Clipboard),
+            )),
         }
-        _ => (error_response(id, "unknown_sink"), None),
+    }
+    let results = targets.iter().map(|(result, _)| result.clone()).collect();
+    (
+        deliveries_response(id, results),
+        Some(SideEffect::Deliveries {
+            content,
+            metadata,
+            targets,
+            request_id: id,
+        }),
+    )
+}
+
+fn delivery_result(target: String, error: Option<&str>) -> DeliveryResult {
+    DeliveryResult {
+        target,
+        status: if error.is_some() {
+            Status::Error
+        } else {
+            Status::Ok
+        },
+        error: error.map(str::to_string),
+    }
+}
+
+/// Answer a multi-target paste or deliver with every target's result:
+/// `ok` if any target succeeded, otherwise `"all_targets_failed"`.
+pub(super) fn deliveries_response(id: u32, results: Vec<DeliveryResult>) -> Message {
+    let delivered = results.iter().any(|r| r.status == Status::Ok);
+    Message::Response {
+        id,
+        status: if delivered { Status::Ok } else { Status::Error },
+        error: (!delivered).then(|| "all_targets_failed".into()),
+        size: None,
+        sessions: None,
+        turn_id: None,
+        content: None,
+        timestamp: None,
+        byte_length: None,
+        interrupted: None,
+        truncated: None,
+        turns: None,
+        registers: None,
+        results: Some(results),
+        routes: None,
     }
 }
 
@@ -426,6 +1515,9 @@ fn ok_response(id: u32) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        registers: None,
+        results: None,
+        routes: None,
     }
 }
 
@@ -443,12 +1535,16 @@ pub(super) fn error_response(id: u32, reason: &str) -> Message {
         interrupted: None,
         truncated: None,
         turns: None,
+        registers: None,
+        results: None,
+        routes: None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ipc::protocol::{AgentState, RouteSpec};
 
     fn fresh() -> (BrokerState, ConnectionId) {
         use crate::broker::state::RingConfig;
@@ -471,6 +1567,11 @@ mod tests {
             session: session.into(),
             pid,
             pattern: "generic".into(),
+            name: None,
+            role: None,
+            labels: Default::default(),
+            command: Vec::new(),
+            started_at: 0,
         }
     }
 
@@ -605,6 +1706,8 @@ mod tests {
             Message::Deregister {
                 id: 2,
                 session: "s1".into(),
+                exit_code: Some(0),
+                reason: Some("exited with code 0".into()),
             },
             c,
         );
@@ -615,6 +1718,22 @@ mod tests {
                 ..
             }
         ));
+
+        let (resp, _) = handle_message(&mut s, Message::ListSessions { id: 3, all: false }, c);
+        assert!(matches!(resp, Message::Response { sessions: Some(ref l), .. } if l.is_empty()));
+        let (resp, _) = handle_message(&mut s, Message::ListSessions { id: 4, all: true }, c);
+        match resp {
+            Message::Response {
+                sessions: Some(sessions),
+                ..
+            } => {
+                assert_eq!(sessions.len(), 1);
+                assert_eq!(sessions[0].state, AgentState::Exited);
+                assert_eq!(sessions[0].exit_code, Some(0));
+                assert_eq!(sessions[0].cwd, None);
+            }
+            other => panic!("expected Response, got {other:?}"),
+        }
     }
 
     // -- Turn completed --
@@ -632,6 +1751,9 @@ mod tests {
                 content: b"output".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
@@ -656,6 +1778,9 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
@@ -687,6 +1812,9 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
@@ -713,6 +1841,9 @@ mod tests {
                 content: b"12345".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
@@ -721,6 +1852,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                register: None,
             },
             c,
         );
@@ -761,6 +1893,9 @@ mod tests {
                 content: b"turn data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c1,
         );
@@ -769,6 +1904,7 @@ mod tests {
             Message::Capture {
                 id: 3,
                 session: "s1".into(),
+                register: None,
             },
             c2,
         );
@@ -778,6 +1914,10 @@ mod tests {
             Message::Paste {
                 id: 4,
                 session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: None,
+                template: None,
             },
             c2,
         );
@@ -806,473 +1946,793 @@ mod tests {
         }
     }
 
+    // -- Capture lines --
+
     #[test]
-    fn paste_buffer_empty() {
+    fn capture_lines_queries_wrapper() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        let (resp, inject) = handle_message(
+        let (_, effect) = handle_message(
             &mut s,
-            Message::Paste {
+            Message::CaptureLines {
+                id: 5,
+                session: "s1".into(),
+                last: 10,
+                grep: Some("err".into()),
+                register: None,
+            },
+            c,
+        );
+        let token = match effect {
+            Some(SideEffect::WrapperQuery { action, token }) => {
+                assert_eq!(action.target_connection, c);
+                assert!(matches!(
+                    action.message,
+                    Message::CaptureLines { id, last: 10, .. } if id == token
+                ));
+                token
+            }
+            other => panic!("expected SideEffect::WrapperQuery, got {other:?}"),
+        };
+
+        let (ack, effect) = handle_message(
+            &mut s,
+            Message::ScrollbackLines {
                 id: 2,
                 session: "s1".into(),
+                query: token,
+                content: b"err\n".to_vec(),
             },
             c,
         );
-        assert!(inject.is_none());
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("buffer_empty"));
+        assert!(matches!(
+            ack,
+            Message::Response {
+                id: 2,
+                status: Status::Ok,
+                ..
             }
-            _ => panic!("expected Response"),
+        ));
+        match effect {
+            Some(SideEffect::QueryReply {
+                token: t,
+                response: Message::Response { id, size, .. },
+            }) => {
+                assert_eq!(t, token);
+                assert_eq!(id, 5);
+                assert_eq!(size, Some(4));
+            }
+            other => panic!("expected SideEffect::QueryReply, got {other:?}"),
         }
+        assert_eq!(s.relay_content(None).unwrap().0, b"err\n");
     }
 
-    // -- List sessions --
+    #[test]
+    fn capture_lines_rejects_bad_regex() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        let (resp, effect) = handle_message(
+            &mut s,
+            Message::CaptureLines {
+                id: 5,
+                session: "s1".into(),
+                last: 10,
+                grep: Some("(".into()),
+                register: None,
+            },
+            c,
+        );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_regex")
+        );
+        assert!(effect.is_none());
+    }
 
     #[test]
-    fn list_sessions_returns_descriptors() {
+    fn scrollback_reply_to_unknown_query_ignored() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        let (resp, _) = handle_message(&mut s, Message::ListSessions { id: 2 }, c);
-        match resp {
-            Message::Response { sessions, .. } => {
-                let sessions = sessions.unwrap();
-                assert_eq!(sessions.len(), 1);
-                assert_eq!(sessions[0].session, "s1");
-            }
-            _ => panic!("expected Response"),
-        }
+        let (_, effect) = handle_message(
+            &mut s,
+            Message::ScrollbackLines {
+                id: 2,
+                session: "s1".into(),
+                query: 99,
+                content: b"late".to_vec(),
+            },
+            c,
+        );
+        assert!(effect.is_none());
+        assert!(s.relay_content(None).is_err());
     }
 
-    // -- Unknown type --
+    // -- Session control --
 
     #[test]
-    fn server_messages_return_unknown_type() {
+    fn signal_routed_to_wrapper() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
-
-        // HelloAck from client → unknown_type
-        let (resp, _) = handle_message(
+        handle_message(&mut s, register(1, "s1", 100), c);
+        let (resp, effect) = handle_message(
             &mut s,
-            Message::HelloAck {
-                id: 1,
-                status: Status::Ok,
-                error: None,
+            Message::Signal {
+                id: 2,
+                session: "s1".into(),
+                signal: "SIGINT".into(),
             },
             c,
         );
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("unknown_type"));
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
             }
-            _ => panic!("expected Response"),
-        }
-
-        // Inject from client → unknown_type
-        let (resp, _) = handle_message(
-            &mut s,
-            Message::Inject {
-                id: 2,
-                content: vec![],
-            },
-            c,
-        );
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("unknown_type"));
+        ));
+        match effect {
+            Some(SideEffect::Inject { action, request_id }) => {
+                assert_eq!(request_id, 2);
+                assert_eq!(action.target_connection, c);
+                assert_eq!(
+                    action.message,
+                    Message::Signal {
+                        id: 0,
+                        session: "s1".into(),
+                        signal: "SIGINT".into(),
+                    }
+                );
             }
-            _ => panic!("expected Response"),
+            other => panic!("expected SideEffect::Inject, got {other:?}"),
         }
     }
 
-    // -- Response ID echoing --
-
     #[test]
-    fn response_echoes_request_id() {
+    fn signal_rejects_unknown_name() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-
-        let (resp, _) = handle_message(
+        let (resp, effect) = handle_message(
             &mut s,
-            Message::TurnCompleted {
-                id: 42,
+            Message::Signal {
+                id: 2,
                 session: "s1".into(),
-                content: b"data".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
+                signal: "SIGNOPE".into(),
             },
             c,
         );
-        match resp {
-            Message::Response { id, .. } => assert_eq!(id, 42),
-            _ => panic!("expected Response"),
-        }
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_signal")
+        );
+        assert!(effect.is_none());
     }
 
-    // -- Turn ID in responses --
-
     #[test]
-    fn turn_completed_response_includes_turn_id() {
+    fn resize_rejects_zero_size() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-
-        let (resp, _) = handle_message(
+        let (resp, effect) = handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::Resize {
                 id: 2,
                 session: "s1".into(),
-                content: b"data".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
+                cols: 0,
+                rows: 24,
             },
             c,
         );
-        match resp {
-            Message::Response {
-                status, turn_id, ..
-            } => {
-                assert_eq!(status, Status::Ok);
-                assert_eq!(turn_id, Some("s1:1".into()));
-            }
-            _ => panic!("expected Response"),
-        }
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_size")
+        );
+        assert!(effect.is_none());
     }
 
     #[test]
-    fn capture_response_includes_turn_id() {
+    fn record_start_requires_absolute_path() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        handle_message(
+        let (resp, effect) = handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::RecordStart {
                 id: 2,
                 session: "s1".into(),
-                content: b"data".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
+                path: "out.cast".into(),
             },
             c,
         );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_path")
+        );
+        assert!(effect.is_none());
 
-        let (resp, _) = handle_message(
+        let (_, effect) = handle_message(
             &mut s,
-            Message::Capture {
+            Message::RecordStart {
                 id: 3,
                 session: "s1".into(),
+                path: "/tmp/out.cast".into(),
             },
             c,
         );
-        match resp {
-            Message::Response {
-                status,
-                size,
-                turn_id,
-                ..
-            } => {
-                assert_eq!(status, Status::Ok);
-                assert_eq!(size, Some(4));
-                assert_eq!(turn_id, Some("s1:1".into()));
-            }
-            _ => panic!("expected Response"),
+        match effect {
+            Some(SideEffect::Inject { action, .. }) => assert!(matches!(
+                action.message,
+                Message::RecordStart { id: 0, ref path, .. } if path == "/tmp/out.cast"
+            )),
+            other => panic!("expected record_start inject, got {other:?}"),
         }
     }
 
     #[test]
-    fn interrupted_flag_stored_via_handler() {
+    fn kill_ended_session_fails() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-
-        let (resp, _) = handle_message(
+        s.deregister_session("s1", Some(0), None, 1000);
+        let (resp, effect) = handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::Kill {
                 id: 2,
                 session: "s1".into(),
-                content: b"data".to_vec(),
-                interrupted: true,
-                timestamp: 1000,
             },
             c,
         );
-        assert!(matches!(
-            resp,
-            Message::Response {
-                status: Status::Ok,
-                ..
-            }
-        ));
-
-        // Verify the interrupted flag was actually stored.
-        let turns = s.list_turns("s1", None).unwrap();
-        assert!(turns[0].interrupted);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "session_ended")
+        );
+        assert!(effect.is_none());
     }
 
+    // -- Snapshots --
+
     #[test]
-    fn turn_id_increments_across_turns() {
+    fn snapshot_save_then_load_into_another_broker() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-
-        let (r1, _) = handle_message(
+        s.store_turn(
+            "s1",
+            b"out".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        let (resp, _) = handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::SnapshotSave {
                 id: 2,
-                session: "s1".into(),
-                content: b"a".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
-            },
-            c,
-        );
-        let (r2, _) = handle_message(
-            &mut s,
-            Message::TurnCompleted {
-                id: 3,
-                session: "s1".into(),
-                content: b"b".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
+                sessions: vec!["s1".into()],
             },
             c,
         );
+        let Message::Response {
+            content: Some(content),
+            ..
+        } = resp
+        else {
+            panic!("expected snapshot content, got {resp:?}");
+        };
 
-        match (r1, r2) {
-            (
-                Message::Response {
-                    turn_id: Some(t1), ..
-                },
-                Message::Response {
-                    turn_id: Some(t2), ..
-                },
-            ) => {
-                assert_eq!(t1, "s1:1");
-                assert_eq!(t2, "s1:2");
+        let (mut other, c2) = fresh();
+        let (resp, _) = handle_message(&mut other, Message::SnapshotLoad { id: 3, content }, c2);
+        match resp {
+            Message::Response {
+                status: Status::Ok,
+                sessions: Some(sessions),
+                ..
+            } => {
+                assert_eq!(sessions.len(), 1);
+                assert_eq!(sessions[0].state, AgentState::Archived);
             }
-            _ => panic!("expected Responses with turn_ids"),
+            other => panic!("expected loaded sessions, got {other:?}"),
         }
+        assert_eq!(other.get_turn("s1:1").unwrap().content, b"out");
     }
 
-    // -- GetTurn --
+    // -- Replay --
 
-    /// Helper: set up a session with a single turn and return state + connection.
-    fn setup_with_turn() -> (BrokerState, ConnectionId) {
+    /// `s1` (ended) with turns "one", "two\n", "three"; live target `s2`.
+    fn replay_fixture() -> (BrokerState, ConnectionId) {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        handle_message(
-            &mut s,
-            Message::TurnCompleted {
-                id: 2,
-                session: "s1".into(),
-                content: b"hello world".to_vec(),
-                interrupted: false,
-                timestamp: 5000,
-            },
-            c,
-        );
+        handle_message(&mut s, register(2, "s2", 200), c);
+        for (i, content) in [&b"one"[..], b"two\n", b"three"].into_iter().enumerate() {
+            s.store_turn(
+                "s1",
+                content.to_vec(),
+                false,
+                1000 + i as u64,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
+        }
+        s.deregister_session("s1", Some(0), None, 2000);
         (s, c)
     }
 
+    fn replay(from: Option<&str>, snapshot: Option<Vec<u8>>, last: u32, frame: bool) -> Message {
+        Message::Replay {
+            id: 9,
+            session: "s2".into(),
+            from: from.map(String::from),
+            snapshot,
+            last,
+            frame,
+        }
+    }
+
+    fn injected(effect: Option<SideEffect>) -> Vec<u8> {
+        match effect {
+            Some(SideEffect::Inject {
+                action:
+                    InjectAction {
+                        message: Message::Inject { content, .. },
+                        ..
+                    },
+                ..
+            }) => content,
+            other => panic!("expected inject, got {other:?}"),
+        }
+    }
+
     #[test]
-    fn get_turn_success() {
-        let (mut s, c) = setup_with_turn();
-        let (resp, _) = handle_message(
-            &mut s,
-            Message::GetTurn {
-                id: 10,
-                turn_id: "s1:1".into(),
-            },
-            c,
-        );
+    fn replay_injects_last_turns_oldest_first() {
+        let (mut s, c) = replay_fixture();
+        let (resp, effect) = handle_message(&mut s, replay(Some("s1"), None, 2, false), c);
+        assert_eq!(injected(effect), b"two\n\nthree\n");
         match resp {
             Message::Response {
-                id,
-                status,
-                turn_id,
-                content,
-                timestamp,
-                byte_length,
-                interrupted,
-                truncated,
-                ..
+                turns: Some(turns), ..
             } => {
-                assert_eq!(id, 10);
-                assert_eq!(status, Status::Ok);
-                assert_eq!(turn_id, Some("s1:1".into()));
-                assert_eq!(content, Some(b"hello world".to_vec()));
-                assert_eq!(timestamp, Some(5000));
-                assert_eq!(byte_length, Some(11));
-                assert_eq!(interrupted, Some(false));
-                assert_eq!(truncated, Some(false));
+                let ids: Vec<_> = turns.iter().map(|t| t.turn_id.as_str()).collect();
+                assert_eq!(ids, ["s1:2", "s1:3"]);
             }
-            _ => panic!("expected Response"),
+            other => panic!("expected turns, got {other:?}"),
         }
     }
 
     #[test]
-    fn get_turn_not_found() {
-        let (mut s, c) = setup_with_turn();
-        let (resp, _) = handle_message(
-            &mut s,
-            Message::GetTurn {
-                id: 10,
-                turn_id: "s1:999".into(),
+    fn replay_frames_each_turn() {
+        let (mut s, c) = replay_fixture();
+        let (_, effect) = handle_message(&mut s, replay(Some("s1"), None, 2, true), c);
+        assert_eq!(
+            injected(effect),
+            b"--- s1:2 ---\ntwo\n\n--- s1:3 ---\nthree\n"
+        );
+    }
+
+    #[test]
+    fn replay_from_snapshot() {
+        let (mut s, c) = replay_fixture();
+        let data = s.snapshot(&["s1".into()], 3000).unwrap().encode();
+        let (_, effect) = handle_message(&mut s, replay(None, Some(data), 1, false), c);
+        assert_eq!(injected(effect), b"three\n");
+    }
+
+    #[test]
+    fn replay_rejects_zero_count_and_ended_target() {
+        let (mut s, c) = replay_fixture();
+        let (resp, _) = handle_message(&mut s, replay(Some("s1"), None, 0, false), c);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_count")
+        );
+        let msg = Message::Replay {
+            id: 9,
+            session: "s1".into(),
+            from: Some("s2".into()),
+            snapshot: None,
+            last: 1,
+            frame: false,
+        };
+        let (resp, effect) = handle_message(&mut s, msg, c);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "session_ended")
+        );
+        assert!(effect.is_none());
+    }
+
+    #[test]
+    fn snapshot_load_rejects_garbage() {
+        let (mut s, c) = fresh();
+        let msg = Message::SnapshotLoad {
+            id: 1,
+            content: b"junk".to_vec(),
+        };
+        let (resp, _) = handle_message(&mut s, msg, c);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_snapshot")
+        );
+    }
+
+    // -- Output tap --
+
+    fn client_hello(s: &mut BrokerState, c: ConnectionId) {
+        handle_message(
+            s,
+            Message::Hello {
+                id: 0,
+                version: PROTOCOL_VERSION,
+                role: Role::Client,
             },
             c,
         );
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("turn_not_found"));
+    }
+
+    fn subscribe(s: &mut BrokerState, id: u32, c: ConnectionId) -> Option<SideEffect> {
+        let msg = Message::Subscribe {
+            id,
+            session: "s1".into(),
+        };
+        let (resp, effect) = handle_message(s, msg, c);
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
             }
-            _ => panic!("expected Response"),
-        }
+        ));
+        effect
     }
 
-    // -- ListTurns --
+    fn output(id: u32) -> Message {
+        Message::Output {
+            id,
+            session: "s1".into(),
+            content: b"hi".to_vec(),
+        }
+    }
 
     #[test]
-    fn list_turns_success() {
-        let (mut s, c) = fresh();
-        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
-        handle_message(&mut s, register(1, "s1", 100), c);
-        for i in 0..3 {
-            handle_message(
-                &mut s,
-                Message::TurnCompleted {
-                    id: 2 + i,
-                    session: "s1".into(),
-                    content: format!("turn-{i}").into_bytes(),
-                    interrupted: false,
-                    timestamp: 1000 + u64::from(i),
-                },
-                c,
-            );
+    fn first_subscribe_enables_output_tap() {
+        let (mut s, w) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        let (c1, c2) = (ConnectionId::new(), ConnectionId::new());
+        client_hello(&mut s, c1);
+        client_hello(&mut s, c2);
+        match subscribe(&mut s, 1, c1) {
+            Some(SideEffect::Inject { action, request_id }) => {
+                assert_eq!(request_id, 1);
+                assert_eq!(action.target_connection, w);
+                assert!(matches!(
+                    action.message,
+                    Message::OutputTap { enabled: true, .. }
+                ));
+            }
+            other => panic!("expected output_tap inject, got {other:?}"),
         }
+        assert!(subscribe(&mut s, 1, c2).is_none());
+    }
 
-        let (resp, _) = handle_message(
+    #[test]
+    fn output_fans_out_to_subscribers() {
+        let (mut s, w) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        let c = ConnectionId::new();
+        client_hello(&mut s, c);
+        subscribe(&mut s, 1, c);
+        let (resp, effect) = handle_message(&mut s, output(2), w);
+        assert!(matches!(
+            resp,
+            Message::Response {
+                id: 2,
+                status: Status::Ok,
+                ..
+            }
+        ));
+        match effect {
+            Some(SideEffect::Broadcast { actions }) => {
+                assert_eq!(actions.len(), 1);
+                assert_eq!(actions[0].target_connection, c);
+                assert!(matches!(
+                    actions[0].message,
+                    Message::Output { id: 0, ref content, .. } if content == b"hi"
+                ));
+            }
+            other => panic!("expected broadcast, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn output_without_subscribers_disables_tap() {
+        let (mut s, w) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        let (_, effect) = handle_message(&mut s, output(2), w);
+        match effect {
+            Some(SideEffect::Broadcast { actions }) => {
+                assert_eq!(actions[0].target_connection, w);
+                assert!(matches!(
+                    actions[0].message,
+                    Message::OutputTap { enabled: false, .. }
+                ));
+            }
+            other => panic!("expected tap disable, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn output_rejected_from_client() {
+        let (mut s, c) = fresh();
+        client_hello(&mut s, c);
+        let (resp, effect) = handle_message(&mut s, output(1), c);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "unknown_type")
+        );
+        assert!(effect.is_none());
+    }
+
+    #[test]
+    fn deregister_ends_subscribers_output() {
+        let (mut s, w) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        let c = ConnectionId::new();
+        client_hello(&mut s, c);
+        subscribe(&mut s, 1, c);
+        let (_, effect) = handle_message(
             &mut s,
-            Message::ListTurns {
-                id: 10,
+            Message::Deregister {
+                id: 2,
                 session: "s1".into(),
-                limit: None,
+                exit_code: Some(0),
+                reason: None,
+            },
+            w,
+        );
+        match effect {
+            Some(SideEffect::Broadcast { actions }) => {
+                assert_eq!(actions[0].target_connection, c);
+                assert!(matches!(actions[0].message, Message::OutputEnd { .. }));
+            }
+            other => panic!("expected output_end, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn disconnect_cleans_up_output_tap() {
+        let (mut s, w) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        let c = ConnectionId::new();
+        client_hello(&mut s, c);
+        subscribe(&mut s, 1, c);
+
+        // The last subscriber leaving disables the wrapper's tap.
+        let actions = handle_disconnect(&mut s, c, 1000);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].target_connection, w);
+        assert!(matches!(
+            actions[0].message,
+            Message::OutputTap { enabled: false, .. }
+        ));
+
+        // The wrapper leaving ends its subscribers' output.
+        let c2 = ConnectionId::new();
+        client_hello(&mut s, c2);
+        subscribe(&mut s, 2, c2);
+        let actions = handle_disconnect(&mut s, w, 2000);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].target_connection, c2);
+        assert!(matches!(actions[0].message, Message::OutputEnd { .. }));
+    }
+
+    #[test]
+    fn paste_buffer_empty() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        let (resp, inject) = handle_message(
+            &mut s,
+            Message::Paste {
+                id: 2,
+                session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: None,
+                template: None,
             },
             c,
         );
+        assert!(inject.is_none());
         match resp {
-            Message::Response {
-                status, turns, id, ..
-            } => {
-                assert_eq!(id, 10);
-                assert_eq!(status, Status::Ok);
-                let turns = turns.unwrap();
-                assert_eq!(turns.len(), 3);
-                // Newest first.
-                assert_eq!(turns[0].turn_id, "s1:3");
-                assert_eq!(turns[1].turn_id, "s1:2");
-                assert_eq!(turns[2].turn_id, "s1:1");
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("buffer_empty"));
             }
             _ => panic!("expected Response"),
         }
     }
 
+    // -- List sessions --
+
     #[test]
-    fn list_turns_with_limit() {
+    fn list_sessions_returns_descriptors() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        for i in 0..5 {
-            handle_message(
-                &mut s,
-                Message::TurnCompleted {
-                    id: 2 + i,
-                    session: "s1".into(),
-                    content: b"x".to_vec(),
-                    interrupted: false,
-                    timestamp: 1000,
-                },
-                c,
-            );
+        let (resp, _) = handle_message(&mut s, Message::ListSessions { id: 2, all: false }, c);
+        match resp {
+            Message::Response { sessions, .. } => {
+                let sessions = sessions.unwrap();
+                assert_eq!(sessions.len(), 1);
+                assert_eq!(sessions[0].session, "s1");
+            }
+            _ => panic!("expected Response"),
         }
+    }
 
+    #[test]
+    fn list_sessions_reads_cwd_and_input_activity() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        // Register with our own PID so /proc/<pid>/cwd is readable.
+        handle_message(&mut s, register(1, "s1", std::process::id()), c);
         let (resp, _) = handle_message(
             &mut s,
-            Message::ListTurns {
-                id: 10,
+            Message::InputActivity {
+                id: 2,
                 session: "s1".into(),
-                limit: Some(2),
+                timestamp: 1234,
             },
             c,
         );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+        let (resp, _) = handle_message(&mut s, Message::ListSessions { id: 3, all: false }, c);
         match resp {
-            Message::Response { turns, .. } => {
-                assert_eq!(turns.unwrap().len(), 2);
+            Message::Response { sessions, .. } => {
+                let sessions = sessions.unwrap();
+                let cwd = std::env::current_dir().unwrap();
+                assert_eq!(sessions[0].cwd, Some(cwd.display().to_string()));
+                assert_eq!(sessions[0].last_input_at, Some(1234));
+                assert!(sessions[0].started_at > 0, "falls back to receipt time");
             }
             _ => panic!("expected Response"),
         }
     }
 
+    // -- Unknown type --
+
     #[test]
-    fn list_turns_session_not_found() {
+    fn server_messages_return_unknown_type() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+
+        // HelloAck from client → unknown_type
         let (resp, _) = handle_message(
             &mut s,
-            Message::ListTurns {
-                id: 10,
-                session: "nonexistent".into(),
-                limit: None,
+            Message::HelloAck {
+                id: 1,
+                status: Status::Ok,
+                error: None,
             },
             c,
         );
         match resp {
             Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("session_not_found"));
+                assert_eq!(error.as_deref(), Some("unknown_type"));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        // Inject from client → unknown_type
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::Inject {
+                id: 2,
+                content: vec![],
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("unknown_type"));
             }
             _ => panic!("expected Response"),
         }
     }
 
-    // -- CaptureByID --
+    // -- Response ID echoing --
 
     #[test]
-    fn capture_by_id_success() {
+    fn response_echoes_request_id() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
         handle_message(&mut s, register(1, "s1", 100), c);
-        handle_message(
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 42,
+                session: "s1".into(),
+                content: b"data".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { id, .. } => assert_eq!(id, 42),
+            _ => panic!("expected Response"),
+        }
+    }
+
+    // -- Turn ID in responses --
+
+    #[test]
+    fn turn_completed_response_includes_turn_id() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+
+        let (resp, _) = handle_message(
             &mut s,
             Message::TurnCompleted {
                 id: 2,
                 session: "s1".into(),
-                content: b"first".to_vec(),
+                content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
+        match resp {
+            Message::Response {
+                status, turn_id, ..
+            } => {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(turn_id, Some("s1:1".into()));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn capture_response_includes_turn_id() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
         handle_message(
             &mut s,
             Message::TurnCompleted {
-                id: 3,
+                id: 2,
                 session: "s1".into(),
-                content: b"second".to_vec(),
+                content: b"data".to_vec(),
                 interrupted: false,
-                timestamp: 2000,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
 
-        // Capture the first turn, not the latest.
         let (resp, _) = handle_message(
             &mut s,
-            Message::CaptureByID {
-                id: 10,
-                turn_id: "s1:1".into(),
+            Message::Capture {
+                id: 3,
+                session: "s1".into(),
+                register: None,
             },
             c,
         );
@@ -1284,7 +2744,7 @@ mod tests {
                 ..
             } => {
                 assert_eq!(status, Status::Ok);
-                assert_eq!(size, Some(5)); // b"first".len()
+                assert_eq!(size, Some(4));
                 assert_eq!(turn_id, Some("s1:1".into()));
             }
             _ => panic!("expected Response"),
@@ -1292,26 +2752,562 @@ mod tests {
     }
 
     #[test]
-    fn capture_by_id_not_found() {
-        let (mut s, c) = setup_with_turn();
+    fn interrupted_flag_stored_via_handler() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+
         let (resp, _) = handle_message(
             &mut s,
-            Message::CaptureByID {
-                id: 10,
-                turn_id: "s1:999".into(),
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"data".to_vec(),
+                interrupted: true,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c,
         );
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("turn_not_found"));
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        // Verify the interrupted flag was actually stored.
+        let turns = s.list_turns("s1", None).unwrap();
+        assert!(turns[0].interrupted);
+    }
+
+    #[test]
+    fn turn_id_increments_across_turns() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+
+        let (r1, _) = handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"a".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+        let (r2, _) = handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 3,
+                session: "s1".into(),
+                content: b"b".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+
+        match (r1, r2) {
+            (
+                Message::Response {
+                    turn_id: Some(t1), ..
+                },
+                Message::Response {
+                    turn_id: Some(t2), ..
+                },
+            ) => {
+                assert_eq!(t1, "s1:1");
+                assert_eq!(t2, "s1:2");
+            }
+            _ => panic!("expected Responses with turn_ids"),
+        }
+    }
+
+    // -- GetTurn --
+
+    /// Helper: set up a session with a single turn and return state + connection.
+    fn setup_with_turn() -> (BrokerState, ConnectionId) {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"hello world".to_vec(),
+                interrupted: false,
+                timestamp: 5000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+        (s, c)
+    }
+
+    #[test]
+    fn get_turn_success() {
+        let (mut s, c) = setup_with_turn();
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 10,
+                turn_id: "s1:1".into(),
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                id,
+                status,
+                turn_id,
+                content,
+                timestamp,
+                byte_length,
+                interrupted,
+                truncated,
+                ..
+            } => {
+                assert_eq!(id, 10);
+                assert_eq!(status, Status::Ok);
+                assert_eq!(turn_id, Some("s1:1".into()));
+                assert_eq!(content, Some(b"hello world".to_vec()));
+                assert_eq!(timestamp, Some(5000));
+                assert_eq!(byte_length, Some(11));
+                assert_eq!(interrupted, Some(false));
+                assert_eq!(truncated, Some(false));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn get_turn_not_found() {
+        let (mut s, c) = setup_with_turn();
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 10,
+                turn_id: "s1:999".into(),
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("turn_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    // -- ListTurns --
+
+    #[test]
+    fn list_turns_success() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        for i in 0..3 {
+            handle_message(
+                &mut s,
+                Message::TurnCompleted {
+                    id: 2 + i,
+                    session: "s1".into(),
+                    content: format!("turn-{i}").into_bytes(),
+                    interrupted: false,
+                    timestamp: 1000 + u64::from(i),
+                    seq: None,
+                    context: Default::default(),
+                    replayed: false,
+                },
+                c,
+            );
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 10,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                status, turns, id, ..
+            } => {
+                assert_eq!(id, 10);
+                assert_eq!(status, Status::Ok);
+                let turns = turns.unwrap();
+                assert_eq!(turns.len(), 3);
+                // Newest first.
+                assert_eq!(turns[0].turn_id, "s1:3");
+                assert_eq!(turns[1].turn_id, "s1:2");
+                assert_eq!(turns[2].turn_id, "s1:1");
             }
             _ => panic!("expected Response"),
         }
     }
 
-    #[test]
-    fn capture_by_id_then_paste() {
+    #[test]
+    fn list_turns_with_limit() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        for i in 0..5 {
+            handle_message(
+                &mut s,
+                Message::TurnCompleted {
+                    id: 2 + i,
+                    session: "s1".into(),
+                    content: b"x".to_vec(),
+                    interrupted: false,
+                    timestamp: 1000,
+                    seq: None,
+                    context: Default::default(),
+                    replayed: false,
+                },
+                c,
+            );
+        }
+
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 10,
+                session: "s1".into(),
+                limit: Some(2),
+            },
+            c,
+        );
+        match resp {
+            Message::Response { turns, .. } => {
+                assert_eq!(turns.unwrap().len(), 2);
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn list_turns_session_not_found() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 10,
+                session: "nonexistent".into(),
+                limit: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("session_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    // -- CaptureByID --
+
+    #[test]
+    fn capture_by_id_success() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"first".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 3,
+                session: "s1".into(),
+                content: b"second".to_vec(),
+                interrupted: false,
+                timestamp: 2000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c,
+        );
+
+        // Capture the first turn, not the latest.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::CaptureByID {
+                id: 10,
+                turn_id: "s1:1".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response {
+                status,
+                size,
+                turn_id,
+                ..
+            } => {
+                assert_eq!(status, Status::Ok);
+                assert_eq!(size, Some(5)); // b"first".len()
+                assert_eq!(turn_id, Some("s1:1".into()));
+            }
+            _ => panic!("expected Response"),
+        }
+
+        // Both turns, combined with a separator.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::CaptureByID {
+                id: 11,
+                turn_id: "s1:1".into(),
+                turn_ids: vec!["s1:2".into()],
+                separator: Some("\n--\n".into()),
+                register: None,
+            },
+            c,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response { size: Some(15), turn_id: Some(ref id), .. } if id == "s1:1,2"
+        ));
+    }
+
+    #[test]
+    fn capture_by_id_not_found() {
+        let (mut s, c) = setup_with_turn();
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::CaptureByID {
+                id: 10,
+                turn_id: "s1:999".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
+            },
+            c,
+        );
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("turn_not_found"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn capture_by_id_then_paste() {
+        let (mut s, c1) = fresh();
+        let c2 = ConnectionId::new();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c1);
+        handle_message(
+            &mut s,
+            Message::Hello {
+                id: 0,
+                version: PROTOCOL_VERSION,
+                role: Role::Client,
+            },
+            c2,
+        );
+        handle_message(&mut s, register(1, "s1", 100), c1);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"first".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c1,
+        );
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 3,
+                session: "s1".into(),
+                content: b"second".to_vec(),
+                interrupted: false,
+                timestamp: 2000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            c1,
+        );
+
+        // Capture the first turn by ID.
+        handle_message(
+            &mut s,
+            Message::CaptureByID {
+                id: 4,
+                turn_id: "s1:1".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
+            },
+            c2,
+        );
+
+        // Paste — should inject the first turn's content.
+        let (resp, inject) = handle_message(
+            &mut s,
+            Message::Paste {
+                id: 5,
+                session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: None,
+                template: None,
+            },
+            c2,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+        let effect = inject.expect("paste should produce SideEffect");
+        match effect {
+            SideEffect::Inject { action, .. } => match action.message {
+                Message::Inject { content, .. } => {
+                    assert_eq!(content, b"first");
+                }
+                _ => panic!("expected Inject message"),
+            },
+            _ => panic!("expected SideEffect::Inject"),
+        }
+    }
+
+    #[test]
+    fn v1_queries_work_from_client_role() {
+        let (mut s, c) = fresh();
+        // Connect as Client (not Wrapper).
+        handle_message(
+            &mut s,
+            Message::Hello {
+                id: 0,
+                version: PROTOCOL_VERSION,
+                role: Role::Client,
+            },
+            c,
+        );
+
+        // Need a wrapper to register a session.
+        let w = ConnectionId::new();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
+        handle_message(&mut s, register(1, "s1", 100), w);
+        handle_message(
+            &mut s,
+            Message::TurnCompleted {
+                id: 2,
+                session: "s1".into(),
+                content: b"data".to_vec(),
+                interrupted: false,
+                timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
+            },
+            w,
+        );
+
+        // Client can use GetTurn.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::GetTurn {
+                id: 10,
+                turn_id: "s1:1".into(),
+            },
+            c,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        // Client can use ListTurns.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::ListTurns {
+                id: 11,
+                session: "s1".into(),
+                limit: None,
+            },
+            c,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        // Client can use CaptureByID.
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::CaptureByID {
+                id: 12,
+                turn_id: "s1:1".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
+            },
+            c,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+    }
+
+    // -- Deliver --
+
+    /// Helper: set up wrapper + client + session with a captured turn.
+    fn setup_with_captured_turn() -> (BrokerState, ConnectionId, ConnectionId) {
         let (mut s, c1) = fresh();
         let c2 = ConnectionId::new();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c1);
@@ -1330,101 +3326,512 @@ mod tests {
             Message::TurnCompleted {
                 id: 2,
                 session: "s1".into(),
-                content: b"first".to_vec(),
+                content: b"turn data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
             c1,
         );
         handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::Capture {
                 id: 3,
                 session: "s1".into(),
-                content: b"second".to_vec(),
-                interrupted: false,
-                timestamp: 2000,
+                register: None,
             },
-            c1,
+            c2,
+        );
+        (s, c1, c2)
+    }
+
+    #[test]
+    fn deliver_inject_success() {
+        let (mut s, c1, c2) = setup_with_captured_turn();
+        let (resp, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 10,
+                sink: "inject".into(),
+                sinks: Vec::new(),
+                session: Some("s1".into()),
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
+            },
+            c2,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+        match effect.expect("should produce SideEffect") {
+            SideEffect::Inject { action, request_id } => {
+                assert_eq!(request_id, 10);
+                assert_eq!(action.target_connection, c1);
+                match action.message {
+                    Message::Inject { content, .. } => {
+                        assert_eq!(content, b"turn data");
+                    }
+                    _ => panic!("expected Inject message"),
+                }
+            }
+            _ => panic!("expected SideEffect::Inject"),
+        }
+    }
+
+    #[test]
+    fn deliver_inject_missing_session() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let (resp, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 10,
+                sink: "inject".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
+            },
+            c2,
+        );
+        assert!(effect.is_none());
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("missing_field"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn deliver_clipboard_success() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let (resp, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 10,
+                sink: "clipboard".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
+            },
+            c2,
+        );
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+        match effect.expect("should produce SideEffect") {
+            SideEffect::Clipboard {
+                content,
+                metadata,
+                request_id,
+            } => {
+                assert_eq!(content, b"turn data");
+                assert_eq!(request_id, 10);
+                assert_eq!(metadata.turn_id, "s1:1");
+                assert_eq!(metadata.timestamp, 1000);
+                assert_eq!(metadata.byte_length, 9);
+                assert!(!metadata.interrupted);
+            }
+            _ => panic!("expected SideEffect::Clipboard"),
+        }
+    }
+
+    #[test]
+    fn paste_and_deliver_render_named_template() {
+        use crate::broker::state::TemplateConfig;
+        let (s, c1, c2) = setup_with_captured_turn();
+        let mut s = s.with_templates(TemplateConfig {
+            templates: [(
+                "review".to_string(),
+                "From {{session}} ({{turn_id}}):\n{{content}}".to_string(),
+            )]
+            .into(),
+        });
+        let paste = |template: &str| Message::Paste {
+            id: 11,
+            session: "s1".into(),
+            sessions: Vec::new(),
+            role: None,
+            register: None,
+            template: Some(template.into()),
+        };
+        let (_, effect) = handle_message(&mut s, paste("review"), c2);
+        match effect {
+            Some(SideEffect::Inject { action, .. }) => {
+                assert_eq!(action.target_connection, c1);
+                assert!(matches!(
+                    action.message,
+                    Message::Inject { ref content, .. } if content == b"From s1 (s1:1):\nturn data"
+                ));
+            }
+            other => panic!("expected inject, got {other:?}"),
+        }
+        let (resp, effect) = handle_message(&mut s, paste("nope"), c2);
+        assert!(effect.is_none());
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "template_not_found")
+        );
+
+        let (_, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 12,
+                sink: "clipboard".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: Some("review".into()),
+            },
+            c2,
+        );
+        match effect {
+            Some(SideEffect::Clipboard {
+                content, metadata, ..
+            }) => {
+                assert_eq!(content, b"From s1 (s1:1):\nturn data");
+                assert_eq!(metadata.byte_length, 9);
+            }
+            other => panic!("expected clipboard, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn deliver_clipboard_buffer_empty() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        let (resp, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 10,
+                sink: "clipboard".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
+            },
+            c,
+        );
+        assert!(effect.is_none());
+        match resp {
+            Message::Response { error, .. } => {
+                assert_eq!(error.as_deref(), Some("buffer_empty"));
+            }
+            _ => panic!("expected Response"),
+        }
+    }
+
+    #[test]
+    fn deliver_from_named_register() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 10,
+                sink: "clipboard".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: Some("a".into()),
+                template: None,
+            },
+            c2,
+        );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "buffer_empty")
         );
 
-        // Capture the first turn by ID.
         handle_message(
             &mut s,
             Message::CaptureByID {
-                id: 4,
+                id: 11,
                 turn_id: "s1:1".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: Some("a".into()),
+            },
+            c2,
+        );
+        let (_, effect) = handle_message(
+            &mut s,
+            Message::Deliver {
+                id: 12,
+                sink: "clipboard".into(),
+                sinks: Vec::new(),
+                session: None,
+                sessions: Vec::new(),
+                role: None,
+                path: None,
+                turn_id: None,
+                separator: None,
+                register: Some("a".into()),
+                template: None,
             },
             c2,
         );
+        assert!(matches!(
+            effect,
+            Some(SideEffect::Clipboard { ref content, .. }) if content == b"turn data"
+        ));
 
-        // Paste — should inject the first turn's content.
-        let (resp, inject) = handle_message(
+        let (resp, _) = handle_message(&mut s, Message::ListRegisters { id: 13 }, c2);
+        match resp {
+            Message::Response {
+                registers: Some(registers),
+                ..
+            } => {
+                let names: Vec<_> = registers.iter().map(|r| r.register.as_deref()).collect();
+                assert_eq!(names, [None, Some("a")]);
+            }
+            other => panic!("expected registers, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn deliver_turn_by_reference() {
+        let (mut s, c1, c2) = setup_with_captured_turn();
+        handle_message(
             &mut s,
-            Message::Paste {
-                id: 5,
-                session: "s1".into(),
+            Message::RelayClear {
+                id: 9,
+                register: None,
             },
             c2,
         );
+        let deliver = |turn_id: &str| Message::Deliver {
+            id: 10,
+            sink: "inject".into(),
+            sinks: Vec::new(),
+            session: Some("s1".into()),
+            sessions: Vec::new(),
+            role: None,
+            path: None,
+            turn_id: Some(turn_id.into()),
+            separator: None,
+            register: None,
+            template: None,
+        };
+        let (resp, effect) = handle_message(&mut s, deliver("s1:@latest"), c2);
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+        match effect {
+            Some(SideEffect::Inject { action, .. }) => {
+                assert_eq!(action.target_connection, c1);
+                assert!(
+                    matches!(action.message, Message::Inject { ref content, .. } if content == b"turn data")
+                );
+            }
+            other => panic!("expected inject, got {other:?}"),
+        }
+
+        let (resp, _) = handle_message(&mut s, deliver("s1:-1"), c2);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "turn_not_found")
+        );
+        let (resp, _) = handle_message(&mut s, deliver("@focused:-1"), c2);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "unresolved_focus")
+        );
+    }
+
+    #[test]
+    fn paste_many_reports_each_target() {
+        let (mut s, c1, c2) = setup_with_captured_turn();
+        let paste = Message::Paste {
+            id: 11,
+            session: String::new(),
+            sessions: vec!["s1".into(), "ghost".into(), "s1".into()],
+            role: None,
+            register: None,
+            template: None,
+        };
+        let (resp, effect) = handle_message(&mut s, paste, c2);
+        let Message::Response {
+            status: Status::Ok,
+            results: Some(results),
+            ..
+        } = resp
+        else {
+            panic!("expected ok with results, got {resp:?}");
+        };
+        let targets: Vec<_> = results.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, ["inject:s1", "inject:ghost"]);
+        assert_eq!(results[1].error.as_deref(), Some("session_not_found"));
+        match effect {
+            Some(SideEffect::Deliveries { targets, .. }) => {
+                assert!(matches!(targets[0].1, Some(Delivery::Inject(conn)) if conn == c1));
+                assert!(targets[1].1.is_none());
+            }
+            other => panic!("expected deliveries, got {other:?}"),
+        }
+
+        let paste = Message::Paste {
+            id: 12,
+            session: String::new(),
+            sessions: vec!["ghost".into()],
+            role: Some("reviewer".into()),
+            register: None,
+            template: None,
+        };
+        let (resp, _) = handle_message(&mut s, paste, c2);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "role_not_found")
+        );
+    }
+
+    #[test]
+    fn deliver_many_sinks() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let deliver = |path: Option<&str>| Message::Deliver {
+            id: 13,
+            sink: "clipboard".into(),
+            sinks: vec!["file".into(), "clipboard".into()],
+            session: None,
+            sessions: Vec::new(),
+            role: None,
+            path: path.map(Into::into),
+            turn_id: None,
+            separator: None,
+            register: None,
+            template: None,
+        };
+        let (resp, effect) = handle_message(&mut s, deliver(Some("/tmp/out")), c2);
+        let Some(SideEffect::Deliveries {
+            content, targets, ..
+        }) = effect
+        else {
+            panic!("expected deliveries, got {effect:?}");
+        };
+        assert_eq!(content, b"turn data");
+        let names: Vec<_> = targets.iter().map(|(r, _)| r.target.as_str()).collect();
+        assert_eq!(names, ["clipboard", "file:/tmp/out"]);
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        let (resp, effect) = handle_message(&mut s, deliver(None), c2);
+        assert!(effect.is_none());
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "missing_field")
+        );
+    }
+
+    #[test]
+    fn deliveries_response_fails_when_no_target_succeeds() {
+        let failed = delivery_result("inject:s1".into(), Some("session_disconnected"));
+        let resp = deliveries_response(1, vec![failed.clone()]);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "all_targets_failed")
+        );
+        let ok = delivery_result("clipboard".into(), None);
+        let resp = deliveries_response(1, vec![failed, ok]);
         assert!(matches!(
             resp,
             Message::Response {
                 status: Status::Ok,
+                results: Some(ref r),
                 ..
-            }
+            } if r.len() == 2
         ));
-        let effect = inject.expect("paste should produce SideEffect");
-        match effect {
-            SideEffect::Inject { action, .. } => match action.message {
-                Message::Inject { content, .. } => {
-                    assert_eq!(content, b"first");
-                }
-                _ => panic!("expected Inject message"),
-            },
-            _ => panic!("expected SideEffect::Inject"),
-        }
     }
 
     #[test]
-    fn v1_queries_work_from_client_role() {
-        let (mut s, c) = fresh();
-        // Connect as Client (not Wrapper).
-        handle_message(
-            &mut s,
-            Message::Hello {
-                id: 0,
-                version: PROTOCOL_VERSION,
-                role: Role::Client,
+    fn route_fires_on_turn_completed() {
+        let (mut s, c1, c2) = setup_with_captured_turn();
+        let add = |pattern: &str| Message::RouteAdd {
+            id: 14,
+            route: RouteSpec {
+                source: "s1".into(),
+                target: "s1".into(),
+                template: Some("> {{content}}".into()),
+                pattern: Some(pattern.into()),
+                ..Default::default()
             },
-            c,
+            disabled: false,
+        };
+        let (resp, _) = handle_message(&mut s, add("("), c2);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_regex")
         );
+        let (resp, _) = handle_message(&mut s, add("^ok"), c2);
+        assert!(matches!(
+            resp,
+            Message::Response { routes: Some(ref r), .. } if r.len() == 1 && r[0].route == 1 && r[0].enabled
+        ));
 
-        // Need a wrapper to register a session.
-        let w = ConnectionId::new();
-        handle_message(&mut s, hello(PROTOCOL_VERSION), w);
-        handle_message(&mut s, register(1, "s1", 100), w);
-        handle_message(
-            &mut s,
-            Message::TurnCompleted {
-                id: 2,
-                session: "s1".into(),
-                content: b"data".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
-            },
-            w,
-        );
+        let completed = |content: &[u8]| Message::TurnCompleted {
+            id: 15,
+            session: "s1".into(),
+            content: content.to_vec(),
+            interrupted: false,
+            timestamp: crate::turn::epoch_millis(),
+            seq: None,
+            context: Default::default(),
+            replayed: false,
+        };
+        let (_, effect) = handle_message(&mut s, completed(b"skip me"), c1);
+        assert!(effect.is_none());
+        let (_, effect) = handle_message(&mut s, completed(b"ok go"), c1);
+        match effect {
+            Some(SideEffect::Broadcast { actions }) => {
+                assert_eq!(actions.len(), 1);
+                assert_eq!(actions[0].target_connection, c1);
+                assert!(
+                    matches!(actions[0].message, Message::Inject { ref content, .. } if content == b"> ok go")
+                );
+            }
+            other => panic!("expected broadcast, got {other:?}"),
+        }
 
-        // Client can use GetTurn.
-        let (resp, _) = handle_message(
-            &mut s,
-            Message::GetTurn {
-                id: 10,
-                turn_id: "s1:1".into(),
-            },
-            c,
-        );
+        let (resp, _) = handle_message(&mut s, Message::RouteRemove { id: 16, route: 1 }, c2);
         assert!(matches!(
             resp,
             Message::Response {
@@ -1432,17 +3839,52 @@ mod tests {
                 ..
             }
         ));
+        let (resp, _) = handle_message(&mut s, Message::RouteRemove { id: 17, route: 1 }, c2);
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "route_not_found")
+        );
+    }
 
-        // Client can use ListTurns.
-        let (resp, _) = handle_message(
+    #[test]
+    fn replayed_turns_fire_no_routes() {
+        let (mut s, c1, c2) = setup_with_captured_turn();
+        let target = ConnectionId::new();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), target);
+        handle_message(&mut s, register(4, "s2", 200), target);
+        handle_message(
             &mut s,
-            Message::ListTurns {
-                id: 11,
-                session: "s1".into(),
-                limit: None,
+            Message::RouteAdd {
+                id: 5,
+                route: RouteSpec {
+                    source: "s1".into(),
+                    target: "s2".into(),
+                    ..Default::default()
+                },
+                disabled: false,
             },
-            c,
+            c2,
+        );
+        let completed = |seq: u64, replayed: bool| Message::TurnCompleted {
+            id: 6,
+            session: "s1".into(),
+            content: b"plan".to_vec(),
+            interrupted: false,
+            timestamp: crate::turn::epoch_millis(),
+            seq: Some(seq),
+            context: Default::default(),
+            replayed,
+        };
+        let (_, effect) = handle_message(&mut s, completed(2, false), c1);
+        assert!(
+            matches!(effect, Some(SideEffect::Broadcast { ref actions }) if actions.len() == 1)
         );
+
+        // The wrapper reconnects and replays its history.
+        s.remove_connection(c1, crate::turn::epoch_millis());
+        let c3 = ConnectionId::new();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c3);
+        handle_message(&mut s, register(7, "s1", 100), c3);
+        let (resp, effect) = handle_message(&mut s, completed(2, true), c3);
         assert!(matches!(
             resp,
             Message::Response {
@@ -1450,179 +3892,180 @@ mod tests {
                 ..
             }
         ));
+        assert!(effect.is_none());
+        let (_, effect) = handle_message(&mut s, completed(3, false), c3);
+        assert!(
+            matches!(effect, Some(SideEffect::Broadcast { ref actions }) if actions.len() == 1)
+        );
+    }
 
-        // Client can use CaptureByID.
+    #[test]
+    fn gather_into_relay() {
+        let (mut s, _c1, c2) = setup_with_captured_turn();
+        let gather = |into_relay| Message::Gather {
+            id: 20,
+            turn_ids: vec!["s1:@latest".into()],
+            style: SectionStyle::Markdown,
+            into_relay,
+            register: Some("syn".into()),
+        };
+        let (resp, _) = handle_message(&mut s, gather(false), c2);
+        assert!(matches!(
+            resp,
+            Message::Response { content: Some(ref c), .. } if c == b"## s1 (s1:1)\n\nturn data\n"
+        ));
         let (resp, _) = handle_message(
             &mut s,
-            Message::CaptureByID {
-                id: 12,
-                turn_id: "s1:1".into(),
+            Message::RelayShow {
+                id: 21,
+                register: Some("syn".into()),
             },
-            c,
+            c2,
+        );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "buffer_empty")
+        );
+
+        handle_message(&mut s, gather(true), c2);
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::RelayShow {
+                id: 22,
+                register: Some("syn".into()),
+            },
+            c2,
         );
         assert!(matches!(
             resp,
-            Message::Response {
-                status: Status::Ok,
-                ..
-            }
+            Message::Response { turn_id: Some(ref t), size: Some(24), .. } if t == "s1:1"
         ));
     }
 
-    // -- Deliver --
-
-    /// Helper: set up wrapper + client + session with a captured turn.
-    fn setup_with_captured_turn() -> (BrokerState, ConnectionId, ConnectionId) {
-        let (mut s, c1) = fresh();
-        let c2 = ConnectionId::new();
-        handle_message(&mut s, hello(PROTOCOL_VERSION), c1);
-        handle_message(
+    #[test]
+    fn relay_show_returns_content_and_metadata() {
+        let (mut s, c) = fresh();
+        handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        let (resp, _) = handle_message(
             &mut s,
-            Message::Hello {
-                id: 0,
-                version: PROTOCOL_VERSION,
-                role: Role::Client,
+            Message::RelaySet {
+                id: 1,
+                content: b"hand-written".to_vec(),
+                register: None,
+                source: None,
             },
-            c2,
+            c,
         );
-        handle_message(&mut s, register(1, "s1", 100), c1);
-        handle_message(
+        assert!(
+            matches!(resp, Message::Response { turn_id: Some(ref t), size: Some(12), .. } if t == "external:set")
+        );
+        let (resp, _) = handle_message(
             &mut s,
-            Message::TurnCompleted {
+            Message::RelayShow {
                 id: 2,
-                session: "s1".into(),
-                content: b"turn data".to_vec(),
-                interrupted: false,
-                timestamp: 1000,
+                register: None,
             },
-            c1,
+            c,
         );
+        match resp {
+            Message::Response {
+                status: Status::Ok,
+                turn_id,
+                content,
+                byte_length,
+                interrupted,
+                ..
+            } => {
+                assert_eq!(turn_id.as_deref(), Some("external:set"));
+                assert_eq!(content.as_deref(), Some(&b"hand-written"[..]));
+                assert_eq!(byte_length, Some(12));
+                assert_eq!(interrupted, Some(false));
+            }
+            other => panic!("expected Response, got {other:?}"),
+        }
+
         handle_message(
             &mut s,
-            Message::Capture {
+            Message::RelayClear {
                 id: 3,
-                session: "s1".into(),
+                register: None,
             },
-            c2,
+            c,
         );
-        (s, c1, c2)
-    }
-
-    #[test]
-    fn deliver_inject_success() {
-        let (mut s, c1, c2) = setup_with_captured_turn();
-        let (resp, effect) = handle_message(
+        let (resp, _) = handle_message(
             &mut s,
-            Message::Deliver {
-                id: 10,
-                sink: "inject".into(),
-                session: Some("s1".into()),
-                path: None,
+            Message::RelayShow {
+                id: 4,
+                register: None,
             },
-            c2,
+            c,
+        );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "buffer_empty")
         );
-        assert!(matches!(
-            resp,
-            Message::Response {
-                status: Status::Ok,
-                ..
-            }
-        ));
-        match effect.expect("should produce SideEffect") {
-            SideEffect::Inject { action, request_id } => {
-                assert_eq!(request_id, 10);
-                assert_eq!(action.target_connection, c1);
-                match action.message {
-                    Message::Inject { content, .. } => {
-                        assert_eq!(content, b"turn data");
-                    }
-                    _ => panic!("expected Inject message"),
-                }
-            }
-            _ => panic!("expected SideEffect::Inject"),
-        }
     }
 
     #[test]
-    fn deliver_inject_missing_session() {
+    fn relay_undo_and_history() {
         let (mut s, _c1, c2) = setup_with_captured_turn();
-        let (resp, effect) = handle_message(
+        let (resp, _) = handle_message(
             &mut s,
-            Message::Deliver {
+            Message::RelayUndo {
                 id: 10,
-                sink: "inject".into(),
-                session: None,
-                path: None,
+                register: None,
             },
             c2,
         );
-        assert!(effect.is_none());
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("missing_field"));
-            }
-            _ => panic!("expected Response"),
-        }
-    }
+        assert!(matches!(resp, Message::Response { error: Some(ref e), .. } if e == "no_history"));
 
-    #[test]
-    fn deliver_clipboard_success() {
-        let (mut s, _c1, c2) = setup_with_captured_turn();
-        let (resp, effect) = handle_message(
+        handle_message(
             &mut s,
-            Message::Deliver {
-                id: 10,
-                sink: "clipboard".into(),
-                session: None,
-                path: None,
+            Message::RelaySet {
+                id: 11,
+                content: b"wrong".to_vec(),
+                register: None,
+                source: None,
             },
             c2,
         );
+        let (resp, _) = handle_message(&mut s, Message::RelayHistory { id: 12 }, c2);
         assert!(matches!(
             resp,
-            Message::Response {
-                status: Status::Ok,
-                ..
-            }
+            Message::Response { registers: Some(ref h), .. } if h.len() == 1 && h[0].turn_id == "s1:1"
         ));
-        match effect.expect("should produce SideEffect") {
-            SideEffect::Clipboard {
-                content,
-                metadata,
-                request_id,
-            } => {
-                assert_eq!(content, b"turn data");
-                assert_eq!(request_id, 10);
-                assert_eq!(metadata.turn_id, "s1:1");
-                assert_eq!(metadata.timestamp, 1000);
-                assert_eq!(metadata.byte_length, 9);
-                assert!(!metadata.interrupted);
-            }
-            _ => panic!("expected SideEffect::Clipboard"),
-        }
+        let (resp, _) = handle_message(
+            &mut s,
+            Message::RelayUndo {
+                id: 13,
+                register: None,
+            },
+            c2,
+        );
+        assert!(
+            matches!(resp, Message::Response { turn_id: Some(ref t), size: Some(9), .. } if t == "s1:1")
+        );
+        assert_eq!(s.relay_content(None).unwrap().0, b"turn data");
     }
 
     #[test]
-    fn deliver_clipboard_buffer_empty() {
+    fn capture_lines_rejects_invalid_register() {
         let (mut s, c) = fresh();
         handle_message(&mut s, hello(PROTOCOL_VERSION), c);
+        handle_message(&mut s, register(1, "s1", 100), c);
         let (resp, effect) = handle_message(
             &mut s,
-            Message::Deliver {
-                id: 10,
-                sink: "clipboard".into(),
-                session: None,
-                path: None,
+            Message::CaptureLines {
+                id: 5,
+                session: "s1".into(),
+                last: 10,
+                grep: None,
+                register: Some("a b".into()),
             },
             c,
         );
+        assert!(
+            matches!(resp, Message::Response { error: Some(ref e), .. } if e == "invalid_register")
+        );
         assert!(effect.is_none());
-        match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("buffer_empty"));
-            }
-            _ => panic!("expected Response"),
-        }
     }
 
     #[test]
@@ -1633,8 +4076,15 @@ mod tests {
             Message::Deliver {
                 id: 10,
                 sink: "file".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: Some("/tmp/turn.txt".into()),
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
             c2,
         );
@@ -1672,8 +4122,15 @@ mod tests {
             Message::Deliver {
                 id: 10,
                 sink: "file".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
             c2,
         );
@@ -1694,8 +4151,15 @@ mod tests {
             Message::Deliver {
                 id: 10,
                 sink: "fax_machine".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
             c2,
         );
@@ -14,20 +14,24 @@
 mod connection;
 mod handler;
 pub mod registry;
+mod routes;
 mod sink;
+mod snapshot;
 pub mod state;
+pub mod template;
+mod turnref;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 use connection::{BrokerCommand, DisconnectNotice};
-use handler::{InjectAction, SideEffect};
+use handler::{Delivery, InjectAction, SideEffect};
 use state::{BrokerState, ConnectionId};
 
-use crate::ipc::protocol::Message;
+use crate::ipc::protocol::{Message, Status};
 
 /// Clipboard writer closure type — wraps a `ClipboardProvider::write()` call.
 ///
@@ -56,6 +60,14 @@ pub enum BrokerError {
     Io(#[from] std::io::Error),
 }
 
+/// Messages queued for one connection before it counts as lagging.
+/// Bounds the memory a client that stops reading can pin in the broker.
+const OUTBOUND_QUEUE: usize = 256;
+
+/// Per-connection senders for unsolicited messages (injects, queries,
+/// subscribed output), keyed by connection.
+type InjectSenders = HashMap<ConnectionId, mpsc::Sender<Message>>;
+
 /// Run the broker daemon until SIGTERM or SIGINT.
 ///
 /// # Errors
@@ -71,6 +83,8 @@ pub enum BrokerError {
 /// - All state in-memory only (lost on exit)
 pub async fn run(
     config: state::RingConfig,
+    tombstones: state::TombstoneConfig,
+    templates: state::TemplateConfig,
     clipboard_writer: ClipboardWriterFn,
 ) -> Result<(), BrokerError> {
     let socket_path = resolve_socket_path()?;
@@ -83,15 +97,21 @@ pub async fn run(
     let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded_channel::<DisconnectNotice>();
 
     // Per-connection inject channels for paste → inject routing.
-    let mut inject_senders: HashMap<ConnectionId, mpsc::UnboundedSender<Message>> = HashMap::new();
+    let mut inject_senders = InjectSenders::new();
 
-    let mut state = BrokerState::new(config);
+    let mut state = BrokerState::new(config)
+        .with_tombstones(tombstones)
+        .with_templates(templates);
 
     // Graceful shutdown on SIGTERM or SIGINT.
     let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
     let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
 
+    // Client requests waiting on a wrapper query reply.
+    let mut pending = PendingReplies::default();
+
     loop {
+        let query_deadline = pending.next_deadline();
         tokio::select! {
             // -- New connection --
             result = listener.accept() => {
@@ -112,11 +132,15 @@ pub async fn run(
 
             // -- Command from connection task --
             Some(cmd) = cmd_rx.recv() => {
+                // Tombstones are only observable through commands, so
+                // expiry is applied lazily here rather than on a timer.
+                state.prune_tombstones(crate::turn::epoch_millis());
                 let (mut response, side_effect) = handler::handle_message(
                     &mut state,
                     cmd.request,
                     cmd.connection_id,
                 );
+                let mut reply_tx = Some(cmd.response_tx);
 
                 // Execute side effects before sending the response.
                 if let Some(effect) = side_effect {
@@ -136,17 +160,59 @@ pub async fn run(
                                 response = handler::error_response(request_id, &reason);
                             }
                         }
+                        SideEffect::WrapperQuery { action, token } => {
+                            if dispatch_inject(&inject_senders, action) {
+                                // Answered later by the wrapper's reply.
+                                pending.hold(token, reply_tx.take());
+                            } else if let Some(query) = state.take_query(token) {
+                                response = handler::error_response(query.request_id, "session_disconnected");
+                            }
+                        }
+                        SideEffect::QueryReply { token, response: reply } => {
+                            pending.complete(token, reply);
+                        }
+                        SideEffect::Broadcast { actions } => {
+                            dispatch_broadcast(&mut inject_senders, &mut state, actions);
+                        }
+                        SideEffect::Deliveries { content, metadata, targets, request_id } => {
+                            let mut results = Vec::with_capacity(targets.len());
+                            for (mut result, delivery) in targets {
+                                if let Some(delivery) = delivery
+                                    && let Err(reason) = deliver(delivery, &content, &metadata, &inject_senders, &*clipboard_writer).await
+                                {
+                                    result.status = Status::Error;
+                                    result.error = Some(reason);
+                                }
+                                results.push(result);
+                            }
+                            response = handler::deliveries_response(request_id, results);
+                        }
                     }
                 }
 
-                let _ = cmd.response_tx.send(response);
+                if let Some(tx) = reply_tx {
+                    let _ = tx.send(response);
+                }
+            }
+
+            // -- Wrapper query timeout --
+            _ = async {
+                match query_deadline {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                pending.expire(&mut state);
             }
 
             // -- Connection disconnected --
             Some(notice) = disconnect_rx.recv() => {
                 let conn_id = notice.connection_id;
                 inject_senders.remove(&conn_id);
-                state.remove_connection(conn_id);
+                let now = crate::turn::epoch_millis();
+                for action in handler::handle_disconnect(&mut state, conn_id, now) {
+                    dispatch_inject(&inject_senders, action);
+                }
                 tracing::debug!(?conn_id, "connection cleaned up");
             }
 
@@ -177,10 +243,10 @@ fn accept_connection(
     stream: UnixStream,
     cmd_tx: &mpsc::UnboundedSender<BrokerCommand>,
     disconnect_tx: &mpsc::UnboundedSender<DisconnectNotice>,
-    inject_senders: &mut HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
+    inject_senders: &mut InjectSenders,
 ) {
     let conn_id = ConnectionId::new();
-    let (inject_tx, inject_rx) = mpsc::unbounded_channel();
+    let (inject_tx, inject_rx) = mpsc::channel(OUTBOUND_QUEUE);
     inject_senders.insert(conn_id, inject_tx);
 
     connection::spawn_connection(
@@ -194,24 +260,86 @@ fn accept_connection(
     tracing::debug!(?conn_id, "accepted connection");
 }
 
+/// Run one target of a multi-target paste or deliver, returning the
+/// same error reason a single-target request would report.
+async fn deliver(
+    delivery: Delivery,
+    content: &[u8],
+    metadata: &state::SinkMetadata,
+    inject_senders: &InjectSenders,
+    clipboard_writer: &(dyn Fn(&[u8]) -> Result<(), String> + Sync),
+) -> Result<(), String> {
+    match delivery {
+        Delivery::Inject(target_connection) => {
+            let action = InjectAction {
+                target_connection,
+                message: Message::Inject {
+                    id: 0,
+                    content: content.to_vec(),
+                },
+            };
+            if dispatch_inject(inject_senders, action) {
+                Ok(())
+            } else {
+                Err("session_disconnected".to_string())
+            }
+        }
+        Delivery::Clipboard => sink::deliver_clipboard(content, metadata, clipboard_writer).await,
+        Delivery::File(path) => sink::deliver_file(&path, content, metadata).await,
+    }
+}
+
+/// Fan broadcast messages out to their connections.
+///
+/// A subscriber whose queue is full has stopped reading its session's
+/// output. It is unsubscribed and its sender dropped, which closes the
+/// connection once the queued messages drain.
+fn dispatch_broadcast(
+    inject_senders: &mut InjectSenders,
+    state: &mut BrokerState,
+    actions: Vec<InjectAction>,
+) {
+    let mut lagging = Vec::new();
+    for action in actions {
+        let target = action.target_connection;
+        if matches!(action.message, Message::Output { .. })
+            && inject_senders
+                .get(&target)
+                .is_some_and(|tx| tx.capacity() == 0)
+        {
+            lagging.push(target);
+            continue;
+        }
+        dispatch_inject(inject_senders, action);
+    }
+    for conn_id in lagging {
+        tracing::warn!(?conn_id, "subscriber lagging — dropping connection");
+        inject_senders.remove(&conn_id);
+        for action in handler::drop_subscriber(state, conn_id) {
+            dispatch_inject(inject_senders, action);
+        }
+    }
+}
+
 /// Route an inject command to the target wrapper's connection task.
 ///
 /// Returns `true` if the inject was successfully queued, `false` if the
-/// target connection is missing or its channel is closed (wrapper
-/// disconnected). The caller overrides the response with
+/// target connection is missing, its queue is full, or its channel is
+/// closed (wrapper disconnected). The caller overrides the response with
 /// `"session_disconnected"` on failure per CONTRACT_BROKER.md §313.
-fn dispatch_inject(
-    inject_senders: &HashMap<ConnectionId, mpsc::UnboundedSender<Message>>,
-    action: InjectAction,
-) -> bool {
+fn dispatch_inject(inject_senders: &InjectSenders, action: InjectAction) -> bool {
     if let Some(tx) = inject_senders.get(&action.target_connection) {
-        if tx.send(action.message).is_ok() {
-            return true;
+        match tx.try_send(action.message) {
+            Ok(()) => return true,
+            Err(mpsc::error::TrySendError::Full(_)) => tracing::warn!(
+                conn_id = ?action.target_connection,
+                "inject send failed — connection queue full"
+            ),
+            Err(mpsc::error::TrySendError::Closed(_)) => tracing::warn!(
+                conn_id = ?action.target_connection,
+                "inject send failed — wrapper disconnected"
+            ),
         }
-        tracing::warn!(
-            conn_id = ?action.target_connection,
-            "inject send failed — wrapper disconnected"
-        );
     } else {
         tracing::warn!(
             conn_id = ?action.target_connection,
@@ -221,6 +349,59 @@ fn dispatch_inject(
     false
 }
 
+/// How long a client request waits for a wrapper's query reply.
+const WRAPPER_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Client requests held until a wrapper answers a query
+/// ([`SideEffect::WrapperQuery`]), keyed by query token.
+#[derive(Default)]
+struct PendingReplies {
+    replies: HashMap<u32, (oneshot::Sender<Message>, tokio::time::Instant)>,
+}
+
+impl PendingReplies {
+    /// Hold a client's response channel until the query is answered.
+    fn hold(&mut self, token: u32, reply_tx: Option<oneshot::Sender<Message>>) {
+        if let Some(tx) = reply_tx {
+            let deadline = tokio::time::Instant::now() + WRAPPER_QUERY_TIMEOUT;
+            self.replies.insert(token, (tx, deadline));
+        }
+    }
+
+    /// Send the wrapper's answer to the waiting client, if still held.
+    fn complete(&mut self, token: u32, response: Message) {
+        if let Some((tx, _)) = self.replies.remove(&token) {
+            let _ = tx.send(response);
+        }
+    }
+
+    /// Earliest deadline among held requests.
+    fn next_deadline(&self) -> Option<tokio::time::Instant> {
+        self.replies.values().map(|(_, deadline)| *deadline).min()
+    }
+
+    /// Fail held requests whose deadline has passed with
+    /// `"wrapper_timeout"`.
+    fn expire(&mut self, state: &mut BrokerState) {
+        let now = tokio::time::Instant::now();
+        let expired: Vec<u32> = self
+            .replies
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in expired {
+            let Some((tx, _)) = self.replies.remove(&token) else {
+                continue;
+            };
+            if let Some(query) = state.take_query(token) {
+                tracing::warn!(session = %query.session, "wrapper query timed out");
+                let _ = tx.send(handler::error_response(query.request_id, "wrapper_timeout"));
+            }
+        }
+    }
+}
+
 // -- Socket setup --
 
 /// Resolve the broker socket path from `$XDG_RUNTIME_DIR`.
@@ -311,8 +492,7 @@ mod tests {
             let listener = bind_socket(&socket_path).await?;
             let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<BrokerCommand>();
             let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded_channel::<DisconnectNotice>();
-            let mut inject_senders: HashMap<ConnectionId, mpsc::UnboundedSender<Message>> =
-                HashMap::new();
+            let mut inject_senders = InjectSenders::new();
             let mut state = BrokerState::new(state::RingConfig::default());
 
             // Test clipboard writer — uses xclip like the real broker.
@@ -339,7 +519,10 @@ mod tests {
                 }
             });
 
+            let mut pending = PendingReplies::default();
+
             loop {
+                let query_deadline = pending.next_deadline();
                 tokio::select! {
                     result = listener.accept() => {
                         if let Ok((stream, _)) = result {
@@ -358,6 +541,7 @@ mod tests {
                                 cmd.request,
                                 cmd.connection_id,
                             );
+                        let mut reply_tx = Some(cmd.response_tx);
                         if let Some(effect) = side_effect {
                             match effect {
                                 SideEffect::Inject { action, request_id } => {
@@ -375,13 +559,52 @@ mod tests {
                                         response = handler::error_response(request_id, &reason);
                                     }
                                 }
+                                SideEffect::WrapperQuery { action, token } => {
+                                    if dispatch_inject(&inject_senders, action) {
+                                        pending.hold(token, reply_tx.take());
+                                    } else if let Some(query) = state.take_query(token) {
+                                        response = handler::error_response(query.request_id, "session_disconnected");
+                                    }
+                                }
+                                SideEffect::QueryReply { token, response: reply } => {
+                                    pending.complete(token, reply);
+                                }
+                                SideEffect::Broadcast { actions } => {
+                                    dispatch_broadcast(&mut inject_senders, &mut state, actions);
+                                }
+                                SideEffect::Deliveries { content, metadata, targets, request_id } => {
+                                    let mut results = Vec::with_capacity(targets.len());
+                                    for (mut result, delivery) in targets {
+                                        if let Some(delivery) = delivery
+                                            && let Err(reason) = deliver(delivery, &content, &metadata, &inject_senders, &*clipboard_writer).await
+                                        {
+                                            result.status = Status::Error;
+                                            result.error = Some(reason);
+                                        }
+                                        results.push(result);
+                                    }
+                                    response = handler::deliveries_response(request_id, results);
+                                }
                             }
                         }
-                        let _ = cmd.response_tx.send(response);
+                        if let Some(tx) = reply_tx {
+                            let _ = tx.send(response);
+                        }
+                    }
+                    _ = async {
+                        match query_deadline {
+                            Some(at) => tokio::time::sleep_until(at).await,
+                            None => std::future::pending().await,
+                        }
+                    } => {
+                        pending.expire(&mut state);
                     }
                     Some(notice) = disconnect_rx.recv() => {
                         inject_senders.remove(&notice.connection_id);
-                        state.remove_connection(notice.connection_id);
+                        let now = crate::turn::epoch_millis();
+                        for action in handler::handle_disconnect(&mut state, notice.connection_id, now) {
+                            dispatch_inject(&inject_senders, action);
+                        }
                     }
                 }
             }
@@ -440,6 +663,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -460,6 +688,9 @@ mod tests {
                 content: b"hello from agent".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -480,6 +711,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                register: None,
             },
         )
         .await;
@@ -500,6 +732,10 @@ mod tests {
             Message::Paste {
                 id: 2,
                 session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: None,
+                template: None,
             },
         )
         .await;
@@ -570,6 +806,11 @@ mod tests {
                 session: "s-temp".into(),
                 pid: 1,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -578,23 +819,22 @@ mod tests {
         drop(wrapper);
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
-        // Client tries to capture the disconnected session.
+        // The session is no longer live, but is kept as a tombstone.
         let mut client = connect(&sock).await;
         handshake(&mut client, Role::Client).await;
-        let resp = send_recv(
-            &mut client,
-            Message::Capture {
-                id: 1,
-                session: "s-temp".into(),
-            },
-        )
-        .await;
+        let resp = send_recv(&mut client, Message::ListSessions { id: 1, all: false }).await;
+        assert!(matches!(resp, Message::Response { sessions: Some(ref l), .. } if l.is_empty()));
 
+        let resp = send_recv(&mut client, Message::ListSessions { id: 2, all: true }).await;
         match resp {
-            Message::Response { error, .. } => {
-                assert_eq!(error.as_deref(), Some("session_not_found"));
+            Message::Response {
+                sessions: Some(sessions),
+                ..
+            } => {
+                assert_eq!(sessions.len(), 1);
+                assert_eq!(sessions[0].exit_reason.as_deref(), Some("connection lost"));
             }
-            other => panic!("expected error response, got {other:?}"),
+            other => panic!("expected session list, got {other:?}"),
         }
     }
 
@@ -615,6 +855,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -626,6 +871,9 @@ mod tests {
                 content: b"data".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -633,7 +881,7 @@ mod tests {
         // Client queries sessions.
         let mut client = connect(&sock).await;
         handshake(&mut client, Role::Client).await;
-        let resp = send_recv(&mut client, Message::ListSessions { id: 1 }).await;
+        let resp = send_recv(&mut client, Message::ListSessions { id: 1, all: false }).await;
 
         match resp {
             Message::Response { sessions, .. } => {
@@ -661,6 +909,11 @@ mod tests {
             session: "s1".into(),
             pid: 42,
             pattern: "generic".into(),
+            name: None,
+            role: None,
+            labels: Default::default(),
+            command: Vec::new(),
+            started_at: 0,
         })
         .await
         .unwrap();
@@ -751,7 +1004,8 @@ mod tests {
         }
 
         // Connection should still be open — send a valid message.
-        let list_msg = rmp_serde::to_vec_named(&Message::ListSessions { id: 7 }).unwrap();
+        let list_msg =
+            rmp_serde::to_vec_named(&Message::ListSessions { id: 7, all: false }).unwrap();
         let mut list_frame = bytes::BytesMut::new();
         list_frame.put_u32(list_msg.len() as u32);
         list_frame.extend_from_slice(&list_msg);
@@ -777,6 +1031,252 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn capture_lines_answered_by_wrapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("broker.sock");
+        let _broker = start_broker(&sock).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut wrapper = connect(&sock).await;
+        handshake(&mut wrapper, Role::Wrapper).await;
+        send_recv(
+            &mut wrapper,
+            Message::Register {
+                id: 1,
+                session: "s1".into(),
+                pid: 42,
+                pattern: "generic".into(),
+                name: Some("builder".into()),
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
+            },
+        )
+        .await;
+
+        // The client's request is held until the wrapper replies.
+        let mut client = connect(&sock).await;
+        handshake(&mut client, Role::Client).await;
+        client
+            .send(Message::CaptureLines {
+                id: 7,
+                session: "builder".into(),
+                last: 2,
+                grep: None,
+                register: None,
+            })
+            .await
+            .unwrap();
+
+        let query = match wrapper.next().await {
+            Some(Ok(Message::CaptureLines { id, last, .. })) => {
+                assert_eq!(last, 2);
+                id
+            }
+            other => panic!("expected forwarded capture_lines, got {other:?}"),
+        };
+        let ack = send_recv(
+            &mut wrapper,
+            Message::ScrollbackLines {
+                id: 2,
+                session: "s1".into(),
+                query,
+                content: b"error: boom\n$ \n".to_vec(),
+            },
+        )
+        .await;
+        assert!(matches!(
+            ack,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        match client.next().await {
+            Some(Ok(Message::Response {
+                id,
+                status,
+                size,
+                turn_id,
+                ..
+            })) => {
+                assert_eq!(id, 7);
+                assert_eq!(status, Status::Ok);
+                assert_eq!(size, Some(15));
+                assert_eq!(turn_id.as_deref(), Some("builder:lines"));
+            }
+            other => panic!("expected capture response, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn output_tap_streams_to_subscriber() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("broker.sock");
+        let _broker = start_broker(&sock).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut wrapper = connect(&sock).await;
+        handshake(&mut wrapper, Role::Wrapper).await;
+        send_recv(
+            &mut wrapper,
+            Message::Register {
+                id: 1,
+                session: "s1".into(),
+                pid: 42,
+                pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
+            },
+        )
+        .await;
+
+        let mut client = connect(&sock).await;
+        handshake(&mut client, Role::Client).await;
+        let resp = send_recv(
+            &mut client,
+            Message::Subscribe {
+                id: 1,
+                session: "s1".into(),
+            },
+        )
+        .await;
+        assert!(matches!(
+            resp,
+            Message::Response {
+                status: Status::Ok,
+                ..
+            }
+        ));
+
+        // The wrapper is told to start sending output...
+        match wrapper.next().await {
+            Some(Ok(Message::OutputTap { enabled, .. })) => assert!(enabled),
+            other => panic!("expected output_tap, got {other:?}"),
+        }
+        // Output is a notification: no response comes back.
+        wrapper
+            .send(Message::Output {
+                id: 0,
+                session: "s1".into(),
+                content: b"building...\r\n".to_vec(),
+            })
+            .await
+            .unwrap();
+
+        // ...which reaches the subscriber unchanged.
+        match client.next().await {
+            Some(Ok(Message::Output { id, content, .. })) => {
+                assert_eq!(id, 0);
+                assert_eq!(content, b"building...\r\n");
+            }
+            other => panic!("expected output, got {other:?}"),
+        }
+
+        // The wrapper's next reply answers its next request.
+        let resp = send_recv(
+            &mut wrapper,
+            Message::SessionTitle {
+                id: 3,
+                session: "s1".into(),
+                title: "build".into(),
+            },
+        )
+        .await;
+        assert!(matches!(resp, Message::Response { id: 3, .. }));
+
+        // The wrapper going away ends the subscription.
+        drop(wrapper);
+        match client.next().await {
+            Some(Ok(Message::OutputEnd { session, .. })) => assert_eq!(session, "s1"),
+            other => panic!("expected output_end, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn lagging_subscriber_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("broker.sock");
+        let _broker = start_broker(&sock).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut wrapper = connect(&sock).await;
+        handshake(&mut wrapper, Role::Wrapper).await;
+        send_recv(
+            &mut wrapper,
+            Message::Register {
+                id: 1,
+                session: "s1".into(),
+                pid: 42,
+                pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
+            },
+        )
+        .await;
+
+        let mut client = connect(&sock).await;
+        handshake(&mut client, Role::Client).await;
+        send_recv(
+            &mut client,
+            Message::Subscribe {
+                id: 1,
+                session: "s1".into(),
+            },
+        )
+        .await;
+        match wrapper.next().await {
+            Some(Ok(Message::OutputTap { enabled, .. })) => assert!(enabled),
+            other => panic!("expected output_tap, got {other:?}"),
+        }
+
+        // The client stops reading. Once its queue fills, the broker
+        // drops it and turns the now unwanted tap off.
+        // The wrapper keeps reading while it sends, as a real one does.
+        let sent = OUTBOUND_QUEUE * 4;
+        let (mut wrapper_tx, mut wrapper_rx) = wrapper.split();
+        let sender = tokio::spawn(async move {
+            for _ in 0..sent {
+                let chunk = Message::Output {
+                    id: 0,
+                    session: "s1".into(),
+                    content: vec![b'x'; 16 * 1024],
+                };
+                if wrapper_tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        match tokio::time::timeout(std::time::Duration::from_secs(5), wrapper_rx.next()).await {
+            Ok(Some(Ok(Message::OutputTap { enabled, .. }))) => assert!(!enabled),
+            other => panic!("expected output_tap, got {other:?}"),
+        }
+        sender.abort();
+
+        // What was queued still drains, then the connection closes.
+        let mut received = 0;
+        loop {
+            let next = tokio::time::timeout(std::time::Duration::from_secs(5), client.next())
+                .await
+                .expect("lagging subscriber left connected");
+            match next {
+                Some(Ok(Message::Output { .. })) => received += 1,
+                None => break,
+                other => panic!("expected output, got {other:?}"),
+            }
+        }
+        assert!(received < sent);
+    }
+
     #[tokio::test]
     async fn get_turn_and_list_turns_flow() {
         let dir = tempfile::tempdir().unwrap();
@@ -794,6 +1294,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -806,6 +1311,9 @@ mod tests {
                 content: b"first turn".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -822,6 +1330,9 @@ mod tests {
                 content: b"second turn".to_vec(),
                 interrupted: true,
                 timestamp: 2000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -948,6 +1459,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -960,6 +1476,9 @@ mod tests {
                 content: b"older turn content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -976,6 +1495,9 @@ mod tests {
                 content: b"newer turn content".to_vec(),
                 interrupted: false,
                 timestamp: 2000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -989,6 +1511,9 @@ mod tests {
             Message::CaptureByID {
                 id: 10,
                 turn_id: first_turn_id.clone(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
             },
         )
         .await;
@@ -1014,6 +1539,10 @@ mod tests {
             Message::Paste {
                 id: 11,
                 session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: None,
+                template: None,
             },
         )
         .await;
@@ -1041,6 +1570,9 @@ mod tests {
             Message::CaptureByID {
                 id: 12,
                 turn_id: "s1:999".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
             },
         )
         .await;
@@ -1070,6 +1602,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -1081,6 +1618,9 @@ mod tests {
                 content: b"deliver inject content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -1093,6 +1633,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                register: None,
             },
         )
         .await;
@@ -1102,8 +1643,15 @@ mod tests {
             Message::Deliver {
                 id: 2,
                 sink: "inject".into(),
+                sinks: Vec::new(),
                 session: Some("s1".into()),
+                sessions: Vec::new(),
+                role: None,
                 path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
         )
         .await;
@@ -1144,6 +1692,11 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: Vec::new(),
+                started_at: 0,
             },
         )
         .await;
@@ -1155,6 +1708,9 @@ mod tests {
                 content: b"file sink content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: false,
             },
         )
         .await;
@@ -1167,6 +1723,7 @@ mod tests {
             Message::Capture {
                 id: 1,
                 session: "s1".into(),
+                register: None,
             },
         )
         .await;
@@ -1176,8 +1733,15 @@ mod tests {
             Message::Deliver {
                 id: 2,
                 sink: "file".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: Some(output_path.to_str().unwrap().into()),
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
         )
         .await;
@@ -1199,7 +1763,7 @@ mod tests {
     #[test]
     fn dispatch_inject_dropped_receiver() {
         let conn = ConnectionId::new();
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, rx) = mpsc::channel(OUTBOUND_QUEUE);
         drop(rx); // Simulate wrapper task gone.
 
         let mut senders = HashMap::new();
@@ -1217,7 +1781,7 @@ mod tests {
 
     #[test]
     fn dispatch_inject_missing_target() {
-        let senders: HashMap<ConnectionId, mpsc::UnboundedSender<Message>> = HashMap::new();
+        let senders = InjectSenders::new();
         let action = InjectAction {
             target_connection: ConnectionId::new(),
             message: Message::Inject {
@@ -6,11 +6,16 @@
 
 use std::collections::VecDeque;
 
+use crate::ipc::protocol::TurnContext;
+
 /// A single completed turn stored in the ring buffer.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TurnRecord {
-    /// Stable turn identifier: `<session_id>:<seq>`.
+    /// Stable turn identifier: `<session_id>:<seq>`, or `<name>:<seq>`
+    /// for named sessions.
     pub turn_id: String,
+    /// Per-session sequence number (the `<seq>` part of the turn ID).
+    pub seq: u64,
     /// Raw turn content (bytes, no interpretation).
     pub content: Vec<u8>,
     /// Unix epoch milliseconds when the turn was stored.
@@ -21,6 +26,8 @@ pub struct TurnRecord {
     pub interrupted: bool,
     /// Whether the content was truncated to fit `max_turn_bytes`.
     pub truncated: bool,
+    /// Timing and environment reported by the wrapper.
+    pub context: TurnContext,
 }
 
 /// Per-session ring buffer of completed turns.
@@ -39,6 +46,9 @@ pub struct TurnRingBuffer {
 impl TurnRingBuffer {
     /// Create a new ring buffer for the given session.
     ///
+    /// `session_id` is the turn ID prefix — the session name for named
+    /// sessions, otherwise the session ID.
+    ///
     /// # Panics
     ///
     /// Panics if `capacity` is 0. The ring buffer must hold at least one turn.
@@ -62,10 +72,46 @@ impl TurnRingBuffer {
     /// `timestamp` is the detection-time Unix epoch millis, set by the
     /// wrapper when the turn was completed (CONTRACT_REGISTRY.md §73).
     ///
-    /// Returns a reference to the newly inserted record.
-    pub fn push(&mut self, mut content: Vec<u8>, interrupted: bool, timestamp: u64) -> &TurnRecord {
-        let turn_id = format!("{}:{}", self.session_id, self.next_seq);
-        self.next_seq += 1;
+    /// Returns a reference to the newly inserted record, with an empty
+    /// [`TurnContext`] for the caller to fill in.
+    pub fn push(&mut self, content: Vec<u8>, interrupted: bool, timestamp: u64) -> &mut TurnRecord {
+        self.insert(self.next_seq, content, interrupted, timestamp)
+    }
+
+    /// Push a turn with a sequence number assigned by the wrapper.
+    ///
+    /// Used when a wrapper replays its local history after reconnecting,
+    /// so the rebuilt registry has the same turn IDs. Gaps are allowed
+    /// (the wrapper's history may be shallower than the whole session);
+    /// a `seq` below the next expected one is rejected with
+    /// `"seq_out_of_order"`, and `u64::MAX`, which leaves no next one,
+    /// with `"invalid_seq"`.
+    pub fn push_with_seq(
+        &mut self,
+        seq: u64,
+        content: Vec<u8>,
+        interrupted: bool,
+        timestamp: u64,
+    ) -> Result<&mut TurnRecord, &'static str> {
+        if seq < self.next_seq {
+            return Err("seq_out_of_order");
+        }
+        if seq.checked_add(1).is_none() {
+            return Err("invalid_seq");
+        }
+        Ok(self.insert(seq, content, interrupted, timestamp))
+    }
+
+    fn insert(
+        &mut self,
+        seq: u64,
+        mut content: Vec<u8>,
+        interrupted: bool,
+        timestamp: u64,
+    ) -> &mut TurnRecord {
+        let turn_id = format!("{}:{}", self.session_id, seq);
+        // Only an unsequenced push after `u64::MAX - 1` can saturate.
+        self.next_seq = seq.saturating_add(1);
 
         let byte_length = content.len() as u32;
         let truncated = content.len() > self.max_turn_bytes;
@@ -75,11 +121,13 @@ impl TurnRingBuffer {
 
         let record = TurnRecord {
             turn_id,
+            seq,
             content,
             timestamp,
             byte_length,
             interrupted,
             truncated,
+            context: TurnContext::default(),
         };
 
         if self.entries.len() == self.capacity {
@@ -87,7 +135,7 @@ impl TurnRingBuffer {
         }
 
         self.entries.push_front(record);
-        &self.entries[0]
+        &mut self.entries[0]
     }
 
     /// Get the most recent turn (ring head), or `None` if empty.
@@ -95,9 +143,13 @@ impl TurnRingBuffer {
         self.entries.front()
     }
 
-    /// Look up a turn by its ID. Linear scan (capacity is small).
-    pub fn get(&self, turn_id: &str) -> Option<&TurnRecord> {
-        self.entries.iter().find(|r| r.turn_id == turn_id)
+    /// Look up a turn by its sequence number. Linear scan (capacity is
+    /// small).
+    ///
+    /// Used when the turn ID prefix has already been resolved to this
+    /// session (it may be the session ID or the session name).
+    pub fn get_seq(&self, seq: u64) -> Option<&TurnRecord> {
+        self.entries.iter().find(|r| r.seq == seq)
     }
 
     /// Iterate turns newest-first, with an optional limit.
@@ -111,6 +163,11 @@ impl TurnRingBuffer {
         self.entries.len()
     }
 
+    /// Total number of turns pushed, including evicted ones.
+    pub fn turn_count(&self) -> u64 {
+        self.next_seq - 1
+    }
+
     /// Whether the ring buffer is empty.
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
@@ -164,9 +221,45 @@ mod tests {
 
         r.push(b"d".to_vec(), false, 1000); // seq 4 — evicts seq 1
         assert_eq!(r.len(), 3);
-        assert!(r.get("test-session:1").is_none(), "seq 1 should be evicted");
-        assert!(r.get("test-session:2").is_some());
-        assert!(r.get("test-session:4").is_some());
+        assert!(r.get_seq(1).is_none(), "seq 1 should be evicted");
+        assert!(r.get_seq(2).is_some());
+        assert!(r.get_seq(4).is_some());
+        assert_eq!(r.turn_count(), 4, "count includes evicted turns");
+    }
+
+    #[test]
+    fn push_with_seq_keeps_wrapper_ids() {
+        let mut r = ring(4);
+        r.push_with_seq(7, b"a".to_vec(), false, 1000).unwrap();
+        r.push_with_seq(9, b"b".to_vec(), false, 2000).unwrap();
+        assert_eq!(r.head().unwrap().turn_id, "test-session:9");
+        assert_eq!(r.get_seq(7).unwrap().timestamp, 1000);
+        assert_eq!(r.turn_count(), 9);
+
+        // Older or repeated sequence numbers are rejected.
+        assert_eq!(
+            r.push_with_seq(9, b"c".to_vec(), false, 3000).unwrap_err(),
+            "seq_out_of_order"
+        );
+        // Unsequenced pushes continue from the highest seq.
+        r.push(b"d".to_vec(), false, 4000);
+        assert_eq!(r.head().unwrap().turn_id, "test-session:10");
+    }
+
+    #[test]
+    fn push_with_seq_rejects_the_last_seq() {
+        let mut r = ring(4);
+        assert_eq!(
+            r.push_with_seq(u64::MAX, b"a".to_vec(), false, 1000)
+                .unwrap_err(),
+            "invalid_seq"
+        );
+        assert!(r.is_empty());
+        assert_eq!(r.turn_count(), 0);
+
+        r.push_with_seq(u64::MAX - 1, b"b".to_vec(), false, 1000)
+            .unwrap();
+        assert_eq!(r.turn_count(), u64::MAX - 1);
     }
 
     #[test]
@@ -191,12 +284,13 @@ mod tests {
     }
 
     #[test]
-    fn get_hit_and_miss() {
+    fn get_seq_hit_and_miss() {
         let mut r = ring(4);
-        r.push(b"data".to_vec(), false, 1000);
-        assert!(r.get("test-session:1").is_some());
-        assert!(r.get("test-session:999").is_none());
-        assert!(r.get("other-session:1").is_none());
+        r.push(b"a".to_vec(), false, 1000);
+        r.push(b"b".to_vec(), false, 1000);
+        assert_eq!(r.get_seq(1).unwrap().content, b"a");
+        assert_eq!(r.get_seq(2).unwrap().turn_id, "test-session:2");
+        assert!(r.get_seq(3).is_none());
     }
 
     #[test]
@@ -280,7 +374,7 @@ mod tests {
         r.push(b"second".to_vec(), false, 1000);
         assert_eq!(r.len(), 1);
         assert_eq!(r.head().unwrap().content, b"second");
-        assert!(r.get("test-session:1").is_none());
+        assert!(r.get_seq(1).is_none());
     }
 
     #[test]
@@ -0,0 +1,212 @@
+//! Relay routes — broker-managed paths that inject a source session's
+//! completed turns into a target session.
+//!
+//! The table only holds routes and decides which turns qualify;
+//! [`BrokerState`](super::state::BrokerState) resolves sessions, applies
+//! the loop guard and queues deliveries. See CONTRACT_BROKER.md §Routes.
+
+use regex::bytes::Regex;
+
+use crate::ipc::protocol::{RouteDescriptor, RouteSpec};
+
+use super::registry::TurnRecord;
+
+/// A route in the table.
+#[derive(Debug)]
+pub struct Route {
+    pub id: u32,
+    pub spec: RouteSpec,
+    /// `spec.pattern`, compiled.
+    pattern: Option<Regex>,
+    pub enabled: bool,
+    /// Unix epoch millis when the route was added. Older turns (a
+    /// reconnecting wrapper's replayed history) never fire it.
+    created_at: u64,
+    pub fired: u64,
+}
+
+impl Route {
+    /// Whether the route's filter lets `turn` through.
+    pub fn accepts(&self, turn: &TurnRecord) -> bool {
+        let spec = &self.spec;
+        turn.timestamp >= self.created_at
+            && turn.byte_length >= spec.min_size
+            && !(spec.skip_interrupted && turn.interrupted)
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|re| re.is_match(&crate::turn::ansi::strip_ansi(&turn.content)))
+    }
+
+    pub fn descriptor(&self) -> RouteDescriptor {
+        RouteDescriptor {
+            route: self.id,
+            spec: self.spec.clone(),
+            enabled: self.enabled,
+            fired: self.fired,
+        }
+    }
+}
+
+/// The broker's routes, in the order they were added.
+#[derive(Debug)]
+pub struct RouteTable {
+    routes: Vec<Route>,
+    /// Next route number (numbers are never reused).
+    next_id: u32,
+}
+
+impl Default for RouteTable {
+    fn default() -> Self {
+        Self {
+            routes: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl RouteTable {
+    /// Add a route. Returns `"missing_field"` for an empty source or
+    /// target, `"invalid_template"` for a template using an unknown
+    /// variable and `"invalid_regex"` for a pattern that does not
+    /// compile.
+    pub fn add(
+        &mut self,
+        spec: RouteSpec,
+        enabled: bool,
+        now: u64,
+    ) -> Result<&Route, &'static str> {
+        if spec.source.is_empty() || spec.target.is_empty() {
+            return Err("missing_field");
+        }
+        if let Some(ref text) = spec.template
+            && super::template::validate(text).is_err()
+        {
+            return Err("invalid_template");
+        }
+        let pattern = spec
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|_| "invalid_regex")?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.routes.push(Route {
+            id,
+            spec,
+            pattern,
+            enabled,
+            created_at: now,
+            fired: 0,
+        });
+        Ok(&self.routes[self.routes.len() - 1])
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<(), &'static str> {
+        let index = self
+            .routes
+            .iter()
+            .position(|r| r.id == id)
+            .ok_or("route_not_found")?;
+        self.routes.remove(index);
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> Result<&Route, &'static str> {
+        let route = self
+            .routes
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or("route_not_found")?;
+        route.enabled = enabled;
+        Ok(route)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Route> {
+        self.routes.iter_mut().find(|r| r.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter()
+    }
+
+    pub fn list(&self) -> Vec<RouteDescriptor> {
+        self.routes.iter().map(Route::descriptor).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(content: &[u8], interrupted: bool) -> TurnRecord {
+        TurnRecord {
+            turn_id: "planner:3".into(),
+            seq: 3,
+            content: content.to_vec(),
+            timestamp: 1000,
+            interrupted,
+            byte_length: content.len() as u32,
+            truncated: false,
+            context: Default::default(),
+        }
+    }
+
+    fn spec() -> RouteSpec {
+        RouteSpec {
+            source: "planner".into(),
+            target: "implementer".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filter_checks_pattern_size_and_interruption() {
+        let mut table = RouteTable::default();
+        let route = table
+            .add(
+                RouteSpec {
+                    pattern: Some("^PLAN".into()),
+                    min_size: 6,
+                    skip_interrupted: true,
+                    ..spec()
+                },
+                true,
+                0,
+            )
+            .unwrap();
+        assert!(route.accepts(&turn(b"\x1b[1mPLAN\x1b[0m: x", false)));
+        assert!(!route.accepts(&turn(b"PLAN", false)));
+        assert!(!route.accepts(&turn(b"PLAN: x", true)));
+        assert!(!route.accepts(&turn(b"notes: PLAN", false)));
+    }
+
+    #[test]
+    fn ignores_turns_older_than_the_route() {
+        let mut table = RouteTable::default();
+        let route = table.add(spec(), true, 2000).unwrap();
+        assert!(!route.accepts(&turn(b"x", false)));
+    }
+
+    #[test]
+    fn table_numbers_and_switches_routes() {
+        let mut table = RouteTable::default();
+        assert_eq!(table.add(spec(), true, 0).unwrap().id, 1);
+        assert_eq!(table.add(spec(), false, 0).unwrap().id, 2);
+        assert!(table.set_enabled(2, true).unwrap().enabled);
+        table.remove(1).unwrap();
+        assert_eq!(table.remove(1), Err("route_not_found"));
+        assert_eq!(table.add(spec(), true, 0).unwrap().id, 3);
+        let bad = RouteSpec {
+            pattern: Some("(".into()),
+            ..spec()
+        };
+        assert_eq!(table.add(bad, true, 0).unwrap_err(), "invalid_regex");
+        let bad = RouteSpec {
+            template: Some("{{contents}}".into()),
+            ..spec()
+        };
+        assert_eq!(table.add(bad, true, 0).unwrap_err(), "invalid_template");
+    }
+}
@@ -56,6 +56,8 @@ mod tests {
             byte_length: 15,
             interrupted: false,
             truncated: false,
+            context: Default::default(),
+            turn_ids: Vec::new(),
         }
     }
 
@@ -0,0 +1,202 @@
+//! Broker snapshots — sessions' turn rings and the relay buffer as a
+//! self-contained, versioned MessagePack document.
+//!
+//! The broker only builds and restores snapshots; the client writes
+//! them to a file (optionally encrypted). Restored sessions are
+//! read-only archives. See CONTRACT_BROKER.md §Snapshots.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ipc::protocol::{TurnContext, TurnDescriptor};
+
+/// Current snapshot format version. Bumped on incompatible changes;
+/// newer versions are rejected on load.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A snapshot of selected sessions and the relay registers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Unix epoch millis when the snapshot was taken.
+    pub created_at: u64,
+    pub sessions: Vec<SessionSnapshot>,
+    /// The unnamed relay register.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<TurnSnapshot>,
+    /// Named relay registers.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub registers: BTreeMap<String, TurnSnapshot>,
+}
+
+/// One session: its metadata and retained turns, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionSnapshot {
+    pub session: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub pattern: String,
+    pub pid: u32,
+    pub started_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// When the session ended; `None` if it was live when saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_reason: Option<String>,
+    pub turns: Vec<TurnSnapshot>,
+}
+
+/// A turn record, or the relay buffer's content and metadata.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TurnSnapshot {
+    /// Turn ID as it was when saved (`<session or name>:<seq>`, or a
+    /// pseudo ID such as `planner:lines` for the relay buffer).
+    pub turn_id: String,
+    pub seq: u64,
+    #[serde(with = "serde_bytes")]
+    pub content: Vec<u8>,
+    pub timestamp: u64,
+    pub byte_length: u32,
+    pub interrupted: bool,
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "TurnContext::is_empty")]
+    pub context: TurnContext,
+    /// Constituent turn IDs of a combined relay capture.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_ids: Vec<String>,
+}
+
+impl TurnSnapshot {
+    /// The turn's metadata as sent in `list_turns`.
+    pub fn descriptor(&self) -> TurnDescriptor {
+        TurnDescriptor {
+            turn_id: self.turn_id.clone(),
+            timestamp: self.timestamp,
+            byte_length: self.byte_length,
+            interrupted: self.interrupted,
+            truncated: self.truncated,
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Encode as MessagePack (named fields, like the wire protocol).
+    pub fn encode(&self) -> Vec<u8> {
+        rmp_serde::to_vec_named(self).expect("snapshot serialization cannot fail")
+    }
+
+    /// Find a session by ID or name; `None` selects the only session.
+    /// Returns `"session_required"` when there are several.
+    pub fn session(&self, key: Option<&str>) -> Result<&SessionSnapshot, &'static str> {
+        match key {
+            Some(key) => self
+                .sessions
+                .iter()
+                .find(|s| s.session == key || s.name.as_deref() == Some(key))
+                .ok_or("session_not_found"),
+            None => match self.sessions.as_slice() {
+                [only] => Ok(only),
+                [] => Err("session_not_found"),
+                _ => Err("session_required"),
+            },
+        }
+    }
+
+    /// Decode and check the version. Returns `"invalid_snapshot"` for
+    /// undecodable data and `"unsupported_snapshot_version"` for a
+    /// version this broker does not understand.
+    pub fn decode(data: &[u8]) -> Result<Self, &'static str> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let Versioned { version } = rmp_serde::from_slice(data).map_err(|_| "invalid_snapshot")?;
+        if version == 0 || version > SNAPSHOT_VERSION {
+            return Err("unsupported_snapshot_version");
+        }
+        rmp_serde::from_slice(data).map_err(|_| "invalid_snapshot")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: 1000,
+            sessions: vec![SessionSnapshot {
+                session: "s1".into(),
+                name: Some("planner".into()),
+                role: None,
+                labels: BTreeMap::new(),
+                command: vec!["claude".into()],
+                pattern: "claude".into(),
+                pid: 42,
+                started_at: 10,
+                title: None,
+                ended_at: None,
+                exit_code: None,
+                exit_reason: None,
+                turns: vec![TurnSnapshot {
+                    turn_id: "planner:1".into(),
+                    seq: 1,
+                    content: b"plan".to_vec(),
+                    timestamp: 500,
+                    byte_length: 4,
+                    interrupted: false,
+                    truncated: false,
+                    context: TurnContext::default(),
+                    turn_ids: Vec::new(),
+                }],
+            }],
+            relay: None,
+            registers: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let snap = snapshot();
+        assert_eq!(Snapshot::decode(&snap.encode()).unwrap(), snap);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut snap = snapshot();
+        snap.version = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            Snapshot::decode(&snap.encode()),
+            Err("unsupported_snapshot_version")
+        );
+    }
+
+    #[test]
+    fn session_by_id_name_or_only() {
+        let mut snap = snapshot();
+        assert_eq!(snap.session(None).unwrap().session, "s1");
+        assert_eq!(snap.session(Some("planner")).unwrap().session, "s1");
+        assert_eq!(snap.session(Some("nope")).unwrap_err(), "session_not_found");
+        snap.sessions.push(snap.sessions[0].clone());
+        assert_eq!(snap.session(None).unwrap_err(), "session_required");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(Snapshot::decode(b"not a snapshot"), Err("invalid_snapshot"));
+    }
+}
@@ -4,12 +4,19 @@
 //! are machine-readable reasons from CONTRACT_BROKER.md §Error Semantics
 //! and CONTRACT_REGISTRY.md.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::ipc::protocol::{Role, SessionDescriptor};
+use crate::ipc::protocol::{
+    AgentState, RegisterDescriptor, Role, RouteDescriptor, RouteSpec, SectionStyle,
+    SessionDescriptor, TurnContext,
+};
 
 use super::registry::{TurnRecord, TurnRingBuffer};
+use super::routes::{Route, RouteTable};
+use super::snapshot::{SNAPSHOT_VERSION, SessionSnapshot, Snapshot, TurnSnapshot};
+use super::template::{self, TemplateVars};
+use super::turnref::{self, Selector, Source, TurnRef};
 
 /// Configuration for per-session turn ring buffers.
 #[derive(Debug, Clone)]
@@ -29,6 +36,37 @@ impl Default for RingConfig {
     }
 }
 
+/// Retention of ended sessions (tombstones).
+///
+/// A session that deregisters or whose wrapper connection drops is kept
+/// read-only so its retained turns stay reachable by turn ID. See
+/// CONTRACT_BROKER.md §Tombstones.
+#[derive(Debug, Clone)]
+pub struct TombstoneConfig {
+    /// How long an ended session is kept, in milliseconds.
+    pub ttl_ms: u64,
+    /// Maximum number of ended sessions kept (oldest evicted first).
+    /// Zero removes sessions as soon as they end.
+    pub max: usize,
+}
+
+impl Default for TombstoneConfig {
+    fn default() -> Self {
+        Self {
+            ttl_ms: 10 * 60 * 1000,
+            max: 16,
+        }
+    }
+}
+
+/// Named delivery templates, selected by a paste or deliver's
+/// `template`. See CONTRACT_REGISTRY.md §Templates.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateConfig {
+    /// Template text by name, checked with [`template::validate`].
+    pub templates: BTreeMap<String, String>,
+}
+
 /// Turn metadata passed to sinks per CONTRACT_REGISTRY.md §266.
 ///
 /// All fields are public and part of the sink interface contract.
@@ -41,6 +79,10 @@ pub struct SinkMetadata {
     pub byte_length: u32,
     pub interrupted: bool,
     pub truncated: bool,
+    pub context: TurnContext,
+    /// Constituent turn IDs of a combined capture, in payload order;
+    /// empty for a single turn.
+    pub turn_ids: Vec<String>,
 }
 
 /// Relay buffer entry — captured turn content with metadata.
@@ -50,6 +92,123 @@ struct RelayEntry {
     metadata: SinkMetadata,
 }
 
+impl RelayEntry {
+    /// Copy a turn record's content and metadata into a relay entry.
+    fn from_record(record: &TurnRecord) -> Self {
+        Self {
+            content: record.content.clone(),
+            metadata: SinkMetadata {
+                turn_id: record.turn_id.clone(),
+                timestamp: record.timestamp,
+                byte_length: record.byte_length,
+                interrupted: record.interrupted,
+                truncated: record.truncated,
+                context: record.context.clone(),
+                turn_ids: Vec::new(),
+            },
+        }
+    }
+
+    fn to_snapshot(&self) -> TurnSnapshot {
+        TurnSnapshot {
+            turn_id: self.metadata.turn_id.clone(),
+            seq: 0,
+            content: self.content.clone(),
+            timestamp: self.metadata.timestamp,
+            byte_length: self.metadata.byte_length,
+            interrupted: self.metadata.interrupted,
+            truncated: self.metadata.truncated,
+            context: self.metadata.context.clone(),
+            turn_ids: self.metadata.turn_ids.clone(),
+        }
+    }
+
+    /// Describe the entry as held by `register`.
+    fn descriptor(&self, register: Option<&str>) -> RegisterDescriptor {
+        RegisterDescriptor {
+            register: register.map(str::to_string),
+            turn_id: self.metadata.turn_id.clone(),
+            size: self.content.len() as u32,
+            timestamp: self.metadata.timestamp,
+        }
+    }
+
+    fn from_snapshot(turn: TurnSnapshot) -> Self {
+        Self {
+            content: turn.content,
+            metadata: SinkMetadata {
+                turn_id: turn.turn_id,
+                timestamp: turn.timestamp,
+                byte_length: turn.byte_length,
+                interrupted: turn.interrupted,
+                truncated: turn.truncated,
+                context: turn.context,
+                turn_ids: turn.turn_ids,
+            },
+        }
+    }
+}
+
+/// Number of replaced relay entries kept for undo, across all
+/// registers.
+const RELAY_HISTORY_DEPTH: usize = 16;
+
+/// Append one gathered turn as a labelled section ending in a newline.
+/// The label is the turn ID's session part (the session name for
+/// named sessions) and the turn ID.
+fn push_section(out: &mut Vec<u8>, record: &TurnRecord, style: SectionStyle) {
+    let session = record
+        .turn_id
+        .rsplit_once(':')
+        .map_or(record.turn_id.as_str(), |(s, _)| s);
+    let header = match style {
+        SectionStyle::Xml => format!(
+            "<turn session=\"{}\" turn_id=\"{}\">\n",
+            escape_attribute(session),
+            escape_attribute(&record.turn_id)
+        ),
+        SectionStyle::Markdown => format!("## {session} ({})\n\n", record.turn_id),
+    };
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(&record.content);
+    if !record.content.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    if style == SectionStyle::Xml {
+        out.extend_from_slice(b"</turn>\n");
+    }
+}
+
+/// Escape `&`, `<` and `"` for an XML attribute value.
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+}
+
+/// Placed between the turns of a combined capture when no separator
+/// is given.
+pub const DEFAULT_SEPARATOR: &str = "\n\n";
+
+/// Longest accepted register name.
+const MAX_REGISTER_LEN: usize = 32;
+
+/// Check a relay register name: 1 to 32 ASCII letters, digits, `_` or
+/// `-`. Returns `"invalid_register"` otherwise.
+pub fn validate_register(name: &str) -> Result<(), &'static str> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_REGISTER_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if valid {
+        Ok(())
+    } else {
+        Err("invalid_register")
+    }
+}
+
 /// Result of a capture operation.
 #[derive(Debug, PartialEq, Eq)]
 pub struct CaptureResult {
@@ -57,6 +216,22 @@ pub struct CaptureResult {
     pub turn_id: String,
 }
 
+/// A broker → wrapper query awaiting the wrapper's reply.
+///
+/// The client's request stays unanswered until the wrapper replies or
+/// the broker loop times the query out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingQuery {
+    /// ID of the client request to answer.
+    pub request_id: u32,
+    /// Session ID the query was sent to.
+    pub session: String,
+    /// Wrapper connection expected to reply.
+    pub connection_id: ConnectionId,
+    /// Register the reply is captured into (`None`: unnamed).
+    pub register: Option<String>,
+}
+
 /// Unique identifier for a client connection.
 ///
 /// Monotonically increasing counter. Used to route inject commands
@@ -71,13 +246,90 @@ impl ConnectionId {
     }
 }
 
+/// Optional session metadata supplied by the wrapper at registration.
+#[derive(Debug, Clone, Default)]
+pub struct SessionMeta {
+    /// Human-readable session name (`wrap --name`), unique per broker.
+    pub name: Option<String>,
+    /// Session role (`wrap --role`); not unique.
+    pub role: Option<String>,
+    /// Arbitrary key/value labels (`wrap --label k=v`).
+    pub labels: BTreeMap<String, String>,
+    /// Wrapped command line.
+    pub command: Vec<String>,
+    /// Prompt pattern preset name or custom regex.
+    pub pattern: String,
+    /// Unix epoch millis when the session started.
+    pub started_at: u64,
+}
+
+/// How and when a session ended.
+#[derive(Debug)]
+struct SessionEnd {
+    /// Unix epoch millis when the session ended.
+    at: u64,
+    exit_code: Option<i32>,
+    reason: String,
+}
+
 /// Session entry in the broker's session table.
 #[derive(Debug)]
 struct SessionEntry {
     connection_id: ConnectionId,
     pid: u32,
+    /// Session name, accepted as an alias for the session ID.
+    name: Option<String>,
+    role: Option<String>,
+    labels: BTreeMap<String, String>,
+    command: Vec<String>,
+    pattern: String,
+    started_at: u64,
+    /// Unix epoch millis of the latest reported input, if any.
+    last_input_at: Option<u64>,
+    /// Latest agent state reported by the wrapper.
+    state: AgentState,
+    /// Current terminal title; `None` until set or after it is cleared.
+    title: Option<String>,
     /// Per-session ring buffer of completed turns.
     ring: TurnRingBuffer,
+    /// Set once the session has ended; the entry is then a read-only
+    /// tombstone.
+    ended: Option<SessionEnd>,
+    /// Loaded from a snapshot: read-only like a tombstone, but kept
+    /// until replaced rather than expiring.
+    archived: bool,
+    /// Client connections following the session's output (`subscribe`).
+    subscribers: HashSet<ConnectionId>,
+    /// Routes that injected into the session since its last turn; its
+    /// next turn does not fire them again (the loop guard).
+    route_chain: Vec<u32>,
+    /// Route deliveries waiting for the session to be idle, oldest
+    /// first, each with the chain it adds once injected.
+    pending_routes: VecDeque<(Vec<u8>, Vec<u32>)>,
+}
+
+impl SessionEntry {
+    /// Record that routes in `chain` led to an injection.
+    fn extend_route_chain(&mut self, chain: &[u32]) {
+        for id in chain {
+            if !self.route_chain.contains(id) {
+                self.route_chain.push(*id);
+            }
+        }
+    }
+
+    /// Release a route delivery that waited for the session to be idle.
+    ///
+    /// Content with a line ending submits input and starts a turn, so
+    /// the session counts as busy until its wrapper next reports idle:
+    /// further waiting deliveries stay queued instead of landing on
+    /// top of this one.
+    fn release_route(&mut self, content: &[u8], chain: &[u32]) {
+        self.extend_route_chain(chain);
+        if content.iter().any(|&b| b == b'\r' || b == b'\n') {
+            self.state = AgentState::Busy;
+        }
+    }
 }
 
 /// Broker state — session table and relay buffer.
@@ -89,12 +341,27 @@ struct SessionEntry {
 pub struct BrokerState {
     /// Session table keyed by session ID.
     sessions: HashMap<String, SessionEntry>,
-    /// Global single-slot relay buffer. `None` until first capture.
+    /// Unnamed relay register. `None` until first capture.
     relay_buffer: Option<RelayEntry>,
+    /// Named relay registers, present once captured into.
+    registers: BTreeMap<String, RelayEntry>,
+    /// Replaced or cleared register contents with the register they
+    /// were in, oldest first; at most [`RELAY_HISTORY_DEPTH`].
+    relay_history: VecDeque<(Option<String>, RelayEntry)>,
     /// Active connections keyed by ID, storing their role.
     connections: HashMap<ConnectionId, Role>,
     /// Ring buffer configuration applied to new sessions.
     ring_config: RingConfig,
+    /// Retention of ended sessions.
+    tombstone_config: TombstoneConfig,
+    /// Wrapper queries awaiting a reply, keyed by query token.
+    pending_queries: HashMap<u32, PendingQuery>,
+    /// Next query token (never 0, which marks unsolicited messages).
+    next_query: u32,
+    /// Relay routes between sessions.
+    routes: RouteTable,
+    /// Named delivery templates.
+    template_config: TemplateConfig,
 }
 
 impl BrokerState {
@@ -102,11 +369,30 @@ impl BrokerState {
         Self {
             sessions: HashMap::new(),
             relay_buffer: None,
+            registers: BTreeMap::new(),
+            relay_history: VecDeque::new(),
             connections: HashMap::new(),
             ring_config: config,
+            tombstone_config: TombstoneConfig::default(),
+            pending_queries: HashMap::new(),
+            next_query: 1,
+            routes: RouteTable::default(),
+            template_config: TemplateConfig::default(),
         }
     }
 
+    /// Set the retention of ended sessions.
+    pub fn with_tombstones(mut self, config: TombstoneConfig) -> Self {
+        self.tombstone_config = config;
+        self
+    }
+
+    /// Set the named delivery templates.
+    pub fn with_templates(mut self, config: TemplateConfig) -> Self {
+        self.template_config = config;
+        self
+    }
+
     /// Register a new connection with its role.
     pub fn add_connection(&mut self, id: ConnectionId, role: Role) {
         self.connections.insert(id, role);
@@ -120,27 +406,50 @@ impl BrokerState {
     /// Remove a connection and implicitly deregister any associated session.
     ///
     /// CONTRACT_BROKER.md §Implicit deregister: if a wrapper connection
-    /// drops without sending `deregister`, the session is removed.
-    pub fn remove_connection(&mut self, id: ConnectionId) {
+    /// drops without sending `deregister`, the session ends with reason
+    /// `"connection lost"` at `now` (Unix epoch millis).
+    pub fn remove_connection(&mut self, id: ConnectionId, now: u64) {
         self.connections.remove(&id);
-        // Find and remove any session owned by this connection.
-        self.sessions.retain(|_, entry| entry.connection_id != id);
+        // End any live session owned by this connection.
+        for session in self.live_sessions_of(id) {
+            self.end_session(&session, None, "connection lost".to_string(), now);
+        }
     }
 
     /// Register a new session.
     ///
-    /// Returns `Err("duplicate_session")` if the session ID is already registered.
+    /// Returns `Err("duplicate_session")` if the session ID is already
+    /// registered (as an ID or a name). A named session's turn IDs use
+    /// the name as prefix (`planner:3`). Returns `Err("invalid_name")`
+    /// for an empty name or one containing `:`, and
+    /// `Err("duplicate_name")` if the name is already in use.
     pub fn register_session(
         &mut self,
         session_id: String,
         connection_id: ConnectionId,
         pid: u32,
+        meta: SessionMeta,
     ) -> Result<(), &'static str> {
-        if self.sessions.contains_key(&session_id) {
+        // A tombstone never blocks registration: a wrapper reconnecting
+        // after a dropped connection reuses its session ID, and a new
+        // session may take an ended session's name.
+        if self.entry(&session_id).is_some_and(|e| e.ended.is_none()) {
             return Err("duplicate_session");
         }
+        if let Some(ref name) = meta.name {
+            if name.is_empty() || name.contains(':') {
+                return Err("invalid_name");
+            }
+            if self.entry(name).is_some_and(|e| e.ended.is_none()) {
+                return Err("duplicate_name");
+            }
+        }
+        self.remove_tombstone(&session_id);
+        if let Some(ref name) = meta.name {
+            self.remove_tombstone(name);
+        }
         let ring = TurnRingBuffer::new(
-            session_id.clone(),
+            meta.name.clone().unwrap_or_else(|| session_id.clone()),
             self.ring_config.depth,
             self.ring_config.max_turn_bytes,
         );
@@ -149,19 +458,118 @@ impl BrokerState {
             SessionEntry {
                 connection_id,
                 pid,
+                name: meta.name,
+                role: meta.role,
+                labels: meta.labels,
+                command: meta.command,
+                pattern: meta.pattern,
+                started_at: meta.started_at,
+                last_input_at: None,
+                state: AgentState::default(),
+                title: None,
                 ring,
+                ended: None,
+                archived: false,
+                subscribers: HashSet::new(),
+                route_chain: Vec::new(),
+                pending_routes: VecDeque::new(),
             },
         );
         Ok(())
     }
 
+    /// Resolve a session ID or session name to its session ID.
+    fn resolve_session(&self, key: &str) -> Option<&str> {
+        if let Some((id, _)) = self.sessions.get_key_value(key) {
+            return Some(id);
+        }
+        self.sessions
+            .iter()
+            .find(|(_, entry)| entry.name.as_deref() == Some(key))
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// Look up a session entry by session ID or name.
+    fn entry(&self, key: &str) -> Option<&SessionEntry> {
+        let id = self.resolve_session(key)?;
+        self.sessions.get(id)
+    }
+
+    /// Mutable lookup of a live session. Tombstones are read-only, so
+    /// ended sessions are not returned.
+    fn entry_mut(&mut self, key: &str) -> Option<&mut SessionEntry> {
+        let id = self.resolve_session(key)?.to_string();
+        self.sessions.get_mut(&id).filter(|e| e.ended.is_none())
+    }
+
+    /// Remove a tombstone or archive by session ID or name, if there
+    /// is one.
+    fn remove_tombstone(&mut self, key: &str) {
+        if let Some(id) = self.resolve_session(key).map(str::to_string)
+            && self.sessions[&id].ended.is_some()
+        {
+            self.sessions.remove(&id);
+        }
+    }
+
     /// Deregister a session. Idempotent — returns `Ok(())` even if
-    /// the session was already removed.
+    /// the session was already removed or has already ended.
+    ///
+    /// The session is kept as a tombstone carrying the reported exit
+    /// code and reason (CONTRACT_BROKER.md §Tombstones). `now` is the
+    /// Unix epoch millis of the deregister.
     ///
     /// CONTRACT_BROKER.md §Deregister: relay buffer is NOT cleared
     /// (content was already captured).
-    pub fn deregister_session(&mut self, session_id: &str) {
-        self.sessions.remove(session_id);
+    pub fn deregister_session(
+        &mut self,
+        session_id: &str,
+        exit_code: Option<i32>,
+        reason: Option<String>,
+        now: u64,
+    ) {
+        let reason = reason.unwrap_or_else(|| "deregistered".to_string());
+        self.end_session(session_id, exit_code, reason, now);
+    }
+
+    /// Turn a live session into a tombstone, then evict tombstones
+    /// beyond the configured count. Archives are not counted.
+    fn end_session(&mut self, session_id: &str, exit_code: Option<i32>, reason: String, now: u64) {
+        let Some(entry) = self.sessions.get_mut(session_id) else {
+            return;
+        };
+        if entry.ended.is_some() {
+            return;
+        }
+        entry.ended = Some(SessionEnd {
+            at: now,
+            exit_code,
+            reason,
+        });
+
+        let mut ended: Vec<(u64, String)> = self
+            .sessions
+            .iter()
+            .filter(|(_, e)| !e.archived)
+            .filter_map(|(id, e)| e.ended.as_ref().map(|end| (end.at, id.clone())))
+            .collect();
+        if ended.len() > self.tombstone_config.max {
+            ended.sort();
+            let excess = ended.len() - self.tombstone_config.max;
+            for (_, id) in ended.into_iter().take(excess) {
+                self.sessions.remove(&id);
+            }
+        }
+    }
+
+    /// Drop tombstones older than the configured grace period.
+    /// Archives are kept.
+    pub fn prune_tombstones(&mut self, now: u64) {
+        let ttl = self.tombstone_config.ttl_ms;
+        self.sessions.retain(|_, entry| match entry.ended {
+            Some(ref end) if !entry.archived => now < end.at.saturating_add(ttl),
+            _ => true,
+        });
     }
 
     /// Store a completed turn for a session.
@@ -172,98 +580,668 @@ impl BrokerState {
     /// CONTRACT_BROKER.md §Turn Storage: raw bytes, no interpretation.
     /// CONTRACT_REGISTRY.md: turn IDs, metadata, ring eviction.
     /// `timestamp` is the detection-time Unix epoch millis from the wrapper.
+    /// `seq`, when given, is the wrapper-assigned sequence number (kept
+    /// so replayed history rebuilds the same turn IDs); otherwise the
+    /// ring assigns the next one.
     pub fn store_turn(
         &mut self,
         session_id: &str,
         content: Vec<u8>,
         interrupted: bool,
         timestamp: u64,
+        seq: Option<u64>,
+        context: TurnContext,
     ) -> Result<String, &'static str> {
-        let entry = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or("session_not_found")?;
-        let record = entry.ring.push(content, interrupted, timestamp);
+        let entry = self.entry_mut(session_id).ok_or("session_not_found")?;
+        let record = match seq {
+            Some(seq) => entry
+                .ring
+                .push_with_seq(seq, content, interrupted, timestamp)?,
+            None => entry.ring.push(content, interrupted, timestamp),
+        };
+        record.context = context;
         Ok(record.turn_id.clone())
     }
 
-    /// Capture: copy a session's latest turn into the relay buffer.
+    /// Record the time of the latest input submitted to a session.
+    pub fn record_input(&mut self, session_id: &str, timestamp: u64) -> Result<(), &'static str> {
+        let entry = self.entry_mut(session_id).ok_or("session_not_found")?;
+        entry.last_input_at = Some(timestamp);
+        Ok(())
+    }
+
+    /// Record the agent state reported by a session's wrapper.
+    pub fn set_agent_state(
+        &mut self,
+        session_id: &str,
+        state: AgentState,
+    ) -> Result<(), &'static str> {
+        let entry = self.entry_mut(session_id).ok_or("session_not_found")?;
+        entry.state = state;
+        Ok(())
+    }
+
+    /// Record the terminal title reported by a session's wrapper.
+    /// An empty title clears it.
+    pub fn set_title(&mut self, session_id: &str, title: String) -> Result<(), &'static str> {
+        let entry = self.entry_mut(session_id).ok_or("session_not_found")?;
+        entry.title = (!title.is_empty()).then_some(title);
+        Ok(())
+    }
+
+    /// Capture: copy a session's latest turn into a relay register
+    /// (`None`: the unnamed one).
     ///
     /// Returns a [`CaptureResult`] with the byte size and turn ID.
     /// The session's turn is NOT cleared.
-    /// The relay buffer is overwritten (previous content replaced).
-    pub fn capture(&mut self, session_id: &str) -> Result<CaptureResult, &'static str> {
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
+    /// The register is overwritten (previous content replaced).
+    pub fn capture(
+        &mut self,
+        session_id: &str,
+        register: Option<&str>,
+    ) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let entry = self.entry(session_id).ok_or("session_not_found")?;
         let head = entry.ring.head().ok_or("no_turn")?;
         let size = head.content.len() as u32;
         let turn_id = head.turn_id.clone();
-        self.relay_buffer = Some(RelayEntry {
-            content: head.content.clone(),
-            metadata: SinkMetadata {
-                turn_id: turn_id.clone(),
-                timestamp: head.timestamp,
-                byte_length: head.byte_length,
-                interrupted: head.interrupted,
-                truncated: head.truncated,
-            },
-        });
+        let relay = RelayEntry::from_record(head);
+        self.set_register(register, relay);
         Ok(CaptureResult { size, turn_id })
     }
 
-    /// Read relay buffer content and resolve the target wrapper connection.
+    /// Read a relay register's content and resolve the target wrapper
+    /// connection.
     ///
     /// Returns `(content, target_connection_id)` on success.
-    /// Does NOT clear the relay buffer (same content can be pasted
+    /// Does NOT clear the register (same content can be pasted
     /// multiple times per CONTRACT_BROKER.md §Relay buffer persistence).
-    pub fn paste_content(&self, session_id: &str) -> Result<(Vec<u8>, ConnectionId), &'static str> {
-        let relay = self.relay_buffer.as_ref().ok_or("buffer_empty")?;
+    pub fn paste_content(
+        &self,
+        session_id: &str,
+        register: Option<&str>,
+    ) -> Result<(Vec<u8>, ConnectionId), &'static str> {
+        let relay = self.register(register)?;
         let content = relay.content.clone();
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
+        let connection_id = self.wrapper_connection(session_id)?;
+        Ok((content, connection_id))
+    }
+
+    /// Look up a relay register (`None`: the unnamed one). Returns
+    /// `"buffer_empty"` if nothing has been captured into it.
+    fn register(&self, register: Option<&str>) -> Result<&RelayEntry, &'static str> {
+        match register {
+            Some(name) => {
+                validate_register(name)?;
+                self.registers.get(name)
+            }
+            None => self.relay_buffer.as_ref(),
+        }
+        .ok_or("buffer_empty")
+    }
+
+    /// Replace a relay register's content, keeping the previous
+    /// content in the history. The name must already be validated.
+    fn set_register(&mut self, register: Option<&str>, relay: RelayEntry) {
+        let previous = match register {
+            Some(name) => self.registers.insert(name.to_string(), relay),
+            None => self.relay_buffer.replace(relay),
+        };
+        self.push_history(register, previous);
+    }
+
+    /// Remember a register's replaced content, dropping the oldest
+    /// entry beyond [`RELAY_HISTORY_DEPTH`].
+    fn push_history(&mut self, register: Option<&str>, previous: Option<RelayEntry>) {
+        let Some(previous) = previous else {
+            return;
+        };
+        if self.relay_history.len() == RELAY_HISTORY_DEPTH {
+            self.relay_history.pop_front();
+        }
+        self.relay_history
+            .push_back((register.map(str::to_string), previous));
+    }
+
+    /// Undo the last change to a relay register: put back the content
+    /// it held before, discarding the current content. Returns
+    /// `"no_history"` if the history has nothing for the register.
+    pub fn undo_relay(&mut self, register: Option<&str>) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let index = self
+            .relay_history
+            .iter()
+            .rposition(|(r, _)| r.as_deref() == register)
+            .ok_or("no_history")?;
+        let (_, relay) = self.relay_history.remove(index).expect("index in range");
+        let result = CaptureResult {
+            size: relay.content.len() as u32,
+            turn_id: relay.metadata.turn_id.clone(),
+        };
+        match register {
+            Some(name) => {
+                self.registers.insert(name.to_string(), relay);
+            }
+            None => self.relay_buffer = Some(relay),
+        }
+        Ok(result)
+    }
+
+    /// Describe the relay history, most recent first.
+    pub fn relay_history(&self) -> Vec<RegisterDescriptor> {
+        self.relay_history
+            .iter()
+            .rev()
+            .map(|(register, relay)| relay.descriptor(register.as_deref()))
+            .collect()
+    }
+
+    /// Put content from outside the registry into a relay register.
+    ///
+    /// The metadata is synthetic: the pseudo turn ID `<source>:set`,
+    /// `timestamp` as given, and no flags or context.
+    pub fn set_relay(
+        &mut self,
+        register: Option<&str>,
+        content: Vec<u8>,
+        source: &str,
+        timestamp: u64,
+    ) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let turn_id = format!("{source}:set");
+        let size = content.len() as u32;
+        let relay = RelayEntry {
+            content,
+            metadata: SinkMetadata {
+                turn_id: turn_id.clone(),
+                timestamp,
+                byte_length: size,
+                interrupted: false,
+                truncated: false,
+                context: TurnContext::default(),
+                turn_ids: Vec::new(),
+            },
+        };
+        self.set_register(register, relay);
+        Ok(CaptureResult { size, turn_id })
+    }
+
+    /// Empty a relay register, keeping its content in the history.
+    /// Clearing an empty register is not an error.
+    pub fn clear_relay(&mut self, register: Option<&str>) -> Result<(), &'static str> {
+        let previous = match register {
+            Some(name) => {
+                validate_register(name)?;
+                self.registers.remove(name)
+            }
+            None => self.relay_buffer.take(),
+        };
+        self.push_history(register, previous);
+        Ok(())
+    }
+
+    /// Describe the non-empty relay registers, the unnamed one first.
+    pub fn list_registers(&self) -> Vec<RegisterDescriptor> {
+        self.relay_buffer
+            .iter()
+            .map(|relay| relay.descriptor(None))
+            .chain(
+                self.registers
+                    .iter()
+                    .map(|(name, relay)| relay.descriptor(Some(name))),
+            )
+            .collect()
+    }
+
+    /// Resolve the wrapper connection of a live session, for paste and
+    /// session control (signal, kill, resize).
+    pub fn wrapper_connection(&self, session_id: &str) -> Result<ConnectionId, &'static str> {
+        let entry = self.entry(session_id).ok_or("session_not_found")?;
+        if entry.archived {
+            return Err("session_archived");
+        }
+        if entry.ended.is_some() {
+            return Err("session_ended");
+        }
         if !self.connections.contains_key(&entry.connection_id) {
             return Err("session_disconnected");
         }
-        Ok((content, entry.connection_id))
+        Ok(entry.connection_id)
+    }
+
+    /// Expand the inject targets of a multi-target paste or deliver:
+    /// `sessions` (IDs or names) as given, then every live session with
+    /// `role` by its name (or ID), oldest first. A session named twice
+    /// is targeted once; unknown sessions are kept so that they fail
+    /// on their own. Returns `"role_not_found"` if `role` matches no
+    /// live session.
+    pub fn inject_targets(
+        &self,
+        sessions: &[&str],
+        role: Option<&str>,
+    ) -> Result<Vec<String>, &'static str> {
+        let mut targets: Vec<String> = sessions.iter().map(|s| s.to_string()).collect();
+        if let Some(role) = role {
+            let mut matches: Vec<(&String, &SessionEntry)> = self
+                .sessions
+                .iter()
+                .filter(|(_, e)| e.ended.is_none() && e.role.as_deref() == Some(role))
+                .collect();
+            if matches.is_empty() {
+                return Err("role_not_found");
+            }
+            matches.sort_by_key(|(id, e)| (e.started_at, *id));
+            targets.extend(
+                matches
+                    .into_iter()
+                    .map(|(id, e)| e.name.clone().unwrap_or_else(|| id.clone())),
+            );
+        }
+        let mut seen = HashSet::new();
+        targets.retain(|t| seen.insert(self.resolve_session(t).unwrap_or(t).to_string()));
+        Ok(targets)
     }
 
-    /// Read a clone of the relay buffer content and metadata, if present.
+    /// Add a relay route (see [`RouteTable::add`]).
+    pub fn add_route(
+        &mut self,
+        spec: RouteSpec,
+        enabled: bool,
+        now: u64,
+    ) -> Result<RouteDescriptor, &'static str> {
+        self.routes.add(spec, enabled, now).map(Route::descriptor)
+    }
+
+    pub fn remove_route(&mut self, id: u32) -> Result<(), &'static str> {
+        self.routes.remove(id)
+    }
+
+    pub fn enable_route(
+        &mut self,
+        id: u32,
+        enabled: bool,
+    ) -> Result<RouteDescriptor, &'static str> {
+        self.routes.set_enabled(id, enabled).map(Route::descriptor)
+    }
+
+    pub fn list_routes(&self) -> Vec<RouteDescriptor> {
+        self.routes.list()
+    }
+
+    /// Fire the enabled routes from a session for its latest turn.
+    ///
+    /// Returns the injects to send now; deliveries that wait for an
+    /// idle target are queued until [`take_route_delivery`]. A route
+    /// whose injection led to this turn is skipped, so a cycle of
+    /// routes runs at most once around. Targets that are not live are
+    /// skipped.
+    ///
+    /// [`take_route_delivery`]: Self::take_route_delivery
+    pub fn route_turn(&mut self, session_id: &str) -> Vec<(ConnectionId, Vec<u8>)> {
+        let Some(source_id) = self.resolve_session(session_id).map(str::to_string) else {
+            return Vec::new();
+        };
+        let chain = match self.sessions.get_mut(&source_id) {
+            Some(entry) => std::mem::take(&mut entry.route_chain),
+            None => return Vec::new(),
+        };
+        let source = &self.sessions[&source_id];
+        let Some(turn) = source.ring.head() else {
+            return Vec::new();
+        };
+        let vars = TemplateVars {
+            content: &turn.content,
+            turn_id: &turn.turn_id,
+            session: source.name.as_deref().unwrap_or(&source_id),
+            role: source.role.as_deref().unwrap_or(""),
+            timestamp: turn.timestamp,
+        };
+        let mut fires = Vec::new();
+        for route in self.routes.iter() {
+            let spec = &route.spec;
+            let from_source =
+                spec.source == source_id || source.name.as_ref() == Some(&spec.source);
+            if !route.enabled || !from_source || !route.accepts(turn) {
+                continue;
+            }
+            if chain.contains(&route.id) {
+                tracing::info!(route = route.id, turn = %turn.turn_id, "route loop blocked");
+                continue;
+            }
+            let content = match spec.template {
+                Some(ref text) => template::render(text, &vars),
+                None => turn.content.clone(),
+            };
+            fires.push((route.id, spec.target.clone(), content, spec.wait_idle));
+        }
+
+        let mut injects = Vec::new();
+        for (route_id, target, content, wait_idle) in fires {
+            let Ok(connection) = self.wrapper_connection(&target) else {
+                continue;
+            };
+            if let Some(route) = self.routes.get_mut(route_id) {
+                route.fired += 1;
+            }
+            let Some(entry) = self.entry_mut(&target) else {
+                continue;
+            };
+            let mut delivered_chain = chain.clone();
+            delivered_chain.push(route_id);
+            if !wait_idle {
+                entry.extend_route_chain(&delivered_chain);
+                injects.push((connection, content));
+                continue;
+            }
+            // Queue behind earlier waiting deliveries, even when idle.
+            // The chain is attached on release: a turn the target
+            // completes meanwhile was not led to by these routes.
+            entry.pending_routes.push_back((content, delivered_chain));
+            if entry.state == AgentState::Idle
+                && let Some((content, chain)) = entry.pending_routes.pop_front()
+            {
+                entry.release_route(&content, &chain);
+                injects.push((connection, content));
+            }
+        }
+        injects
+    }
+
+    /// Take the oldest route delivery waiting for a session to be
+    /// idle. Called when the session reports the idle state, so each
+    /// idle report releases one delivery.
+    pub fn take_route_delivery(&mut self, session_id: &str) -> Option<(ConnectionId, Vec<u8>)> {
+        let entry = self.entry_mut(session_id)?;
+        if entry.state != AgentState::Idle {
+            return None;
+        }
+        let (content, chain) = entry.pending_routes.pop_front()?;
+        entry.release_route(&content, &chain);
+        Some((entry.connection_id, content))
+    }
+
+    /// Render content for delivery through the named template. The
+    /// variables come from its metadata: the session is the turn ID's
+    /// prefix, and its role is looked up while the broker knows the
+    /// session. Returns `"template_not_found"` for an unknown name.
+    pub fn apply_template(
+        &self,
+        name: &str,
+        content: &[u8],
+        metadata: &SinkMetadata,
+    ) -> Result<Vec<u8>, &'static str> {
+        let text = self
+            .template_config
+            .templates
+            .get(name)
+            .ok_or("template_not_found")?;
+        let turn_id = metadata.turn_id.as_str();
+        let session = turn_id.split_once(':').map_or(turn_id, |(s, _)| s);
+        let vars = TemplateVars {
+            content,
+            turn_id,
+            session,
+            role: self
+                .entry(session)
+                .and_then(|e| e.role.as_deref())
+                .unwrap_or(""),
+            timestamp: metadata.timestamp,
+        };
+        Ok(template::render(text, &vars))
+    }
+
+    /// Read a clone of a relay register's content and metadata.
     ///
     /// Used by non-inject sinks (clipboard, file) that need the
-    /// content and metadata without session routing. Returns `None`
-    /// if no turn has been captured yet.
+    /// content and metadata without session routing. Returns
+    /// `"buffer_empty"` if no turn has been captured into it yet.
     ///
     /// CONTRACT_REGISTRY.md §266: sinks receive `(content, metadata)`.
-    pub fn relay_content(&self) -> Option<(Vec<u8>, SinkMetadata)> {
-        self.relay_buffer
-            .as_ref()
+    pub fn relay_content(
+        &self,
+        register: Option<&str>,
+    ) -> Result<(Vec<u8>, SinkMetadata), &'static str> {
+        self.register(register)
             .map(|r| (r.content.clone(), r.metadata.clone()))
     }
 
-    /// List all active sessions.
+    /// List active sessions, plus ended ones (tombstones) when `all`.
     ///
     /// Returns a descriptor for each session including whether it
-    /// has a completed turn. Backward compatible with v0.
-    pub fn list_sessions(&self) -> Vec<SessionDescriptor> {
+    /// has a completed turn. Backward compatible with v0. `cwd` is left
+    /// unset; it requires reading `/proc` and is filled by the handler.
+    pub fn list_sessions(&self, all: bool) -> Vec<SessionDescriptor> {
         self.sessions
             .iter()
+            .filter(|(_, entry)| all || entry.ended.is_none())
             .map(|(id, entry)| SessionDescriptor {
                 session: id.clone(),
                 pid: entry.pid,
                 has_turn: !entry.ring.is_empty(),
+                name: entry.name.clone(),
+                role: entry.role.clone(),
+                labels: entry.labels.clone(),
+                command: entry.command.clone(),
+                cwd: None,
+                pattern: entry.pattern.clone(),
+                started_at: entry.started_at,
+                turn_count: entry.ring.turn_count(),
+                last_turn_at: entry.ring.head().map(|r| r.timestamp),
+                last_input_at: entry.last_input_at,
+                state: match entry.ended {
+                    Some(_) if entry.archived => AgentState::Archived,
+                    Some(_) => AgentState::Exited,
+                    None => entry.state,
+                },
+                title: entry.title.clone(),
+                ended_at: entry.ended.as_ref().map(|end| end.at),
+                exit_code: entry.ended.as_ref().and_then(|end| end.exit_code),
+                exit_reason: entry.ended.as_ref().map(|end| end.reason.clone()),
             })
             .collect()
     }
 
-    /// Look up a specific turn by its ID.
+    /// Look up a single turn by reference.
+    ///
+    /// Turn IDs have the format `<session>:<seq>`, split on the first
+    /// `:`. The session part may be the session ID or its name, so
+    /// `planner:3` and `<uuid>:3` address the same turn. Relative
+    /// references (`planner:-1`, `planner:@latest`, `@relay`) are
+    /// accepted too; a range naming more than one turn is
+    /// `"range_not_allowed"`. See [`turnref`].
+    pub fn get_turn(&self, reference: &str) -> Result<&TurnRecord, &'static str> {
+        match self.resolve_turns(reference)?.as_slice() {
+            [record] => Ok(record),
+            _ => Err("range_not_allowed"),
+        }
+    }
+
+    /// Resolve a turn reference to the retained turns it names: a
+    /// range oldest first, a register's combined capture in payload
+    /// order. Returns `"turn_not_found"` for a malformed reference or
+    /// when no retained turn matches, and `"unresolved_focus"` for
+    /// `@focused`, which only the client can resolve.
+    pub fn resolve_turns(&self, reference: &str) -> Result<Vec<&TurnRecord>, &'static str> {
+        let TurnRef { source, selector } = turnref::parse(reference)?;
+        let session = match source {
+            Source::Session(session) => session,
+            Source::Focused => return Err("unresolved_focus"),
+            Source::Relay(register) => {
+                // The register holds a copy; resolve the turns it came
+                // from. Pseudo IDs (`planner:lines`) name no turn.
+                let relay = &self.register(register)?.metadata;
+                let ids = match relay.turn_ids.as_slice() {
+                    [] => std::slice::from_ref(&relay.turn_id),
+                    ids => ids,
+                };
+                let mut records = Vec::new();
+                for id in ids {
+                    match turnref::parse(id) {
+                        Ok(TurnRef {
+                            source: Source::Session(_),
+                            selector: Selector::Seq(_),
+                        }) => records.extend(self.resolve_turns(id)?),
+                        _ => return Err("turn_not_found"),
+                    }
+                }
+                return Ok(records);
+            }
+        };
+        let ring = &self.entry(session).ok_or("turn_not_found")?.ring;
+        let records: Vec<&TurnRecord> = match selector {
+            Selector::Seq(seq) => ring.get_seq(seq).into_iter().collect(),
+            Selector::Latest => ring.head().into_iter().collect(),
+            Selector::Back(n) => ring
+                .iter_newest_first(None)
+                .nth(usize::try_from(n).unwrap_or(usize::MAX))
+                .into_iter()
+                .collect(),
+            Selector::Range(first, last) => {
+                let mut records: Vec<_> = ring
+                    .iter_newest_first(None)
+                    .filter(|r| (first..=last).contains(&r.seq))
+                    .collect();
+                records.reverse();
+                records
+            }
+        };
+        if records.is_empty() {
+            return Err("turn_not_found");
+        }
+        Ok(records)
+    }
+
+    /// Read the content and metadata of the turns a reference names,
+    /// combined as by [`capture_by_id`](Self::capture_by_id), for
+    /// delivering them to a sink without going through a register.
+    pub fn turn_content(
+        &self,
+        reference: &str,
+        separator: Option<&str>,
+    ) -> Result<(Vec<u8>, SinkMetadata), &'static str> {
+        let RelayEntry { content, metadata } = self.combine_turns(&[reference], separator)?;
+        Ok((content, metadata))
+    }
+
+    /// Combine the turns named by `references` into one relay entry,
+    /// in the order given (a range contributes its turns oldest
+    /// first). A single turn is copied as is.
+    ///
+    /// `separator` goes between consecutive turns
+    /// ([`DEFAULT_SEPARATOR`] if `None`); `{turn_id}` in it is replaced
+    /// with the ID of the turn that follows. The entry's turn ID lists
+    /// the sequence numbers (`planner:3,4,5`) and its metadata every
+    /// constituent turn ID. Returns `"mixed_sessions"` when the turns
+    /// come from more than one session.
+    fn combine_turns(
+        &self,
+        references: &[&str],
+        separator: Option<&str>,
+    ) -> Result<RelayEntry, &'static str> {
+        let mut records = Vec::new();
+        for reference in references {
+            records.extend(self.resolve_turns(reference)?);
+        }
+        let [first, rest @ ..] = records.as_slice() else {
+            return Err("turn_not_found");
+        };
+        if rest.is_empty() {
+            return Ok(RelayEntry::from_record(first));
+        }
+        // Turn IDs are `<session or name>:<seq>` and a session's prefix
+        // never changes, so the prefix identifies the session.
+        let session = |r: &TurnRecord| -> String {
+            r.turn_id
+                .rsplit_once(':')
+                .map_or(r.turn_id.clone(), |(s, _)| s.to_string())
+        };
+        let prefix = session(first);
+        if rest.iter().any(|r| session(r) != prefix) {
+            return Err("mixed_sessions");
+        }
+
+        let separator = separator.unwrap_or(DEFAULT_SEPARATOR);
+        let mut content = first.content.clone();
+        for record in rest {
+            content.extend_from_slice(separator.replace("{turn_id}", &record.turn_id).as_bytes());
+            content.extend_from_slice(&record.content);
+        }
+        let seqs: Vec<String> = records.iter().map(|r| r.seq.to_string()).collect();
+        Ok(RelayEntry {
+            metadata: SinkMetadata {
+                turn_id: format!("{prefix}:{}", seqs.join(",")),
+                timestamp: records
+                    .iter()
+                    .map(|r| r.timestamp)
+                    .max()
+                    .unwrap_or_default(),
+                byte_length: content.len() as u32,
+                interrupted: records.iter().any(|r| r.interrupted),
+                truncated: records.iter().any(|r| r.truncated),
+                context: TurnContext::default(),
+                turn_ids: records.iter().map(|r| r.turn_id.clone()).collect(),
+            },
+            content,
+        })
+    }
+
+    /// Gather the turns named by `references`, from any sessions, into
+    /// one payload with a labelled section per turn, in the order given.
     ///
-    /// Turn IDs have the format `<session_id>:<seq>`. The session ID
-    /// is extracted by splitting on the first `:`.
-    pub fn get_turn(&self, turn_id: &str) -> Result<&TurnRecord, &'static str> {
-        let session_id = turn_id
-            .split_once(':')
-            .map(|(s, _)| s)
-            .ok_or("turn_not_found")?;
-        let entry = self.sessions.get(session_id).ok_or("turn_not_found")?;
-        entry.ring.get(turn_id).ok_or("turn_not_found")
+    /// The metadata's turn ID joins the constituent IDs with `+`
+    /// (`planner:3+reviewer:7`) and `turn_ids` lists them.
+    /// CONTRACT_REGISTRY.md §Gather.
+    pub fn gather(
+        &self,
+        references: &[&str],
+        style: SectionStyle,
+    ) -> Result<(Vec<u8>, SinkMetadata), &'static str> {
+        let mut records = Vec::new();
+        for reference in references {
+            records.extend(self.resolve_turns(reference)?);
+        }
+        if records.is_empty() {
+            return Err("turn_not_found");
+        }
+        let mut content = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            if i > 0 {
+                content.push(b'\n');
+            }
+            push_section(&mut content, record, style);
+        }
+        let turn_ids: Vec<String> = records.iter().map(|r| r.turn_id.clone()).collect();
+        let metadata = SinkMetadata {
+            turn_id: turn_ids.join("+"),
+            timestamp: records
+                .iter()
+                .map(|r| r.timestamp)
+                .max()
+                .unwrap_or_default(),
+            byte_length: content.len() as u32,
+            interrupted: records.iter().any(|r| r.interrupted),
+            truncated: records.iter().any(|r| r.truncated),
+            context: TurnContext::default(),
+            turn_ids,
+        };
+        Ok((content, metadata))
+    }
+
+    /// Put content built by the broker (a gathered payload) into a
+    /// relay register.
+    pub fn store_relay(
+        &mut self,
+        register: Option<&str>,
+        content: Vec<u8>,
+        metadata: SinkMetadata,
+    ) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let result = CaptureResult {
+            size: content.len() as u32,
+            turn_id: metadata.turn_id.clone(),
+        };
+        self.set_register(register, RelayEntry { content, metadata });
+        Ok(result)
     }
 
     /// List turn descriptors for a session, newest first.
@@ -272,69 +1250,365 @@ impl BrokerState {
         session_id: &str,
         limit: Option<usize>,
     ) -> Result<Vec<&TurnRecord>, &'static str> {
-        let entry = self.sessions.get(session_id).ok_or("session_not_found")?;
+        let entry = self.entry(session_id).ok_or("session_not_found")?;
         Ok(entry.ring.iter_newest_first(limit).collect())
     }
 
-    /// Capture a specific turn by ID into the relay buffer.
+    /// Subscribe a connection to a live session's output.
     ///
-    /// Like [`capture`](Self::capture) but resolves a specific turn
-    /// from the ring instead of the head.
-    pub fn capture_by_id(&mut self, turn_id: &str) -> Result<CaptureResult, &'static str> {
-        let session_id = turn_id
-            .split_once(':')
-            .map(|(s, _)| s)
-            .ok_or("turn_not_found")?;
-        let entry = self.sessions.get(session_id).ok_or("turn_not_found")?;
-        let record = entry.ring.get(turn_id).ok_or("turn_not_found")?;
-        let size = record.content.len() as u32;
-        let turn_id = record.turn_id.clone();
-        self.relay_buffer = Some(RelayEntry {
-            content: record.content.clone(),
-            metadata: SinkMetadata {
-                turn_id: turn_id.clone(),
-                timestamp: record.timestamp,
-                byte_length: record.byte_length,
-                interrupted: record.interrupted,
-                truncated: record.truncated,
-            },
-        });
-        Ok(CaptureResult { size, turn_id })
+    /// Returns the session's wrapper connection when this is the first
+    /// subscriber, so the caller can enable the wrapper's output tap.
+    pub fn subscribe(
+        &mut self,
+        session_id: &str,
+        subscriber: ConnectionId,
+    ) -> Result<Option<ConnectionId>, &'static str> {
+        let wrapper = self.wrapper_connection(session_id)?;
+        let entry = self.entry_mut(session_id).ok_or("session_not_found")?;
+        let first = entry.subscribers.is_empty();
+        entry.subscribers.insert(subscriber);
+        Ok(first.then_some(wrapper))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    fn state() -> BrokerState {
-        BrokerState::new(RingConfig::default())
+    /// Connections subscribed to a session's output.
+    pub fn subscribers(&self, session_id: &str) -> Vec<ConnectionId> {
+        self.entry(session_id)
+            .map(|entry| entry.subscribers.iter().copied().collect())
+            .unwrap_or_default()
     }
 
-    fn conn() -> ConnectionId {
-        ConnectionId::new()
+    /// Remove and return a session's subscribers (the session ended).
+    pub fn take_subscribers(&mut self, session_id: &str) -> Vec<ConnectionId> {
+        match self.sessions.get_mut(session_id) {
+            Some(entry) => entry.subscribers.drain().collect(),
+            None => Vec::new(),
+        }
     }
 
-    // -- Connection tracking --
-
-    #[test]
-    fn add_and_remove_connection() {
-        let mut s = state();
-        let c = conn();
-        s.add_connection(c, Role::Wrapper);
-        assert!(s.connections.contains_key(&c));
-        s.remove_connection(c);
-        assert!(!s.connections.contains_key(&c));
+    /// Drop a connection's subscriptions. Returns `(session, wrapper)`
+    /// for each live session left without subscribers, whose output tap
+    /// the caller should disable.
+    pub fn unsubscribe(&mut self, subscriber: ConnectionId) -> Vec<(String, ConnectionId)> {
+        let mut idle_taps = Vec::new();
+        for (id, entry) in &mut self.sessions {
+            if entry.subscribers.remove(&subscriber)
+                && entry.subscribers.is_empty()
+                && entry.ended.is_none()
+            {
+                idle_taps.push((id.clone(), entry.connection_id));
+            }
+        }
+        idle_taps
     }
 
-    #[test]
-    fn connection_role_tracking() {
-        let mut s = state();
-        let c = conn();
-        s.add_connection(c, Role::Client);
-        assert_eq!(s.connection_role(c), Some(Role::Client));
-        s.remove_connection(c);
-        assert_eq!(s.connection_role(c), None);
+    /// IDs of the live sessions registered by a wrapper connection.
+    pub fn live_sessions_of(&self, connection_id: ConnectionId) -> Vec<String> {
+        self.sessions
+            .iter()
+            .filter(|(_, e)| e.connection_id == connection_id && e.ended.is_none())
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Start a query to a live session's wrapper on behalf of client
+    /// request `request_id`. Returns the query token and the wrapper
+    /// connection to send it to.
+    pub fn start_query(
+        &mut self,
+        session: &str,
+        request_id: u32,
+        register: Option<String>,
+    ) -> Result<(u32, ConnectionId), &'static str> {
+        let connection_id = self.wrapper_connection(session)?;
+        let session = self
+            .resolve_session(session)
+            .ok_or("session_not_found")?
+            .to_string();
+        let token = self.next_query;
+        self.next_query = self.next_query.checked_add(1).unwrap_or(1);
+        self.pending_queries.insert(
+            token,
+            PendingQuery {
+                request_id,
+                session,
+                connection_id,
+                register,
+            },
+        );
+        Ok((token, connection_id))
+    }
+
+    /// Remove a pending query when its reply arrives or it times out.
+    pub fn take_query(&mut self, token: u32) -> Option<PendingQuery> {
+        self.pending_queries.remove(&token)
+    }
+
+    /// Put scrollback lines returned by a session's wrapper into a
+    /// relay register.
+    ///
+    /// The relay metadata carries the pseudo turn ID `<session>:lines`,
+    /// which is not a registry turn.
+    pub fn capture_lines(
+        &mut self,
+        session_id: &str,
+        content: Vec<u8>,
+        timestamp: u64,
+        register: Option<&str>,
+    ) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let entry = self.entry(session_id).ok_or("session_not_found")?;
+        let prefix = entry.name.as_deref().unwrap_or(session_id);
+        let turn_id = format!("{prefix}:lines");
+        let size = content.len() as u32;
+        let relay = RelayEntry {
+            content,
+            metadata: SinkMetadata {
+                turn_id: turn_id.clone(),
+                timestamp,
+                byte_length: size,
+                interrupted: false,
+                truncated: false,
+                context: TurnContext::default(),
+                turn_ids: Vec::new(),
+            },
+        };
+        self.set_register(register, relay);
+        Ok(CaptureResult { size, turn_id })
+    }
+
+    /// Capture specific turns by ID or reference into a relay register.
+    ///
+    /// Like [`capture`](Self::capture) but resolves specific turns from
+    /// the ring instead of the head. Several turns of one session are
+    /// concatenated (see [`combine_turns`](Self::combine_turns)).
+    pub fn capture_by_id(
+        &mut self,
+        references: &[&str],
+        separator: Option<&str>,
+        register: Option<&str>,
+    ) -> Result<CaptureResult, &'static str> {
+        register.map(validate_register).transpose()?;
+        let relay = self.combine_turns(references, separator)?;
+        let size = relay.content.len() as u32;
+        let turn_id = relay.metadata.turn_id.clone();
+        self.set_register(register, relay);
+        Ok(CaptureResult { size, turn_id })
+    }
+
+    /// Snapshot the given sessions (IDs or names; all sessions, ended
+    /// ones included, when empty) and the relay registers.
+    ///
+    /// CONTRACT_BROKER.md §Snapshots. `now` is the Unix epoch millis
+    /// recorded as the snapshot time.
+    pub fn snapshot(&self, sessions: &[String], now: u64) -> Result<Snapshot, &'static str> {
+        let mut ids: Vec<&str> = if sessions.is_empty() {
+            self.sessions.keys().map(String::as_str).collect()
+        } else {
+            sessions
+                .iter()
+                .map(|key| self.resolve_session(key).ok_or("session_not_found"))
+                .collect::<Result<_, _>>()?
+        };
+        ids.sort_by_key(|id| (self.sessions[*id].started_at, *id));
+        ids.dedup();
+        let sessions = ids
+            .into_iter()
+            .map(|id| {
+                let entry = &self.sessions[id];
+                let mut turns: Vec<TurnSnapshot> = entry
+                    .ring
+                    .iter_newest_first(None)
+                    .map(|r| TurnSnapshot {
+                        turn_id: r.turn_id.clone(),
+                        seq: r.seq,
+                        content: r.content.clone(),
+                        timestamp: r.timestamp,
+                        byte_length: r.byte_length,
+                        interrupted: r.interrupted,
+                        truncated: r.truncated,
+                        context: r.context.clone(),
+                        turn_ids: Vec::new(),
+                    })
+                    .collect();
+                turns.reverse();
+                SessionSnapshot {
+                    session: id.to_string(),
+                    name: entry.name.clone(),
+                    role: entry.role.clone(),
+                    labels: entry.labels.clone(),
+                    command: entry.command.clone(),
+                    pattern: entry.pattern.clone(),
+                    pid: entry.pid,
+                    started_at: entry.started_at,
+                    title: entry.title.clone(),
+                    ended_at: entry.ended.as_ref().map(|end| end.at),
+                    exit_code: entry.ended.as_ref().and_then(|end| end.exit_code),
+                    exit_reason: entry.ended.as_ref().map(|end| end.reason.clone()),
+                    turns,
+                }
+            })
+            .collect();
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: now,
+            sessions,
+            relay: self.relay_buffer.as_ref().map(RelayEntry::to_snapshot),
+            registers: self
+                .registers
+                .iter()
+                .map(|(name, relay)| (name.clone(), relay.to_snapshot()))
+                .collect(),
+        })
+    }
+
+    /// Restore a snapshot's sessions as read-only archives, and the
+    /// relay registers it holds. Returns the restored session IDs.
+    ///
+    /// All or nothing: a session whose ID or name belongs to a live
+    /// session fails the load with `"duplicate_session"` or
+    /// `"duplicate_name"`. Tombstones and archives with the same ID or
+    /// name are replaced. A snapshot that uses an ID or name twice, or
+    /// holds a turn seq the registry rejects, is `"invalid_snapshot"`.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<Vec<String>, &'static str> {
+        for name in snapshot.registers.keys() {
+            validate_register(name).map_err(|_| "invalid_snapshot")?;
+        }
+        // IDs and names share one namespace: lookups accept either.
+        let mut seen = HashSet::new();
+        for session in &snapshot.sessions {
+            for key in std::iter::once(&session.session).chain(&session.name) {
+                if !seen.insert(key.as_str()) {
+                    return Err("invalid_snapshot");
+                }
+            }
+        }
+        let mut restored = Vec::with_capacity(snapshot.sessions.len());
+        for session in snapshot.sessions {
+            if self
+                .entry(&session.session)
+                .is_some_and(|e| e.ended.is_none())
+            {
+                return Err("duplicate_session");
+            }
+            if let Some(ref name) = session.name {
+                if name.is_empty() || name.contains(':') {
+                    return Err("invalid_snapshot");
+                }
+                if self.entry(name).is_some_and(|e| e.ended.is_none()) {
+                    return Err("duplicate_name");
+                }
+            }
+            let entry = self.archive_entry(session, snapshot.created_at)?;
+            restored.push(entry);
+        }
+        let ids = restored.iter().map(|(id, _)| id.clone()).collect();
+        for (id, entry) in restored {
+            self.remove_tombstone(&id);
+            if let Some(ref name) = entry.name {
+                self.remove_tombstone(name);
+            }
+            self.sessions.insert(id, entry);
+        }
+        if let Some(relay) = snapshot.relay {
+            self.set_register(None, RelayEntry::from_snapshot(relay));
+        }
+        for (name, relay) in snapshot.registers {
+            self.set_register(Some(&name), RelayEntry::from_snapshot(relay));
+        }
+        Ok(ids)
+    }
+
+    /// Build an archived session entry from its snapshot. A session
+    /// that was live when saved ends at the snapshot time.
+    fn archive_entry(
+        &self,
+        session: SessionSnapshot,
+        created_at: u64,
+    ) -> Result<(String, SessionEntry), &'static str> {
+        // Never truncate or evict restored turns further.
+        let depth = self.ring_config.depth.max(session.turns.len());
+        let max_turn_bytes = session
+            .turns
+            .iter()
+            .map(|t| t.content.len())
+            .fold(self.ring_config.max_turn_bytes, usize::max);
+        let mut ring = TurnRingBuffer::new(
+            session
+                .name
+                .clone()
+                .unwrap_or_else(|| session.session.clone()),
+            depth,
+            max_turn_bytes,
+        );
+        for turn in session.turns {
+            let record = ring
+                .push_with_seq(turn.seq, turn.content, turn.interrupted, turn.timestamp)
+                .map_err(|_| "invalid_snapshot")?;
+            record.byte_length = turn.byte_length;
+            record.truncated = turn.truncated;
+            record.context = turn.context;
+        }
+        let entry = SessionEntry {
+            connection_id: ConnectionId::new(),
+            pid: session.pid,
+            name: session.name,
+            role: session.role,
+            labels: session.labels,
+            command: session.command,
+            pattern: session.pattern,
+            started_at: session.started_at,
+            last_input_at: None,
+            state: AgentState::Exited,
+            title: session.title,
+            ring,
+            ended: Some(SessionEnd {
+                at: session.ended_at.unwrap_or(created_at),
+                exit_code: session.exit_code,
+                reason: session
+                    .exit_reason
+                    .unwrap_or_else(|| "archived".to_string()),
+            }),
+            archived: true,
+            subscribers: HashSet::new(),
+            route_chain: Vec::new(),
+            pending_routes: VecDeque::new(),
+        };
+        Ok((session.session, entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> BrokerState {
+        BrokerState::new(RingConfig::default())
+    }
+
+    fn conn() -> ConnectionId {
+        ConnectionId::new()
+    }
+
+    // -- Connection tracking --
+
+    #[test]
+    fn add_and_remove_connection() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        assert!(s.connections.contains_key(&c));
+        s.remove_connection(c, 1000);
+        assert!(!s.connections.contains_key(&c));
+    }
+
+    #[test]
+    fn connection_role_tracking() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Client);
+        assert_eq!(s.connection_role(c), Some(Role::Client));
+        s.remove_connection(c, 1000);
+        assert_eq!(s.connection_role(c), None);
     }
 
     // -- Registration --
@@ -344,7 +1618,10 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        assert!(s.register_session("s1".into(), c, 100).is_ok());
+        assert!(
+            s.register_session("s1".into(), c, 100, SessionMeta::default())
+                .is_ok()
+        );
         assert_eq!(s.sessions.len(), 1);
     }
 
@@ -353,28 +1630,109 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         assert_eq!(
-            s.register_session("s1".into(), c, 200),
+            s.register_session("s1".into(), c, 200, SessionMeta::default()),
             Err("duplicate_session")
         );
     }
 
+    fn named(name: &str) -> SessionMeta {
+        SessionMeta {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
     #[test]
-    fn deregister_session_removes_entry() {
+    fn register_duplicate_name() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.deregister_session("s1");
-        assert!(s.sessions.is_empty());
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        assert_eq!(
+            s.register_session("s2".into(), c, 200, named("planner")),
+            Err("duplicate_name")
+        );
+        // A name may not shadow another session's ID either.
+        assert_eq!(
+            s.register_session("s3".into(), c, 300, named("s1")),
+            Err("duplicate_name")
+        );
+    }
+
+    #[test]
+    fn register_invalid_name() {
+        let mut s = state();
+        let c = conn();
+        assert_eq!(
+            s.register_session("s1".into(), c, 100, named("a:b")),
+            Err("invalid_name")
+        );
+        assert_eq!(
+            s.register_session("s1".into(), c, 100, named("")),
+            Err("invalid_name")
+        );
+    }
+
+    #[test]
+    fn named_session_addressable_by_name() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("uuid-1".into(), c, 100, named("planner"))
+            .unwrap();
+        let turn_id = s
+            .store_turn(
+                "uuid-1",
+                b"plan".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
+        assert_eq!(turn_id, "planner:1");
+
+        let result = s.capture("planner", None).unwrap();
+        assert_eq!(result.turn_id, "planner:1");
+        assert_eq!(s.list_turns("planner", None).unwrap().len(), 1);
+        let (_, target) = s.paste_content("planner", None).unwrap();
+        assert_eq!(target, c);
+
+        // Turn IDs resolve via either the name or the session ID.
+        assert_eq!(s.get_turn("planner:1").unwrap().content, b"plan");
+        assert_eq!(s.get_turn("uuid-1:1").unwrap().turn_id, "planner:1");
+
+        let list = s.list_sessions(false);
+        assert_eq!(list[0].name.as_deref(), Some("planner"));
+    }
+
+    #[test]
+    fn deregister_session_leaves_tombstone() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.deregister_session("s1", Some(1), Some("exited with code 1".into()), 5000);
+        assert!(s.list_sessions(false).is_empty());
+
+        let all = s.list_sessions(true);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].state, AgentState::Exited);
+        assert_eq!(all[0].ended_at, Some(5000));
+        assert_eq!(all[0].exit_code, Some(1));
+        assert_eq!(all[0].exit_reason.as_deref(), Some("exited with code 1"));
     }
 
     #[test]
     fn deregister_nonexistent_is_ok() {
         let mut s = state();
         // No panic, no error.
-        s.deregister_session("nonexistent");
+        s.deregister_session("nonexistent", None, None, 1000);
     }
 
     // -- Implicit deregister --
@@ -384,9 +1742,27 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.remove_connection(c);
-        assert!(s.sessions.is_empty());
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.remove_connection(c, 1000);
+        assert!(s.list_sessions(false).is_empty());
+        let all = s.list_sessions(true);
+        assert_eq!(all[0].exit_code, None);
+        assert_eq!(all[0].exit_reason.as_deref(), Some("connection lost"));
+    }
+
+    #[test]
+    fn remove_connection_keeps_reported_exit() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.deregister_session("s1", Some(0), Some("exited with code 0".into()), 1000);
+        s.remove_connection(c, 2000);
+        let all = s.list_sessions(true);
+        assert_eq!(all[0].ended_at, Some(1000));
+        assert_eq!(all[0].exit_code, Some(0));
     }
 
     #[test]
@@ -396,11 +1772,136 @@ mod tests {
         let c2 = conn();
         s.add_connection(c1, Role::Wrapper);
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s1".into(), c1, 100).unwrap();
-        s.register_session("s2".into(), c2, 200).unwrap();
-        s.remove_connection(c1);
-        assert_eq!(s.sessions.len(), 1);
-        assert!(s.sessions.contains_key("s2"));
+        s.register_session("s1".into(), c1, 100, SessionMeta::default())
+            .unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
+        s.remove_connection(c1, 1000);
+        let live = s.list_sessions(false);
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].session, "s2");
+    }
+
+    // -- Tombstones --
+
+    fn ended_with_turn(s: &mut BrokerState, session: &str, at: u64) {
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session(session.into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            session,
+            b"last words".to_vec(),
+            false,
+            at,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.deregister_session(session, Some(139), Some("killed by SIGSEGV".into()), at);
+    }
+
+    #[test]
+    fn tombstone_turns_remain_readable() {
+        let mut s = state();
+        ended_with_turn(&mut s, "s1", 1000);
+        assert_eq!(s.get_turn("s1:1").unwrap().content, b"last words");
+        assert_eq!(s.list_turns("s1", None).unwrap().len(), 1);
+        let result = s.capture_by_id(&["s1:1"], None, None).unwrap();
+        assert_eq!(result.turn_id, "s1:1");
+        assert_eq!(s.relay_content(None).unwrap().0, b"last words");
+    }
+
+    #[test]
+    fn tombstone_is_read_only() {
+        let mut s = state();
+        ended_with_turn(&mut s, "s1", 1000);
+        assert_eq!(
+            s.store_turn(
+                "s1",
+                b"more".to_vec(),
+                false,
+                2000,
+                None,
+                TurnContext::default()
+            ),
+            Err("session_not_found")
+        );
+        assert_eq!(s.record_input("s1", 2000), Err("session_not_found"));
+        s.capture("s1", None).unwrap();
+        assert_eq!(s.paste_content("s1", None).unwrap_err(), "session_ended");
+    }
+
+    #[test]
+    fn tombstones_pruned_after_grace_period() {
+        let mut s = state().with_tombstones(TombstoneConfig {
+            ttl_ms: 1000,
+            max: 16,
+        });
+        ended_with_turn(&mut s, "s1", 1000);
+        s.prune_tombstones(1999);
+        assert_eq!(s.list_sessions(true).len(), 1);
+        s.prune_tombstones(2000);
+        assert!(s.list_sessions(true).is_empty());
+        assert_eq!(s.get_turn("s1:1").unwrap_err(), "turn_not_found");
+    }
+
+    #[test]
+    fn tombstone_count_evicts_oldest() {
+        let mut s = state().with_tombstones(TombstoneConfig {
+            ttl_ms: u64::MAX,
+            max: 2,
+        });
+        ended_with_turn(&mut s, "s1", 1000);
+        ended_with_turn(&mut s, "s2", 2000);
+        ended_with_turn(&mut s, "s3", 3000);
+        let mut ids: Vec<String> = s
+            .list_sessions(true)
+            .into_iter()
+            .map(|d| d.session)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["s2", "s3"]);
+    }
+
+    #[test]
+    fn zero_tombstones_removes_on_end() {
+        let mut s = state().with_tombstones(TombstoneConfig {
+            ttl_ms: u64::MAX,
+            max: 0,
+        });
+        ended_with_turn(&mut s, "s1", 1000);
+        assert!(s.sessions.is_empty());
+    }
+
+    #[test]
+    fn register_replaces_tombstone() {
+        let mut s = state();
+        ended_with_turn(&mut s, "s1", 1000);
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        // Same session ID, e.g. a wrapper reconnecting after a drop.
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        let list = s.list_sessions(true);
+        assert_eq!(list.len(), 1);
+        assert_ne!(list[0].state, AgentState::Exited);
+        assert!(!list[0].has_turn);
+    }
+
+    #[test]
+    fn register_takes_name_from_tombstone() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        s.deregister_session("s1", Some(0), None, 1000);
+        s.register_session("s2".into(), c, 200, named("planner"))
+            .unwrap();
+        let list = s.list_sessions(true);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].session, "s2");
     }
 
     // -- Turn storage --
@@ -410,9 +1911,17 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         let turn_id = s
-            .store_turn("s1", b"turn content".to_vec(), false, 1000)
+            .store_turn(
+                "s1",
+                b"turn content".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
             .unwrap();
         assert_eq!(turn_id, "s1:1");
     }
@@ -422,9 +1931,28 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        let t1 = s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        let t2 = s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        let t1 = s
+            .store_turn(
+                "s1",
+                b"first".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
+        let t2 = s
+            .store_turn(
+                "s1",
+                b"second".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
         assert_eq!(t1, "s1:1");
         assert_eq!(t2, "s1:2");
     }
@@ -434,20 +1962,75 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"first".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"second".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         let head = s.sessions["s1"].ring.head().unwrap();
         assert_eq!(head.content, b"second");
     }
 
+    #[test]
+    fn turn_context_reaches_registry_and_relay() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        let context = TurnContext {
+            submitted_at: Some(1000),
+            duration_ms: Some(4000),
+            line_count: Some(3),
+            cwd: Some("/repo".into()),
+            git: None,
+        };
+        s.store_turn(
+            "s1",
+            b"a\nb\nc".to_vec(),
+            false,
+            5000,
+            None,
+            context.clone(),
+        )
+        .unwrap();
+        assert_eq!(s.get_turn("s1:1").unwrap().context, context);
+        s.capture("s1", None).unwrap();
+        let (_, metadata) = s.relay_content(None).unwrap();
+        assert_eq!(metadata.context, context);
+    }
+
     #[test]
     fn store_turn_stores_interrupted() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), true, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            true,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         let head = s.sessions["s1"].ring.head().unwrap();
         assert!(head.interrupted);
     }
@@ -456,7 +2039,14 @@ mod tests {
     fn store_turn_session_not_found() {
         let mut s = state();
         assert_eq!(
-            s.store_turn("nonexistent", b"data".to_vec(), false, 1000),
+            s.store_turn(
+                "nonexistent",
+                b"data".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default()
+            ),
             Err("session_not_found")
         );
     }
@@ -468,10 +2058,18 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
             .unwrap();
-        let result = s.capture("s1").unwrap();
+        s.store_turn(
+            "s1",
+            b"turn data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        let result = s.capture("s1", None).unwrap();
         assert_eq!(result.size, 9);
         assert_eq!(result.turn_id, "s1:1");
         assert_eq!(
@@ -485,10 +2083,27 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"a".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"b".to_vec(), false, 1000).unwrap();
-        let result = s.capture("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"a".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"b".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        let result = s.capture("s1", None).unwrap();
         // Captures the head (latest = seq 2).
         assert_eq!(result.turn_id, "s1:2");
     }
@@ -496,7 +2111,7 @@ mod tests {
     #[test]
     fn capture_session_not_found() {
         let mut s = state();
-        assert_eq!(s.capture("nonexistent"), Err("session_not_found"));
+        assert_eq!(s.capture("nonexistent", None), Err("session_not_found"));
     }
 
     #[test]
@@ -504,8 +2119,9 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.capture("s1"), Err("no_turn"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(s.capture("s1", None), Err("no_turn"));
     }
 
     #[test]
@@ -513,10 +2129,18 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
             .unwrap();
-        s.capture("s1").unwrap();
+        s.store_turn(
+            "s1",
+            b"turn data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
         // Session's ring still has the turn.
         assert!(!s.sessions["s1"].ring.is_empty());
     }
@@ -526,14 +2150,120 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"first".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
+        s.store_turn(
+            "s1",
+            b"second".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
         assert_eq!(s.relay_buffer.as_ref().unwrap().content, b"second".to_vec());
     }
 
+    // -- Registers --
+
+    #[test]
+    fn named_registers_are_independent() {
+        let mut s = state();
+        planner_with_turns(&mut s);
+        s.capture_by_id(&["planner:1"], None, Some("a")).unwrap();
+        // The unnamed register keeps the capture made before.
+        assert_eq!(s.relay_content(None).unwrap().0, b"two");
+        assert_eq!(s.relay_content(Some("a")).unwrap().0, b"one");
+        assert_eq!(s.relay_content(Some("b")).unwrap_err(), "buffer_empty");
+        let (content, _) = s.paste_content("planner", Some("a")).unwrap();
+        assert_eq!(content, b"one");
+
+        let registers = s.list_registers();
+        assert_eq!(registers.len(), 2);
+        assert_eq!(registers[0].register, None);
+        assert_eq!(registers[0].turn_id, "planner:2");
+        assert_eq!(registers[1].register.as_deref(), Some("a"));
+        assert_eq!(registers[1].size, 3);
+    }
+
+    #[test]
+    fn invalid_register_name_is_rejected() {
+        let mut s = state();
+        planner_with_turns(&mut s);
+        assert_eq!(s.capture("planner", Some("")), Err("invalid_register"));
+        assert_eq!(s.capture("planner", Some("a:b")), Err("invalid_register"));
+        assert_eq!(
+            s.relay_content(Some("no spaces")).unwrap_err(),
+            "invalid_register"
+        );
+        assert_eq!(s.list_registers().len(), 1);
+    }
+
+    #[test]
+    fn set_and_clear_relay() {
+        let mut s = state();
+        let result = s
+            .set_relay(Some("a"), b"notes".to_vec(), "notes.md", 7000)
+            .unwrap();
+        assert_eq!(result.turn_id, "notes.md:set");
+        let (content, metadata) = s.relay_content(Some("a")).unwrap();
+        assert_eq!(content, b"notes");
+        assert_eq!(metadata.timestamp, 7000);
+        assert_eq!(metadata.byte_length, 5);
+        assert!(s.relay_content(None).is_err());
+
+        s.clear_relay(Some("a")).unwrap();
+        assert_eq!(s.relay_content(Some("a")).unwrap_err(), "buffer_empty");
+        // Clearing an empty register is fine.
+        s.clear_relay(None).unwrap();
+        assert_eq!(s.clear_relay(Some("")), Err("invalid_register"));
+    }
+
+    #[test]
+    fn undo_restores_previous_relay() {
+        let mut s = state();
+        planner_with_turns(&mut s);
+        s.capture_by_id(&["planner:1"], None, None).unwrap();
+        s.set_relay(Some("a"), b"notes".to_vec(), "stdin", 3000)
+            .unwrap();
+        s.clear_relay(Some("a")).unwrap();
+
+        let history = s.relay_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].register.as_deref(), Some("a"));
+        assert_eq!(history[1].turn_id, "planner:2");
+
+        let restored = s.undo_relay(None).unwrap();
+        assert_eq!(restored.turn_id, "planner:2");
+        assert_eq!(s.relay_content(None).unwrap().0, b"two");
+        assert_eq!(s.undo_relay(None), Err("no_history"));
+        assert_eq!(s.undo_relay(Some("a")).unwrap().turn_id, "stdin:set");
+        assert!(s.relay_history().is_empty());
+    }
+
+    #[test]
+    fn relay_history_is_bounded() {
+        let mut s = state();
+        for i in 0..RELAY_HISTORY_DEPTH + 5 {
+            s.set_relay(None, vec![b'x'; i], "stdin", i as u64).unwrap();
+        }
+        let history = s.relay_history();
+        assert_eq!(history.len(), RELAY_HISTORY_DEPTH);
+        // The newest replaced entry is the one before the current.
+        assert_eq!(history[0].size as usize, RELAY_HISTORY_DEPTH + 3);
+    }
+
     // -- Paste --
 
     #[test]
@@ -543,24 +2273,207 @@ mod tests {
         let c2 = conn();
         s.add_connection(c1, Role::Wrapper);
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s1".into(), c1, 100).unwrap();
-        s.register_session("s2".into(), c2, 200).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c1, 100, SessionMeta::default())
             .unwrap();
-        s.capture("s1").unwrap();
-
-        let (content, target) = s.paste_content("s2").unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"turn data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
+
+        let (content, target) = s.paste_content("s2", None).unwrap();
         assert_eq!(content, b"turn data");
         assert_eq!(target, c2);
     }
 
+    #[test]
+    fn inject_targets_expand_role() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        for (id, name, started_at) in [
+            ("s1", Some("impl-b"), 20),
+            ("s2", Some("impl-a"), 10),
+            ("s3", None, 30),
+        ] {
+            let meta = SessionMeta {
+                name: name.map(String::from),
+                role: Some("implementer".into()),
+                started_at,
+                ..Default::default()
+            };
+            s.register_session(id.into(), c, 100, meta).unwrap();
+        }
+        s.register_session("s4".into(), c, 100, named("rev"))
+            .unwrap();
+
+        assert_eq!(
+            s.inject_targets(&[], Some("implementer")).unwrap(),
+            ["impl-a", "impl-b", "s3"]
+        );
+        // Named twice (by ID and via the role) and unknown sessions.
+        assert_eq!(
+            s.inject_targets(&["rev", "s2", "nope"], Some("implementer"))
+                .unwrap(),
+            ["rev", "s2", "nope", "impl-b", "s3"]
+        );
+        assert_eq!(s.inject_targets(&["rev", "s4"], None).unwrap(), ["rev"]);
+        assert_eq!(s.inject_targets(&[], Some("tester")), Err("role_not_found"));
+    }
+
+    #[test]
+    fn routes_relay_turns_once_around_a_cycle() {
+        let mut s = state();
+        let (cp, ci) = (conn(), conn());
+        s.add_connection(cp, Role::Wrapper);
+        s.add_connection(ci, Role::Wrapper);
+        s.register_session("s1".into(), cp, 100, named("planner"))
+            .unwrap();
+        s.register_session("s2".into(), ci, 100, named("impl"))
+            .unwrap();
+        let route = |source: &str, target: &str, wait_idle| RouteSpec {
+            source: source.into(),
+            target: target.into(),
+            wait_idle,
+            ..Default::default()
+        };
+        s.add_route(route("planner", "impl", false), true, 0)
+            .unwrap();
+        s.add_route(route("s2", "planner", true), true, 0).unwrap();
+        let turn = |s: &mut BrokerState, session: &str, content: &[u8]| {
+            s.store_turn(
+                session,
+                content.to_vec(),
+                false,
+                1000,
+                None,
+                Default::default(),
+            )
+            .unwrap();
+            s.route_turn(session)
+        };
+
+        assert_eq!(turn(&mut s, "s1", b"plan"), [(ci, b"plan".to_vec())]);
+        // Waits for the planner to be idle.
+        assert!(turn(&mut s, "s2", b"done").is_empty());
+        assert_eq!(s.take_route_delivery("s1"), None);
+        s.set_agent_state("s1", AgentState::Idle).unwrap();
+        assert_eq!(s.take_route_delivery("s1"), Some((cp, b"done".to_vec())));
+        assert_eq!(s.take_route_delivery("s1"), None);
+        // The reply came around the cycle: route 1 does not fire again
+        // until a turn that no route led to.
+        assert!(turn(&mut s, "s1", b"ack").is_empty());
+        assert_eq!(turn(&mut s, "s1", b"plan 2"), [(ci, b"plan 2".to_vec())]);
+
+        s.enable_route(1, false).unwrap();
+        assert!(turn(&mut s, "s1", b"plan 3").is_empty());
+        let fired: Vec<_> = s.list_routes().iter().map(|r| r.fired).collect();
+        assert_eq!(fired, [2, 1]);
+    }
+
+    #[test]
+    fn queued_route_joins_the_chain_when_released() {
+        let mut s = state();
+        let (cp, ci) = (conn(), conn());
+        s.add_connection(cp, Role::Wrapper);
+        s.add_connection(ci, Role::Wrapper);
+        s.register_session("s1".into(), cp, 100, named("planner"))
+            .unwrap();
+        s.register_session("s2".into(), ci, 100, named("impl"))
+            .unwrap();
+        let route = |source: &str, target: &str, wait_idle| RouteSpec {
+            source: source.into(),
+            target: target.into(),
+            wait_idle,
+            ..Default::default()
+        };
+        s.add_route(route("planner", "impl", true), true, 0)
+            .unwrap();
+        s.add_route(route("impl", "planner", false), true, 0)
+            .unwrap();
+        let turn = |s: &mut BrokerState, session: &str, content: &[u8]| {
+            s.store_turn(
+                session,
+                content.to_vec(),
+                false,
+                1000,
+                None,
+                Default::default(),
+            )
+            .unwrap();
+            s.route_turn(session)
+        };
+
+        s.set_agent_state("s2", AgentState::Busy).unwrap();
+        assert!(turn(&mut s, "s1", b"plan").is_empty());
+        // The busy target finishes something unrelated first; the
+        // queued plan did not lead to it.
+        assert_eq!(turn(&mut s, "s2", b"working"), [(cp, b"working".to_vec())]);
+        s.set_agent_state("s2", AgentState::Idle).unwrap();
+        assert_eq!(s.take_route_delivery("s2"), Some((ci, b"plan".to_vec())));
+        assert_eq!(turn(&mut s, "s2", b"reply"), [(cp, b"reply".to_vec())]);
+        // The reply came around the cycle.
+        assert!(turn(&mut s, "s1", b"ack").is_empty());
+    }
+
+    #[test]
+    fn released_route_holds_the_queue_until_idle() {
+        let mut s = state();
+        let (cp, ci) = (conn(), conn());
+        s.add_connection(cp, Role::Wrapper);
+        s.add_connection(ci, Role::Wrapper);
+        s.register_session("s1".into(), cp, 100, named("planner"))
+            .unwrap();
+        s.register_session("s2".into(), ci, 100, named("impl"))
+            .unwrap();
+        for template in [None, Some("again: {{content}}".to_string())] {
+            let spec = RouteSpec {
+                source: "planner".into(),
+                target: "impl".into(),
+                wait_idle: true,
+                template,
+                ..Default::default()
+            };
+            s.add_route(spec, true, 0).unwrap();
+        }
+        s.set_agent_state("s2", AgentState::Idle).unwrap();
+        s.store_turn(
+            "s1",
+            b"plan\n".to_vec(),
+            false,
+            1000,
+            None,
+            Default::default(),
+        )
+        .unwrap();
+
+        // One delivery goes in; the other waits for it to finish.
+        assert_eq!(s.route_turn("s1"), [(ci, b"plan\n".to_vec())]);
+        assert_eq!(s.take_route_delivery("s2"), None);
+        s.set_agent_state("s2", AgentState::Busy).unwrap();
+        assert_eq!(s.take_route_delivery("s2"), None);
+        s.set_agent_state("s2", AgentState::Idle).unwrap();
+        assert_eq!(
+            s.take_route_delivery("s2"),
+            Some((ci, b"again: plan\n".to_vec()))
+        );
+    }
+
     #[test]
     fn paste_buffer_empty() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.paste_content("s1"), Err("buffer_empty"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(s.paste_content("s1", None), Err("buffer_empty"));
     }
 
     #[test]
@@ -574,9 +2487,14 @@ mod tests {
                 byte_length: 4,
                 interrupted: false,
                 truncated: false,
+                context: TurnContext::default(),
+                turn_ids: Vec::new(),
             },
         });
-        assert_eq!(s.paste_content("nonexistent"), Err("session_not_found"));
+        assert_eq!(
+            s.paste_content("nonexistent", None),
+            Err("session_not_found")
+        );
     }
 
     #[test]
@@ -584,13 +2502,21 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"turn data".to_vec(), false, 1000)
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
             .unwrap();
-        s.capture("s1").unwrap();
+        s.store_turn(
+            "s1",
+            b"turn data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
         // Simulate disconnect without deregister.
         s.connections.remove(&c);
-        assert_eq!(s.paste_content("s1"), Err("session_disconnected"));
+        assert_eq!(s.paste_content("s1", None), Err("session_disconnected"));
     }
 
     #[test]
@@ -598,10 +2524,19 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
-        s.capture("s1").unwrap();
-        s.paste_content("s1").unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
+        s.paste_content("s1", None).unwrap();
         // Relay buffer still has content.
         assert!(s.relay_buffer.is_some());
     }
@@ -611,7 +2546,7 @@ mod tests {
     #[test]
     fn list_sessions_empty() {
         let s = state();
-        assert!(s.list_sessions().is_empty());
+        assert!(s.list_sessions(false).is_empty());
     }
 
     #[test]
@@ -619,14 +2554,24 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
 
         let c2 = conn();
         s.add_connection(c2, Role::Wrapper);
-        s.register_session("s2".into(), c2, 200).unwrap();
+        s.register_session("s2".into(), c2, 200, SessionMeta::default())
+            .unwrap();
 
-        let mut list = s.list_sessions();
+        let mut list = s.list_sessions(false);
         list.sort_by(|a, b| a.session.cmp(&b.session));
         assert_eq!(list.len(), 2);
         assert_eq!(list[0].session, "s1");
@@ -637,6 +2582,217 @@ mod tests {
         assert!(!list[1].has_turn);
     }
 
+    #[test]
+    fn list_sessions_includes_role_and_labels() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        let meta = SessionMeta {
+            role: Some("reviewer".into()),
+            labels: BTreeMap::from([("repo".to_string(), "clippy".to_string())]),
+            ..Default::default()
+        };
+        s.register_session("s1".into(), c, 100, meta).unwrap();
+
+        let list = s.list_sessions(false);
+        assert_eq!(list[0].role.as_deref(), Some("reviewer"));
+        assert_eq!(
+            list[0].labels.get("repo").map(String::as_str),
+            Some("clippy")
+        );
+    }
+
+    #[test]
+    fn list_sessions_reports_activity() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        let meta = SessionMeta {
+            command: vec!["claude".into(), "--resume".into()],
+            pattern: "claude".into(),
+            started_at: 500,
+            ..Default::default()
+        };
+        s.register_session("s1".into(), c, 100, meta).unwrap();
+
+        let list = s.list_sessions(false);
+        assert_eq!(list[0].command, ["claude", "--resume"]);
+        assert_eq!(list[0].pattern, "claude");
+        assert_eq!(list[0].started_at, 500);
+        assert_eq!(list[0].turn_count, 0);
+        assert_eq!(list[0].last_turn_at, None);
+        assert_eq!(list[0].last_input_at, None);
+
+        s.record_input("s1", 1500).unwrap();
+        s.store_turn(
+            "s1",
+            b"a".to_vec(),
+            false,
+            2000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"b".to_vec(),
+            false,
+            3000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        let list = s.list_sessions(false);
+        assert_eq!(list[0].turn_count, 2);
+        assert_eq!(list[0].last_turn_at, Some(3000));
+        assert_eq!(list[0].last_input_at, Some(1500));
+    }
+
+    #[test]
+    fn agent_state_starts_and_updates() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        assert_eq!(s.list_sessions(false)[0].state, AgentState::Starting);
+
+        s.set_agent_state("planner", AgentState::Busy).unwrap();
+        assert_eq!(s.list_sessions(false)[0].state, AgentState::Busy);
+        s.set_agent_state("s1", AgentState::Idle).unwrap();
+        assert_eq!(s.list_sessions(false)[0].state, AgentState::Idle);
+
+        assert_eq!(
+            s.set_agent_state("nope", AgentState::Idle),
+            Err("session_not_found")
+        );
+    }
+
+    #[test]
+    fn title_set_and_cleared() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(s.list_sessions(false)[0].title, None);
+
+        s.set_title("s1", "fix flaky test".into()).unwrap();
+        assert_eq!(
+            s.list_sessions(false)[0].title.as_deref(),
+            Some("fix flaky test")
+        );
+        s.set_title("s1", String::new()).unwrap();
+        assert_eq!(s.list_sessions(false)[0].title, None);
+    }
+
+    #[test]
+    fn record_input_unknown_session() {
+        let mut s = state();
+        assert_eq!(s.record_input("nope", 1000), Err("session_not_found"));
+    }
+
+    #[test]
+    fn store_turn_with_seq_keeps_wrapper_ids() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        // Replayed history starting past evicted turns.
+        assert_eq!(
+            s.store_turn(
+                "s1",
+                b"a".to_vec(),
+                false,
+                1000,
+                Some(4),
+                TurnContext::default()
+            ),
+            Ok("planner:4".into())
+        );
+        assert_eq!(
+            s.store_turn(
+                "s1",
+                b"b".to_vec(),
+                false,
+                2000,
+                Some(5),
+                TurnContext::default()
+            ),
+            Ok("planner:5".into())
+        );
+        assert_eq!(s.get_turn("planner:4").unwrap().timestamp, 1000);
+        assert_eq!(
+            s.store_turn(
+                "s1",
+                b"dup".to_vec(),
+                false,
+                3000,
+                Some(5),
+                TurnContext::default()
+            ),
+            Err("seq_out_of_order")
+        );
+    }
+
+    // -- Output subscribers --
+
+    #[test]
+    fn first_subscriber_enables_tap() {
+        let mut s = state();
+        let w = conn();
+        s.add_connection(w, Role::Wrapper);
+        s.register_session("s1".into(), w, 100, named("planner"))
+            .unwrap();
+        let (c1, c2) = (conn(), conn());
+        assert_eq!(s.subscribe("planner", c1), Ok(Some(w)));
+        assert_eq!(s.subscribe("s1", c2), Ok(None));
+        let subs = s.subscribers("s1");
+        assert_eq!(subs.len(), 2);
+        assert!(subs.contains(&c1) && subs.contains(&c2));
+    }
+
+    #[test]
+    fn subscribe_ended_session_fails() {
+        let mut s = state();
+        let w = conn();
+        s.add_connection(w, Role::Wrapper);
+        s.register_session("s1".into(), w, 100, SessionMeta::default())
+            .unwrap();
+        s.deregister_session("s1", Some(0), None, 1000);
+        assert_eq!(s.subscribe("s1", conn()), Err("session_ended"));
+        assert_eq!(s.subscribe("nope", conn()), Err("session_not_found"));
+    }
+
+    #[test]
+    fn last_unsubscribe_reports_idle_tap() {
+        let mut s = state();
+        let w = conn();
+        s.add_connection(w, Role::Wrapper);
+        s.register_session("s1".into(), w, 100, SessionMeta::default())
+            .unwrap();
+        let (c1, c2) = (conn(), conn());
+        s.subscribe("s1", c1).unwrap();
+        s.subscribe("s1", c2).unwrap();
+        assert!(s.unsubscribe(c1).is_empty());
+        assert_eq!(s.unsubscribe(c2), [("s1".to_string(), w)]);
+        assert!(s.subscribers("s1").is_empty());
+    }
+
+    #[test]
+    fn take_subscribers_clears_set() {
+        let mut s = state();
+        let w = conn();
+        s.add_connection(w, Role::Wrapper);
+        s.register_session("s1".into(), w, 100, SessionMeta::default())
+            .unwrap();
+        let c = conn();
+        s.subscribe("s1", c).unwrap();
+        assert_eq!(s.take_subscribers("s1"), [c]);
+        assert!(s.take_subscribers("s1").is_empty());
+    }
+
     // -- Get turn --
 
     #[test]
@@ -644,8 +2800,17 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         let record = s.get_turn("s1:1").unwrap();
         assert_eq!(record.content, b"data");
     }
@@ -655,7 +2820,8 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         assert_eq!(s.get_turn("s1:99"), Err("turn_not_found"));
     }
 
@@ -665,13 +2831,178 @@ mod tests {
         assert_eq!(s.get_turn("no_colon"), Err("turn_not_found"));
     }
 
+    #[test]
+    fn get_turn_non_numeric_seq() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        assert_eq!(s.get_turn("s1:one"), Err("turn_not_found"));
+    }
+
+    #[test]
+    fn get_turn_by_relative_reference() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("planner"))
+            .unwrap();
+        for content in [b"one", b"two", b"six"] {
+            s.store_turn(
+                "s1",
+                content.to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
+        }
+        assert_eq!(s.get_turn("planner:@latest").unwrap().content, b"six");
+        assert_eq!(s.get_turn("planner:-0").unwrap().content, b"six");
+        assert_eq!(s.get_turn("s1:-2").unwrap().content, b"one");
+        assert_eq!(s.get_turn("planner:-3"), Err("turn_not_found"));
+        assert_eq!(s.get_turn("planner:2..2").unwrap().content, b"two");
+        assert_eq!(s.get_turn("planner:1..2"), Err("range_not_allowed"));
+        assert_eq!(s.get_turn("@focused:1"), Err("unresolved_focus"));
+
+        let range = s.resolve_turns("planner:2..9").unwrap();
+        let seqs: Vec<u64> = range.iter().map(|r| r.seq).collect();
+        assert_eq!(seqs, [2, 3]);
+
+        assert_eq!(s.get_turn("@relay"), Err("buffer_empty"));
+        s.capture_by_id(&["planner:-1"], None, Some("a")).unwrap();
+        assert_eq!(s.get_turn("@relay:a").unwrap().turn_id, "planner:2");
+        s.set_relay(None, b"x".to_vec(), "ext", 1).unwrap();
+        assert_eq!(s.get_turn("@relay"), Err("turn_not_found"));
+    }
+
+    #[test]
+    fn capture_combines_turns() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        for (id, name) in [("s1", "planner"), ("s2", "other")] {
+            s.register_session(id.into(), c, 100, named(name)).unwrap();
+        }
+        for (i, content) in [b"plan", b"more", b"done"].iter().enumerate() {
+            s.store_turn(
+                "s1",
+                content.to_vec(),
+                i == 1,
+                1000 + i as u64,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
+        }
+        s.store_turn("s2", b"x".to_vec(), false, 1, None, TurnContext::default())
+            .unwrap();
+
+        let result = s
+            .capture_by_id(&["planner:1..2", "s1:3"], None, None)
+            .unwrap();
+        assert_eq!(result.turn_id, "planner:1,2,3");
+        let (content, metadata) = s.relay_content(None).unwrap();
+        assert_eq!(content, b"plan\n\nmore\n\ndone");
+        assert_eq!(result.size as usize, content.len());
+        assert_eq!(metadata.turn_ids, ["planner:1", "planner:2", "planner:3"]);
+        assert_eq!(metadata.timestamp, 1002);
+        assert!(metadata.interrupted);
+
+        s.capture_by_id(&["planner:3", "planner:1"], Some("[{turn_id}]"), None)
+            .unwrap();
+        let (content, metadata) = s.relay_content(None).unwrap();
+        assert_eq!(content, b"done[planner:1]plan");
+        let seqs: Vec<u64> = s
+            .resolve_turns("@relay")
+            .unwrap()
+            .iter()
+            .map(|r| r.seq)
+            .collect();
+        assert_eq!(seqs, [3, 1]);
+        assert_eq!(s.get_turn("@relay"), Err("range_not_allowed"));
+        assert_eq!(metadata.turn_id, "planner:3,1");
+
+        assert_eq!(
+            s.capture_by_id(&["planner:1", "other:1"], None, None),
+            Err("mixed_sessions")
+        );
+        let (content, _) = s.turn_content("planner:2..3", Some("|")).unwrap();
+        assert_eq!(content, b"more|done");
+    }
+
+    #[test]
+    fn gather_labels_each_turn() {
+        let mut s = state();
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("s1".into(), c, 100, named("alice"))
+            .unwrap();
+        s.register_session("s2".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"looks good\n".to_vec(),
+            false,
+            5,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn("s2", b"nit".to_vec(), true, 7, None, TurnContext::default())
+            .unwrap();
+
+        let (content, metadata) = s
+            .gather(&["alice:@latest", "s2:1"], SectionStyle::Xml)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(content).unwrap(),
+            "<turn session=\"alice\" turn_id=\"alice:1\">\nlooks good\n</turn>\n\n\
+             <turn session=\"s2\" turn_id=\"s2:1\">\nnit\n</turn>\n"
+        );
+        assert_eq!(metadata.turn_id, "alice:1+s2:1");
+        assert_eq!(metadata.turn_ids, ["alice:1", "s2:1"]);
+        assert!(metadata.interrupted);
+
+        let (content, _) = s.gather(&["s2:1"], SectionStyle::Markdown).unwrap();
+        assert_eq!(content, b"## s2 (s2:1)\n\nnit\n");
+        assert_eq!(
+            s.gather(&[], SectionStyle::Xml).unwrap_err(),
+            "turn_not_found"
+        );
+
+        let (content, metadata) = s.gather(&["alice:1"], SectionStyle::Xml).unwrap();
+        s.store_relay(Some("syn"), content, metadata).unwrap();
+        assert_eq!(s.get_turn("@relay:syn").unwrap().turn_id, "alice:1");
+    }
+
     #[test]
     fn get_turn_wrong_session() {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         assert_eq!(s.get_turn("s2:1"), Err("turn_not_found"));
     }
 
@@ -682,10 +3013,35 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"a".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"b".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"c".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"a".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"b".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"c".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         let turns = s.list_turns("s1", None).unwrap();
         let ids: Vec<&str> = turns.iter().map(|t| t.turn_id.as_str()).collect();
         assert_eq!(ids, vec!["s1:3", "s1:2", "s1:1"]);
@@ -696,9 +3052,18 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
         for _ in 0..5 {
-            s.store_turn("s1", b"x".to_vec(), false, 1000).unwrap();
+            s.store_turn(
+                "s1",
+                b"x".to_vec(),
+                false,
+                1000,
+                None,
+                TurnContext::default(),
+            )
+            .unwrap();
         }
         let turns = s.list_turns("s1", Some(2)).unwrap();
         assert_eq!(turns.len(), 2);
@@ -717,11 +3082,28 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"first".to_vec(), false, 1000).unwrap();
-        s.store_turn("s1", b"second".to_vec(), false, 1000).unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"first".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.store_turn(
+            "s1",
+            b"second".to_vec(),
+            false,
+            1000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
         // Capture the first turn, not the head.
-        let result = s.capture_by_id("s1:1").unwrap();
+        let result = s.capture_by_id(&["s1:1"], None, None).unwrap();
         assert_eq!(result.turn_id, "s1:1");
         assert_eq!(result.size, 5);
         assert_eq!(s.relay_buffer.as_ref().unwrap().content, b"first".to_vec());
@@ -732,14 +3114,21 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        assert_eq!(s.capture_by_id("s1:99"), Err("turn_not_found"));
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        assert_eq!(
+            s.capture_by_id(&["s1:99"], None, None),
+            Err("turn_not_found")
+        );
     }
 
     #[test]
     fn capture_by_id_wrong_session() {
         let mut s = state();
-        assert_eq!(s.capture_by_id("nonexistent:1"), Err("turn_not_found"));
+        assert_eq!(
+            s.capture_by_id(&["nonexistent:1"], None, None),
+            Err("turn_not_found")
+        );
     }
 
     // -- Relay stores metadata --
@@ -749,11 +3138,20 @@ mod tests {
         let mut s = state();
         let c = conn();
         s.add_connection(c, Role::Wrapper);
-        s.register_session("s1".into(), c, 100).unwrap();
-        s.store_turn("s1", b"data".to_vec(), true, 5000).unwrap();
-        s.capture("s1").unwrap();
-
-        let (content, metadata) = s.relay_content().unwrap();
+        s.register_session("s1".into(), c, 100, SessionMeta::default())
+            .unwrap();
+        s.store_turn(
+            "s1",
+            b"data".to_vec(),
+            true,
+            5000,
+            None,
+            TurnContext::default(),
+        )
+        .unwrap();
+        s.capture("s1", None).unwrap();
+
+        let (content, metadata) = s.relay_content(None).unwrap();
         assert_eq!(content, b"data");
         assert_eq!(metadata.turn_id, "s1:1");
         assert_eq!(metadata.timestamp, 5000);
@@ -761,4 +3159,166 @@ mod tests {
         assert!(metadata.interrupted);
         assert!(!metadata.truncated);
     }
+
+    // -- Snapshots --
+
+    /// A named live session with two turns, its second one captured.
+    fn planner_with_turns(s: &mut BrokerState) {
+        let c = conn();
+        s.add_connection(c, Role::Wrapper);
+        s.register_session("uuid-1".into(), c, 100, named("planner"))
+            .unwrap();
+        for (content, at) in [(b"one".to_vec(), 1000), (b"two".to_vec(), 2000)] {
+            s.store_turn("planner", content, false, at, None, TurnContext::default())
+                .unwrap();
+        }
+        s.capture("planner", None).unwrap();
+    }
+
+    #[test]
+    fn snapshot_restores_as_archive() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        let snapshot = source.snapshot(&[], 5000).unwrap();
+        assert_eq!(snapshot.sessions.len(), 1);
+        assert_eq!(snapshot.relay.as_ref().unwrap().turn_id, "planner:2");
+
+        let mut s = state();
+        assert_eq!(s.restore(snapshot).unwrap(), ["uuid-1"]);
+        let list = s.list_sessions(true);
+        assert_eq!(list[0].state, AgentState::Archived);
+        assert_eq!(list[0].turn_count, 2);
+        assert_eq!(list[0].ended_at, Some(5000));
+        assert!(s.list_sessions(false).is_empty());
+        assert_eq!(s.get_turn("planner:1").unwrap().content, b"one");
+        assert_eq!(s.relay_content(None).unwrap().0, b"two");
+        s.capture_by_id(&["planner:1"], None, None).unwrap();
+        assert_eq!(s.relay_content(None).unwrap().0, b"one");
+    }
+
+    #[test]
+    fn restore_rejects_repeated_ids_and_names() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        let snapshot = source.snapshot(&[], 5000).unwrap();
+
+        let mut same_id = snapshot.clone();
+        let mut twin = snapshot.sessions[0].clone();
+        twin.name = None;
+        same_id.sessions.push(twin);
+
+        let mut same_name = snapshot.clone();
+        let mut twin = snapshot.sessions[0].clone();
+        twin.session = "uuid-2".into();
+        same_name.sessions.push(twin);
+
+        // A name may not repeat another session's ID either.
+        let mut name_is_id = snapshot.clone();
+        let mut twin = snapshot.sessions[0].clone();
+        twin.session = "uuid-2".into();
+        twin.name = Some("uuid-1".into());
+        name_is_id.sessions.push(twin);
+
+        for bad in [same_id, same_name, name_is_id] {
+            let mut s = state();
+            assert_eq!(s.restore(bad).unwrap_err(), "invalid_snapshot");
+            assert!(s.list_sessions(true).is_empty());
+            assert_eq!(s.relay_content(None).unwrap_err(), "buffer_empty");
+        }
+    }
+
+    #[test]
+    fn restore_rejects_the_last_turn_seq() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        let mut snapshot = source.snapshot(&[], 5000).unwrap();
+        snapshot.sessions[0].turns[1].seq = u64::MAX;
+
+        let mut s = state();
+        assert_eq!(s.restore(snapshot).unwrap_err(), "invalid_snapshot");
+        assert!(s.list_sessions(true).is_empty());
+    }
+
+    #[test]
+    fn snapshot_keeps_named_registers() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        source
+            .capture_by_id(&["planner:1"], None, Some("plan"))
+            .unwrap();
+        let mut s = state();
+        s.restore(source.snapshot(&[], 5000).unwrap()).unwrap();
+        assert_eq!(s.relay_content(Some("plan")).unwrap().0, b"one");
+        assert_eq!(s.relay_content(None).unwrap().0, b"two");
+    }
+
+    #[test]
+    fn snapshot_selects_sessions() {
+        let mut s = state();
+        planner_with_turns(&mut s);
+        ended_with_turn(&mut s, "s2", 3000);
+        let snapshot = s.snapshot(&["s2".into()], 5000).unwrap();
+        assert_eq!(snapshot.sessions.len(), 1);
+        assert_eq!(snapshot.sessions[0].exit_code, Some(139));
+        assert_eq!(
+            s.snapshot(&["nope".into()], 5000).unwrap_err(),
+            "session_not_found"
+        );
+    }
+
+    #[test]
+    fn archive_is_read_only_and_never_expires() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        let mut s = state().with_tombstones(TombstoneConfig { ttl_ms: 1, max: 0 });
+        s.restore(source.snapshot(&[], 5000).unwrap()).unwrap();
+        assert_eq!(
+            s.paste_content("planner", None).unwrap_err(),
+            "session_archived"
+        );
+        assert_eq!(
+            s.store_turn(
+                "planner",
+                b"x".to_vec(),
+                false,
+                6000,
+                None,
+                TurnContext::default()
+            ),
+            Err("session_not_found")
+        );
+        ended_with_turn(&mut s, "s2", 6000);
+        s.prune_tombstones(u64::MAX);
+        assert_eq!(s.list_sessions(true).len(), 1);
+    }
+
+    #[test]
+    fn restore_conflicting_with_live_session_fails() {
+        let mut s = state();
+        planner_with_turns(&mut s);
+        let snapshot = s.snapshot(&[], 5000).unwrap();
+        assert_eq!(s.restore(snapshot.clone()), Err("duplicate_session"));
+
+        let mut other = state();
+        let c = conn();
+        other.add_connection(c, Role::Wrapper);
+        other
+            .register_session("uuid-2".into(), c, 100, named("planner"))
+            .unwrap();
+        assert_eq!(other.restore(snapshot), Err("duplicate_name"));
+        // Nothing was loaded.
+        assert_eq!(other.list_sessions(true).len(), 1);
+        assert!(other.relay_content(None).is_err());
+    }
+
+    #[test]
+    fn restore_replaces_archive() {
+        let mut source = state();
+        planner_with_turns(&mut source);
+        let snapshot = source.snapshot(&[], 5000).unwrap();
+        let mut s = state();
+        s.restore(snapshot.clone()).unwrap();
+        s.restore(snapshot).unwrap();
+        assert_eq!(s.list_sessions(true).len(), 1);
+    }
 }
@@ -0,0 +1,122 @@
+//! Delivery templates — text that wraps a turn on its way to a sink.
+//!
+//! ```text
+//! Please review the following implementation from {{session}}:
+//! ```
+//! {{content}}
+//! ```
+//! ```
+//!
+//! Rendering happens in the broker, so inject, clipboard and file
+//! sinks all see the same bytes. See CONTRACT_REGISTRY.md §Templates.
+
+/// The variables a template may use.
+pub const VARIABLES: [&str; 6] = [
+    "content",
+    "plain_content",
+    "turn_id",
+    "session",
+    "role",
+    "timestamp",
+];
+
+/// What a template's variables stand for.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateVars<'a> {
+    /// The turn as captured, ANSI sequences included.
+    pub content: &'a [u8],
+    pub turn_id: &'a str,
+    /// The source session's name, or its ID if it has none.
+    pub session: &'a str,
+    /// The source session's role; empty if it has none.
+    pub role: &'a str,
+    /// Unix epoch millis of the turn.
+    pub timestamp: u64,
+}
+
+/// Check that every `{{...}}` in a template names a known variable.
+pub fn validate(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "unclosed '{{'".to_string())?;
+        let name = after[..end].trim();
+        if !VARIABLES.contains(&name) {
+            return Err(format!(
+                "unknown variable {name:?} (expected: {})",
+                VARIABLES.join(", ")
+            ));
+        }
+        rest = &after[end + 2..];
+    }
+    Ok(())
+}
+
+/// Render a template. Text outside `{{...}}` is copied as is; an
+/// unknown variable is kept verbatim (templates are checked with
+/// [`validate`] before use).
+pub fn render(template: &str, vars: &TemplateVars) -> Vec<u8> {
+    let mut out = Vec::with_capacity(template.len() + vars.content.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.extend_from_slice(&rest.as_bytes()[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        match after[..end].trim() {
+            "content" => out.extend_from_slice(vars.content),
+            "plain_content" => out.extend(crate::turn::ansi::strip_ansi(vars.content)),
+            "turn_id" => out.extend_from_slice(vars.turn_id.as_bytes()),
+            "session" => out.extend_from_slice(vars.session.as_bytes()),
+            "role" => out.extend_from_slice(vars.role.as_bytes()),
+            "timestamp" => out.extend_from_slice(vars.timestamp.to_string().as_bytes()),
+            _ => out.extend_from_slice(&rest.as_bytes()[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.extend_from_slice(rest.as_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(content: &[u8]) -> TemplateVars<'_> {
+        TemplateVars {
+            content,
+            turn_id: "impl:4",
+            session: "impl",
+            role: "implementer",
+            timestamp: 1700000000000,
+        }
+    }
+
+    #[test]
+    fn renders_every_variable() {
+        let template = "Review {{session}} ({{ role }}) {{turn_id}} @{{timestamp}}:\n```\n{{content}}\n```\n{{plain_content}}";
+        assert_eq!(
+            render(template, &vars(b"\x1b[1mfn main()\x1b[0m")),
+            b"Review impl (implementer) impl:4 @1700000000000:\n```\n\x1b[1mfn main()\x1b[0m\n```\nfn main()"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_verbatim() {
+        assert_eq!(
+            render("{{nope}} {x} {{content", &vars(b"c")),
+            b"{{nope}} {x} {{content"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_unknown_variables() {
+        assert!(validate("{{content}} and {single}").is_ok());
+        assert!(validate("{{contents}}").unwrap_err().contains("contents"));
+        assert!(validate("{{content").is_err());
+    }
+}
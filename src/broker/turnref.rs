@@ -0,0 +1,153 @@
+//! Turn references — what turn-taking requests accept in place of a
+//! literal `<session>:<seq>` turn ID.
+//!
+//! ```text
+//! reference := "@relay" [":" register]
+//!            | session ":" selector
+//! session   := session ID | session name | "@focused"
+//! selector  := seq             planner:3
+//!            | "-" n           planner:-1   (n turns before the latest)
+//!            | "@latest"       planner:@latest
+//!            | seq ".." seq    planner:3..5 (inclusive)
+//! ```
+//!
+//! `@focused` is resolved by the client, which knows the focused
+//! window; the broker only parses it. See CONTRACT_REGISTRY.md
+//! §Turn references.
+
+/// Where a reference's turns come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source<'a> {
+    /// A session by ID or name.
+    Session(&'a str),
+    /// The session in the focused window, left for the client.
+    Focused,
+    /// The turn held by a relay register (`None`: the unnamed one).
+    Relay(Option<&'a str>),
+}
+
+/// Which of the source session's turns a reference names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selector {
+    /// The turn with this sequence number.
+    Seq(u64),
+    /// The turn this many turns before the latest (0 is the latest).
+    Back(u64),
+    /// The latest turn.
+    Latest,
+    /// Turns with sequence numbers in `first..=last`.
+    Range(u64, u64),
+}
+
+/// A parsed turn reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurnRef<'a> {
+    pub source: Source<'a>,
+    /// Ignored for [`Source::Relay`].
+    pub selector: Selector,
+}
+
+/// Parse a turn reference. Anything that does not match the grammar
+/// is `"turn_not_found"`, as a malformed literal turn ID always was.
+pub fn parse(reference: &str) -> Result<TurnRef<'_>, &'static str> {
+    const INVALID: &str = "turn_not_found";
+    if let Some(rest) = reference.strip_prefix("@relay") {
+        let register = match rest {
+            "" => None,
+            _ => Some(rest.strip_prefix(':').ok_or(INVALID)?),
+        };
+        return Ok(TurnRef {
+            source: Source::Relay(register),
+            selector: Selector::Latest,
+        });
+    }
+
+    let (session, selector) = reference.split_once(':').ok_or(INVALID)?;
+    let source = match session {
+        "" => return Err(INVALID),
+        "@focused" => Source::Focused,
+        session => Source::Session(session),
+    };
+    let selector = if selector == "@latest" {
+        Selector::Latest
+    } else if let Some(n) = selector.strip_prefix('-') {
+        Selector::Back(parse_number(n)?)
+    } else if let Some((first, last)) = selector.split_once("..") {
+        let (first, last) = (parse_number(first)?, parse_number(last)?);
+        if first > last {
+            return Err(INVALID);
+        }
+        Selector::Range(first, last)
+    } else {
+        Selector::Seq(parse_number(selector)?)
+    };
+    Ok(TurnRef { source, selector })
+}
+
+/// Parse a plain decimal number (no sign, no whitespace).
+fn parse_number(s: &str) -> Result<u64, &'static str> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("turn_not_found");
+    }
+    s.parse().map_err(|_| "turn_not_found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(name: &str, selector: Selector) -> TurnRef<'_> {
+        TurnRef {
+            source: Source::Session(name),
+            selector,
+        }
+    }
+
+    #[test]
+    fn literal_and_relative() {
+        assert_eq!(parse("planner:3"), Ok(session("planner", Selector::Seq(3))));
+        assert_eq!(
+            parse("planner:-1"),
+            Ok(session("planner", Selector::Back(1)))
+        );
+        assert_eq!(
+            parse("planner:@latest"),
+            Ok(session("planner", Selector::Latest))
+        );
+        assert_eq!(
+            parse("planner:3..5"),
+            Ok(session("planner", Selector::Range(3, 5)))
+        );
+    }
+
+    #[test]
+    fn special_sources() {
+        assert_eq!(
+            parse("@focused:-2"),
+            Ok(TurnRef {
+                source: Source::Focused,
+                selector: Selector::Back(2),
+            })
+        );
+        assert_eq!(parse("@relay").unwrap().source, Source::Relay(None));
+        assert_eq!(parse("@relay:a").unwrap().source, Source::Relay(Some("a")));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for bad in [
+            "planner",
+            ":3",
+            "planner:",
+            "planner:x",
+            "planner:+3",
+            "planner:5..3",
+            "planner:3..",
+            "planner:--1",
+            "@relayx",
+            "planner:@first",
+        ] {
+            assert_eq!(parse(bad), Err("turn_not_found"), "{bad}");
+        }
+    }
+}
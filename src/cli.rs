@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use nix::sys::signal::Signal;
+
+use crate::ipc::protocol::{AgentState, RouteTrigger, SectionStyle};
 
 #[derive(Parser)]
 #[command(name = "clippyctl", about = "Keyboard-driven agent turn relay")]
@@ -15,6 +18,53 @@ pub enum Command {
         #[arg(long, default_value = "generic")]
         pattern: String,
 
+        /// Session name, unique per broker; usable wherever a session ID
+        /// is accepted and as the turn ID prefix (e.g. planner:3)
+        #[arg(long, value_parser = parse_session_name)]
+        name: Option<String>,
+
+        /// Session role (e.g. planner, implementer, reviewer)
+        #[arg(long)]
+        role: Option<String>,
+
+        /// Session label as key=value (repeatable)
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+
+        /// Set CLIPPY_SESSION_ID and CLIPPY_SOCKET in the child environment
+        #[arg(long)]
+        export_env: bool,
+
+        /// Recent turns kept by the wrapper and replayed after a broker
+        /// reconnect (minimum 1)
+        #[arg(long, default_value = "32", value_parser = clap::value_parser!(u64).range(1..))]
+        history: u64,
+
+        /// Print a status line on stderr when the broker connection is
+        /// lost or restored
+        #[arg(long)]
+        broker_status: bool,
+
+        /// Recent output lines kept for `client capture-lines` (minimum 1)
+        #[arg(long, default_value = "1000", value_parser = clap::value_parser!(u64).range(1..))]
+        scrollback: u64,
+
+        /// Record output and input to this asciicast v2 file
+        #[arg(long)]
+        record: Option<std::path::PathBuf>,
+
+        /// Run without a terminal: no stdin, no raw mode; interact via inject
+        #[arg(long)]
+        headless: bool,
+
+        /// Headless window size as COLSxROWS
+        #[arg(long, requires = "headless", default_value = "80x24", value_parser = parse_size)]
+        size: (u16, u16),
+
+        /// Append headless child output to this file
+        #[arg(long, requires = "headless")]
+        output_log: Option<std::path::PathBuf>,
+
         /// Command to run
         #[arg(trailing_var_arg = true, required = true)]
         command: Vec<String>,
@@ -29,6 +79,24 @@ pub enum Command {
         /// Maximum byte size per turn (content truncated beyond this)
         #[arg(long, default_value = "4194304")]
         max_turn_size: usize,
+
+        /// Seconds an ended session's turns stay readable
+        #[arg(long, default_value = "600")]
+        tombstone_ttl: u64,
+
+        /// Maximum number of ended sessions kept (0 drops them on exit)
+        #[arg(long, default_value = "16")]
+        tombstone_limit: usize,
+
+        /// Named delivery template, NAME=TEXT (repeatable; `\n` and `\t`
+        /// are expanded)
+        #[arg(long = "template", value_parser = parse_template)]
+        templates: Vec<(String, String)>,
+
+        /// Named delivery template read from a file, NAME=PATH
+        /// (repeatable)
+        #[arg(long = "template-file", value_parser = parse_template_file)]
+        template_files: Vec<(String, std::path::PathBuf)>,
     },
 
     /// Run the hotkey client
@@ -44,6 +112,24 @@ pub enum Command {
         /// Clipboard-deliver hotkey binding (capture + copy to clipboard)
         #[arg(long)]
         clipboard_key: Option<String>,
+
+        /// Undo hotkey binding (restore the relay buffer's previous
+        /// content)
+        #[arg(long)]
+        undo_key: Option<String>,
+
+        /// Capture into and paste from this named register instead of
+        /// the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+
+        /// Paste through this named template (see `broker --template`)
+        #[arg(long)]
+        paste_template: Option<String>,
+
+        /// Copy to the clipboard through this named template
+        #[arg(long)]
+        clipboard_template: Option<String>,
     },
 
     /// CLI client for broker operations
@@ -57,13 +143,22 @@ pub enum Command {
 pub enum ClientAction {
     /// List all active sessions
     #[command(name = "list-sessions")]
-    ListSessions,
+    ListSessions {
+        /// Only show sessions in this state: starting, idle, busy, exited,
+        /// or archived
+        #[arg(long, value_parser = parse_agent_state)]
+        state: Option<AgentState>,
+
+        /// Include ended sessions still within the broker's grace period
+        #[arg(long)]
+        all: bool,
+    },
 
     /// List turns for a session
     #[command(name = "list-turns")]
     ListTurns {
-        /// Session ID to query
-        session: String,
+        #[command(flatten)]
+        target: SessionTarget,
 
         /// Maximum number of turns to return
         #[arg(long)]
@@ -73,7 +168,7 @@ pub enum ClientAction {
     /// Get turn content and metadata by ID
     #[command(name = "get-turn")]
     GetTurn {
-        /// Turn ID (format: session_id:seq)
+        /// Turn ID or reference (session:seq, session:-1, @relay, ...)
         turn_id: String,
 
         /// Show only metadata, omit content
@@ -83,34 +178,744 @@ pub enum ClientAction {
 
     /// Capture latest turn from session to relay buffer
     Capture {
-        /// Session ID
-        session: String,
+        #[command(flatten)]
+        target: SessionTarget,
+
+        #[command(flatten)]
+        wait: WaitIdle,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
     },
 
-    /// Capture specific turn by ID to relay buffer
+    /// Capture specific turns by ID to relay buffer
     #[command(name = "capture-by-id")]
     CaptureByID {
-        /// Turn ID (format: session_id:seq)
-        turn_id: String,
+        /// Turn IDs or references of one session (session:seq,
+        /// session:-1, session:3..5, @relay, ...), combined in order
+        #[arg(required = true)]
+        turn_ids: Vec<String>,
+
+        /// Text between combined turns ({turn_id}: the next turn's ID;
+        /// \n and \t are expanded) [default: a blank line]
+        #[arg(long, value_parser = parse_separator)]
+        separator: Option<String>,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// Capture the last lines of a session's output to relay buffer
+    #[command(name = "capture-lines")]
+    CaptureLines {
+        #[command(flatten)]
+        target: SessionTarget,
+
+        #[command(flatten)]
+        wait: WaitIdle,
+
+        /// Number of lines (counted after --grep filtering)
+        #[arg(long, default_value = "80", value_parser = clap::value_parser!(u32).range(1..))]
+        last: u32,
+
+        /// Only keep lines matching this regex
+        #[arg(long)]
+        grep: Option<String>,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
     },
 
-    /// Paste relay buffer content to session
+    /// Follow a session's live output until it ends (read-only)
+    Tail {
+        #[command(flatten)]
+        target: SessionTarget,
+
+        /// Strip ANSI escape sequences from the output
+        #[arg(long)]
+        strip_ansi: bool,
+    },
+
+    /// Paste relay buffer content to one or more sessions
     Paste {
-        /// Target session ID
-        session: String,
+        /// Target session IDs or names
+        #[arg(required_unless_present_any = ["role", "all_with_role"])]
+        sessions: Vec<String>,
+
+        /// Select the single session with this role instead
+        #[arg(long, conflicts_with_all = ["sessions", "all_with_role"])]
+        role: Option<String>,
+
+        /// Also paste into every live session with this role
+        #[arg(long)]
+        all_with_role: Option<String>,
+
+        /// Wait until each session is idle (at its prompt) before pasting
+        #[arg(long)]
+        wait_idle: bool,
+
+        /// With --wait-idle, give up after this many seconds
+        #[arg(long, requires = "wait_idle", default_value = "300", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: u64,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+
+        /// Wrap the content in this named template (see `broker
+        /// --template`)
+        #[arg(long)]
+        template: Option<String>,
     },
 
-    /// Deliver relay buffer to a sink
+    /// Paste a session's last turns into another session, e.g. to give
+    /// a fresh agent the context of one that ended
+    Replay {
+        /// Source: a session ID or name (ended and archived sessions
+        /// included), or a snapshot file
+        #[arg(long)]
+        from: String,
+
+        /// Session inside the snapshot file (needed when it holds several)
+        #[arg(long)]
+        from_session: Option<String>,
+
+        /// Number of turns to replay, oldest first
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        last: u32,
+
+        /// Target session ID or name
+        #[arg(long)]
+        into: String,
+
+        /// Precede each turn with a `--- <turn_id> ---` header line
+        #[arg(long)]
+        frame: bool,
+
+        /// Wait until the target session is idle before pasting
+        #[arg(long)]
+        wait_idle: bool,
+
+        /// With --wait-idle, give up after this many seconds
+        #[arg(long, requires = "wait_idle", default_value = "300", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: u64,
+    },
+
+    /// Gather turns of several sessions into one payload with a
+    /// labelled section per turn, e.g. reviews for a synthesizer agent
+    Gather {
+        /// Sessions (their latest turn) or turn references
+        #[arg(required_unless_present = "role")]
+        sources: Vec<String>,
+
+        /// Also gather the latest turn of every session with this role
+        #[arg(long)]
+        role: Vec<String>,
+
+        /// Section style: xml or markdown
+        #[arg(long, default_value = "xml", value_parser = parse_section_style)]
+        style: SectionStyle,
+
+        /// Store the payload in the relay buffer instead of printing it
+        #[arg(long)]
+        into_relay: bool,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, requires = "into_relay", value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// Deliver relay buffer to one or more sinks
     Deliver {
-        /// Sink name: clipboard, file, or inject
-        sink: String,
+        /// Sink names: clipboard, file, or inject
+        #[arg(required = true)]
+        sinks: Vec<String>,
 
-        /// Target session ID (required for inject sink)
+        /// Target session ID or name (inject sink requires this, --role
+        /// or --all-with-role; repeatable)
         #[arg(long)]
-        session: Option<String>,
+        session: Vec<String>,
+
+        /// Target the single session with this role (inject sink)
+        #[arg(long, conflicts_with_all = ["session", "all_with_role"])]
+        role: Option<String>,
+
+        /// Also target every live session with this role (inject sink)
+        #[arg(long)]
+        all_with_role: Option<String>,
 
         /// File path (required for file sink)
         #[arg(long)]
         path: Option<String>,
+
+        /// Wait until each target session is idle before injecting
+        #[arg(long)]
+        wait_idle: bool,
+
+        /// With --wait-idle, give up after this many seconds
+        #[arg(long, requires = "wait_idle", default_value = "300", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: u64,
+
+        /// Deliver this turn (ID or reference) instead of the relay buffer
+        #[arg(long, conflicts_with = "register")]
+        turn: Option<String>,
+
+        /// Text between the turns of a --turn range (as for capture-by-id)
+        #[arg(long, requires = "turn", value_parser = parse_separator)]
+        separator: Option<String>,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+
+        /// Wrap the content in this named template (see `broker
+        /// --template`)
+        #[arg(long)]
+        template: Option<String>,
+    },
+
+    /// List the relay registers holding content
+    Registers,
+
+    /// Inspect, fill or clear the relay buffer
+    Relay {
+        #[command(subcommand)]
+        action: RelayAction,
+    },
+
+    /// Relay one session's turns into another automatically
+    Route {
+        #[command(subcommand)]
+        action: RouteAction,
+    },
+
+    /// Send a signal to a session's child process group
+    #[command(allow_missing_positional = true)]
+    Signal {
+        #[command(flatten)]
+        target: SessionTarget,
+
+        /// Signal name or number (INT, SIGTERM, 15, ...)
+        #[arg(value_parser = parse_signal)]
+        signal: Signal,
+    },
+
+    /// Terminate a session: SIGTERM, then SIGKILL after a grace period
+    Kill {
+        #[command(flatten)]
+        target: SessionTarget,
+    },
+
+    /// Set a headless session's window size
+    #[command(allow_missing_positional = true)]
+    Resize {
+        #[command(flatten)]
+        target: SessionTarget,
+
+        /// Window size as COLSxROWS
+        #[arg(value_parser = parse_size)]
+        size: (u16, u16),
+    },
+
+    /// Start or stop recording a session to an asciicast v2 file
+    Record {
+        #[command(subcommand)]
+        action: RecordAction,
+    },
+
+    /// Save or load a snapshot of sessions and the relay buffer
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotAction {
+    /// Save sessions' turns and the relay buffer to a file
+    Save {
+        /// Snapshot file to write
+        file: std::path::PathBuf,
+
+        /// Session ID or name to include (repeatable; default: all,
+        /// ended sessions included)
+        #[arg(long = "session")]
+        sessions: Vec<String>,
+
+        /// Encrypt with a passphrase ($CLIPPY_SNAPSHOT_PASSPHRASE, or
+        /// prompted)
+        #[arg(long)]
+        encrypt: bool,
+    },
+
+    /// Load a snapshot file as read-only archived sessions
+    Load {
+        /// Snapshot file to read
+        file: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RelayAction {
+    /// Print the relay buffer's metadata (stderr) and content (stdout)
+    Show {
+        /// Show only metadata, omit content
+        #[arg(long)]
+        metadata_only: bool,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// Load a file, or stdin, into the relay buffer
+    Set {
+        /// File to read (default: stdin)
+        file: Option<std::path::PathBuf>,
+
+        /// Label for the content's pseudo turn ID `<source>:set`
+        /// (default: the file name, or `stdin`)
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// Empty the relay buffer
+    Clear {
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// Put back what the relay buffer held before its last change
+    Undo {
+        /// Use this named register instead of the unnamed one
+        #[arg(long, value_parser = parse_register)]
+        register: Option<String>,
+    },
+
+    /// List previous relay contents, most recent first
+    History,
+}
+
+#[derive(Subcommand)]
+pub enum RouteAction {
+    /// Add a route, e.g. `planner '->' implementer`
+    Add {
+        /// Source session ID or name, or the whole `<source> -> <target>`
+        source: String,
+
+        /// `->` (quoted in the shell) or the target
+        #[arg(allow_hyphen_values = true)]
+        arrow: Option<String>,
+
+        /// Target session ID or name, after `->`
+        target: Option<String>,
+
+        /// What fires the route
+        #[arg(long, default_value = "turn", value_parser = parse_route_trigger)]
+        on: RouteTrigger,
+
+        /// Inject this instead of the bare turn, with {{content}},
+        /// {{turn_id}}, {{session}} etc. substituted (\n and \t are
+        /// expanded; variables as for `broker --template`)
+        #[arg(long, value_parser = parse_separator)]
+        template: Option<String>,
+
+        /// Only relay turns whose text matches this regex
+        #[arg(long)]
+        grep: Option<String>,
+
+        /// Only relay turns of at least this many bytes
+        #[arg(long, default_value = "0")]
+        min_size: u32,
+
+        /// Do not relay interrupted turns
+        #[arg(long)]
+        skip_interrupted: bool,
+
+        /// Hold each delivery until the target is idle
+        #[arg(long)]
+        wait_idle: bool,
+
+        /// Add the route switched off
+        #[arg(long)]
+        disabled: bool,
+    },
+
+    /// List routes
+    List,
+
+    /// Remove a route
+    Rm {
+        /// Route number, as shown by `route list`
+        route: u32,
+    },
+
+    /// Switch a route on
+    Enable {
+        /// Route number, as shown by `route list`
+        route: u32,
     },
+
+    /// Switch a route off
+    Disable {
+        /// Route number, as shown by `route list`
+        route: u32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecordAction {
+    /// Start recording (replaces a recording in progress)
+    #[command(allow_missing_positional = true)]
+    Start {
+        #[command(flatten)]
+        target: SessionTarget,
+
+        /// Output file, written by the session's wrapper
+        path: std::path::PathBuf,
+    },
+
+    /// Stop recording
+    Stop {
+        #[command(flatten)]
+        target: SessionTarget,
+    },
+}
+
+/// A session addressed by ID/name or by role.
+///
+/// Role selection is resolved by the client against `list-sessions`
+/// and must match exactly one session.
+#[derive(Args)]
+pub struct SessionTarget {
+    /// Session ID or name
+    #[arg(required_unless_present = "role")]
+    pub session: Option<String>,
+
+    /// Select the single session with this role instead
+    #[arg(long, conflicts_with = "session")]
+    pub role: Option<String>,
+}
+
+/// Wait for the target session to be idle before capturing.
+#[derive(Args)]
+pub struct WaitIdle {
+    /// Wait until the session is idle (at its prompt) before capturing
+    #[arg(long)]
+    pub wait_idle: bool,
+
+    /// With --wait-idle, give up after this many seconds
+    #[arg(long, requires = "wait_idle", default_value = "300", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: u64,
+}
+
+/// Validate a `wrap --name` value.
+///
+/// Names share the turn ID namespace (`<name>:<seq>`), so they must be
+/// non-empty and must not contain `:`.
+fn parse_session_name(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        return Err("session name must not be empty".into());
+    }
+    if s.contains(':') {
+        return Err("session name must not contain ':'".into());
+    }
+    Ok(s.to_string())
+}
+
+/// Validate a `--register` name (see CONTRACT_BROKER.md §Registers).
+fn parse_register(s: &str) -> Result<String, String> {
+    crate::broker::state::validate_register(s)
+        .map(|()| s.to_string())
+        .map_err(|_| format!("invalid register {s:?} (1-32 letters, digits, '_' or '-')"))
+}
+
+/// Expand `\n`, `\t` and `\\` in a `--separator` value, so shells
+/// need no special quoting for newlines.
+fn parse_separator(s: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    Ok(out)
+}
+
+/// Parse a `broker --template NAME=TEXT` value.
+fn parse_template(s: &str) -> Result<(String, String), String> {
+    let (name, text) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=TEXT, got {s:?}"))?;
+    let name = parse_template_name(name)?;
+    let text = parse_separator(text)?;
+    crate::broker::template::validate(&text).map_err(|e| format!("template {name:?}: {e}"))?;
+    Ok((name, text))
+}
+
+/// Parse a `broker --template-file NAME=PATH` value; the file is read
+/// and checked at startup.
+fn parse_template_file(s: &str) -> Result<(String, std::path::PathBuf), String> {
+    let (name, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=PATH, got {s:?}"))?;
+    Ok((parse_template_name(name)?, path.into()))
+}
+
+/// Template names follow the register name rules.
+fn parse_template_name(s: &str) -> Result<String, String> {
+    crate::broker::state::validate_register(s)
+        .map(|()| s.to_string())
+        .map_err(|_| format!("invalid template name {s:?} (1-32 letters, digits, '_' or '-')"))
+}
+
+/// Parse a signal name (`INT`, `SIGINT`, case-insensitive) or number.
+fn parse_signal(s: &str) -> Result<Signal, String> {
+    if let Ok(n) = s.parse::<i32>() {
+        return Signal::try_from(n).map_err(|_| format!("unknown signal number {n}"));
+    }
+    let upper = s.to_ascii_uppercase();
+    let name = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{upper}")
+    };
+    name.parse().map_err(|_| format!("unknown signal {s:?}"))
+}
+
+/// Parse a `wrap --size COLSxROWS` value.
+fn parse_size(s: &str) -> Result<(u16, u16), String> {
+    let (cols, rows) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected COLSxROWS, got {s:?}"))?;
+    let cols: u16 = cols
+        .parse()
+        .map_err(|_| format!("invalid columns: {cols:?}"))?;
+    let rows: u16 = rows
+        .parse()
+        .map_err(|_| format!("invalid rows: {rows:?}"))?;
+    if cols == 0 || rows == 0 {
+        return Err("size must be non-zero".into());
+    }
+    Ok((cols, rows))
+}
+
+/// Parse a `list-sessions --state` value.
+fn parse_agent_state(s: &str) -> Result<AgentState, String> {
+    [
+        AgentState::Starting,
+        AgentState::Idle,
+        AgentState::Busy,
+        AgentState::Exited,
+        AgentState::Archived,
+    ]
+    .into_iter()
+    .find(|state| state.as_str() == s)
+    .ok_or_else(|| {
+        format!("unknown state {s:?} (expected: starting, idle, busy, exited, archived)")
+    })
+}
+
+/// Parse a `gather --style` value.
+fn parse_section_style(s: &str) -> Result<SectionStyle, String> {
+    [SectionStyle::Xml, SectionStyle::Markdown]
+        .into_iter()
+        .find(|style| style.as_str() == s)
+        .ok_or_else(|| format!("unknown style {s:?} (expected: xml, markdown)"))
+}
+
+/// Parse a `route add --on` value.
+fn parse_route_trigger(s: &str) -> Result<RouteTrigger, String> {
+    [RouteTrigger::Turn]
+        .into_iter()
+        .find(|on| on.as_str() == s)
+        .ok_or_else(|| format!("unknown trigger {s:?} (expected: turn)"))
+}
+
+/// Parse a `wrap --label key=value` value.
+fn parse_label(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, got {s:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_label_key_value() {
+        assert_eq!(
+            parse_label("repo=clippy"),
+            Ok(("repo".to_string(), "clippy".to_string()))
+        );
+        // Only the first '=' separates key from value.
+        assert_eq!(
+            parse_label("expr=a=b"),
+            Ok(("expr".to_string(), "a=b".to_string()))
+        );
+        assert_eq!(
+            parse_label("empty="),
+            Ok(("empty".to_string(), String::new()))
+        );
+    }
+
+    #[test]
+    fn parse_label_rejects_malformed() {
+        assert!(parse_label("novalue").is_err());
+        assert!(parse_label("=value").is_err());
+    }
+
+    #[test]
+    fn parse_register_names() {
+        assert_eq!(parse_register("a"), Ok("a".to_string()));
+        assert_eq!(parse_register("review-2"), Ok("review-2".to_string()));
+        assert!(parse_register("").is_err());
+        assert!(parse_register("a:b").is_err());
+        assert!(parse_register(&"x".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn parse_separator_escapes() {
+        assert_eq!(
+            parse_separator(r"\n--- {turn_id} ---\n").unwrap(),
+            "\n--- {turn_id} ---\n"
+        );
+        assert_eq!(parse_separator(r"a\tb\\n\x").unwrap(), "a\tb\\n\\x");
+    }
+
+    #[test]
+    fn parse_template_name_and_text() {
+        assert_eq!(
+            parse_template(r"review=From {{session}}:\n{{content}}"),
+            Ok((
+                "review".to_string(),
+                "From {{session}}:\n{{content}}".to_string()
+            ))
+        );
+        assert!(parse_template("review").is_err());
+        assert!(parse_template("bad name={{content}}").is_err());
+        assert!(parse_template("review={{contents}}").is_err());
+    }
+
+    #[test]
+    fn parse_size_cols_rows() {
+        assert_eq!(parse_size("120x40"), Ok((120, 40)));
+        assert!(parse_size("120").is_err());
+        assert!(parse_size("0x40").is_err());
+        assert!(parse_size("axb").is_err());
+    }
+
+    #[test]
+    fn parse_signal_names_and_numbers() {
+        assert_eq!(parse_signal("INT"), Ok(Signal::SIGINT));
+        assert_eq!(parse_signal("sigterm"), Ok(Signal::SIGTERM));
+        assert_eq!(parse_signal("9"), Ok(Signal::SIGKILL));
+        assert!(parse_signal("NOPE").is_err());
+        assert!(parse_signal("0").is_err());
+    }
+
+    #[test]
+    fn parse_agent_state_names() {
+        assert_eq!(parse_agent_state("idle"), Ok(AgentState::Idle));
+        assert_eq!(parse_agent_state("busy"), Ok(AgentState::Busy));
+        assert_eq!(parse_agent_state("starting"), Ok(AgentState::Starting));
+        assert_eq!(parse_agent_state("exited"), Ok(AgentState::Exited));
+        assert_eq!(parse_agent_state("archived"), Ok(AgentState::Archived));
+        assert!(parse_agent_state("awake").is_err());
+    }
+
+    #[test]
+    fn session_control_takes_a_role() {
+        let parse = |args: &[&str]| {
+            let args = ["clippyctl", "client"].iter().chain(args);
+            match Cli::try_parse_from(args).map(|cli| cli.command) {
+                Ok(Command::Client {
+                    action: ClientAction::Signal { target, signal },
+                }) => Some((target.session, target.role, signal)),
+                _ => None,
+            }
+        };
+        assert_eq!(
+            parse(&["signal", "s1", "INT"]),
+            Some((Some("s1".into()), None, Signal::SIGINT))
+        );
+        assert_eq!(
+            parse(&["signal", "--role", "reviewer", "TERM"]),
+            Some((None, Some("reviewer".into()), Signal::SIGTERM))
+        );
+        assert_eq!(parse(&["signal", "INT"]), None);
+        assert!(
+            Cli::try_parse_from(["clippyctl", "client", "resize", "--role", "r", "80x24"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn record_takes_a_role() {
+        let parse = |args: &[&str]| {
+            let args = ["clippyctl", "client", "record"].iter().chain(args);
+            match Cli::try_parse_from(args).map(|cli| cli.command) {
+                Ok(Command::Client {
+                    action:
+                        ClientAction::Record {
+                            action: RecordAction::Start { target, path },
+                        },
+                }) => Some((target.session, target.role, path)),
+                _ => None,
+            }
+        };
+        assert_eq!(
+            parse(&["start", "s1", "a.cast"]),
+            Some((Some("s1".into()), None, "a.cast".into()))
+        );
+        assert_eq!(
+            parse(&["start", "--role", "reviewer", "a.cast"]),
+            Some((None, Some("reviewer".into()), "a.cast".into()))
+        );
+        assert_eq!(parse(&["start", "a.cast"]), None);
+        assert!(
+            Cli::try_parse_from(["clippyctl", "client", "record", "stop", "--role", "r"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn wait_idle_only_on_capture() {
+        let parse =
+            |args: &[&str]| Cli::try_parse_from(["clippyctl", "client"].iter().chain(args)).is_ok();
+        assert!(parse(&["capture", "s1", "--wait-idle", "--timeout", "5"]));
+        assert!(parse(&["capture-lines", "--role", "r", "--wait-idle"]));
+        assert!(!parse(&["capture", "s1", "--timeout", "5"]));
+        assert!(!parse(&["kill", "s1", "--wait-idle"]));
+        assert!(!parse(&["signal", "s1", "INT", "--wait-idle"]));
+        assert!(!parse(&["resize", "s1", "80x24", "--wait-idle"]));
+        assert!(!parse(&["record", "stop", "s1", "--wait-idle"]));
+    }
+
+    #[test]
+    fn parse_session_name_rejects_colon() {
+        assert!(parse_session_name("planner").is_ok());
+        assert!(parse_session_name("a:b").is_err());
+        assert!(parse_session_name("").is_err());
+    }
 }
@@ -12,7 +12,8 @@ use tokio_util::codec::Framed;
 
 use crate::ipc::codec::LengthPrefixedCodec;
 use crate::ipc::protocol::{
-    Message, PROTOCOL_VERSION, Role, SessionDescriptor, Status, TurnDescriptor,
+    DeliveryResult, Message, PROTOCOL_VERSION, RegisterDescriptor, Role, RouteDescriptor,
+    RouteSpec, SectionStyle, SessionDescriptor, Status, TurnDescriptor,
 };
 
 use super::ClientError;
@@ -32,6 +33,25 @@ pub struct GetTurnResult {
     pub truncated: bool,
 }
 
+/// The inject targets of a multi-target paste or deliver.
+pub struct InjectTargets {
+    /// Session IDs or names.
+    pub sessions: Vec<String>,
+    /// Also every live session with this role.
+    pub role: Option<String>,
+}
+
+/// What a deliver sends: the relay register, or a turn reference, and
+/// the template to render it through.
+#[derive(Debug, Default)]
+pub struct DeliverySource {
+    pub turn_id: Option<String>,
+    /// Separator between the turns of a ranged `turn_id`.
+    pub separator: Option<String>,
+    pub register: Option<String>,
+    pub template: Option<String>,
+}
+
 /// Broker client for one-shot CLI commands.
 ///
 /// Simpler than the PTY wrapper's client — no split sink/stream needed
@@ -88,13 +108,16 @@ impl BrokerClient {
         })
     }
 
-    /// List all active sessions.
-    pub async fn list_sessions(&mut self) -> Result<Vec<SessionDescriptor>, ClientError> {
+    /// List active sessions, plus ended ones still retained when `all`.
+    pub async fn list_sessions(
+        &mut self,
+        all: bool,
+    ) -> Result<Vec<SessionDescriptor>, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
         self.framed
-            .send(Message::ListSessions { id })
+            .send(Message::ListSessions { id, all })
             .await
             .map_err(|e| ClientError::Broker(format!("send list_sessions: {e}")))?;
 
@@ -119,8 +142,13 @@ impl BrokerClient {
         }
     }
 
-    /// Capture the latest turn from a session into the relay buffer.
-    pub async fn capture(&mut self, session: &str) -> Result<CaptureResult, ClientError> {
+    /// Capture the latest turn from a session into a relay register
+    /// (`None`: the unnamed one).
+    pub async fn capture(
+        &mut self,
+        session: &str,
+        register: Option<String>,
+    ) -> Result<CaptureResult, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -128,6 +156,7 @@ impl BrokerClient {
             .send(Message::Capture {
                 id,
                 session: session.to_string(),
+                register,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send capture: {e}")))?;
@@ -149,8 +178,54 @@ impl BrokerClient {
         }
     }
 
-    /// Paste relay buffer content to a session (inject into its PTY).
-    pub async fn paste(&mut self, session: &str) -> Result<(), ClientError> {
+    /// Capture the last lines of a session's scrollback into a relay
+    /// register.
+    pub async fn capture_lines(
+        &mut self,
+        session: &str,
+        last: u32,
+        grep: Option<String>,
+        register: Option<String>,
+    ) -> Result<CaptureResult, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::CaptureLines {
+                id,
+                session: session.to_string(),
+                last,
+                grep,
+                register,
+            })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send capture_lines: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                size: Some(size),
+                ..
+            })) => Ok(CaptureResult { turn_id, size }),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "capture_lines failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected capture_lines response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Paste a relay register's content to a session (inject into its
+    /// PTY).
+    pub async fn paste(
+        &mut self,
+        session: &str,
+        register: Option<String>,
+        template: Option<String>,
+    ) -> Result<(), ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -158,6 +233,10 @@ impl BrokerClient {
             .send(Message::Paste {
                 id,
                 session: session.to_string(),
+                sessions: Vec::new(),
+                role: None,
+                register,
+                template,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send paste: {e}")))?;
@@ -176,8 +255,408 @@ impl BrokerClient {
         }
     }
 
+    /// Paste a relay register into several sessions, returning each
+    /// target's result.
+    pub async fn paste_many(
+        &mut self,
+        targets: InjectTargets,
+        register: Option<String>,
+        template: Option<String>,
+    ) -> Result<Vec<DeliveryResult>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Message::Paste {
+            id,
+            session: String::new(),
+            sessions: targets.sessions,
+            role: targets.role,
+            register,
+            template,
+        };
+        self.send_deliveries(request, "paste").await
+    }
+
+    /// List the relay registers holding content.
+    pub async fn list_registers(&mut self) -> Result<Vec<RegisterDescriptor>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_registers_query(Message::ListRegisters { id }, "list_registers")
+            .await
+    }
+
+    /// List the relay history, most recent first.
+    pub async fn relay_history(&mut self) -> Result<Vec<RegisterDescriptor>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_registers_query(Message::RelayHistory { id }, "relay_history")
+            .await
+    }
+
+    /// Send a request answered with register descriptors.
+    async fn send_registers_query(
+        &mut self,
+        msg: Message,
+        op: &str,
+    ) -> Result<Vec<RegisterDescriptor>, ClientError> {
+        self.framed
+            .send(msg)
+            .await
+            .map_err(|e| ClientError::Broker(format!("send {op}: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                registers,
+                ..
+            })) => Ok(registers.unwrap_or_default()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "{op} failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected {op} response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Read a relay register's content and metadata. Returns the
+    /// (possibly pseudo) turn ID it holds with the content.
+    pub async fn relay_show(
+        &mut self,
+        register: Option<String>,
+    ) -> Result<(String, GetTurnResult), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::RelayShow { id, register })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send relay_show: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                content: Some(content),
+                timestamp: Some(timestamp),
+                byte_length: Some(byte_length),
+                interrupted: Some(interrupted),
+                truncated: Some(truncated),
+                ..
+            })) => Ok((
+                turn_id,
+                GetTurnResult {
+                    content,
+                    timestamp,
+                    byte_length,
+                    interrupted,
+                    truncated,
+                },
+            )),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "relay_show failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected relay_show response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Gather turns into one sectioned payload, also storing it in a
+    /// relay register when `into_relay`. Returns the payload's turn ID
+    /// and content.
+    pub async fn gather(
+        &mut self,
+        turn_ids: Vec<String>,
+        style: SectionStyle,
+        into_relay: bool,
+        register: Option<String>,
+    ) -> Result<(String, Vec<u8>), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::Gather {
+                id,
+                turn_ids,
+                style,
+                into_relay,
+                register,
+            })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send gather: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                content: Some(content),
+                ..
+            })) => Ok((turn_id, content)),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "gather failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected gather response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Put `content` into a relay register, labelled with `source`.
+    pub async fn relay_set(
+        &mut self,
+        content: Vec<u8>,
+        register: Option<String>,
+        source: Option<String>,
+    ) -> Result<CaptureResult, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::RelaySet {
+                id,
+                content,
+                register,
+                source,
+            })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send relay_set: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                size: Some(size),
+                ..
+            })) => Ok(CaptureResult { turn_id, size }),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "relay_set failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected relay_set response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Put back a relay register's previous content.
+    pub async fn relay_undo(
+        &mut self,
+        register: Option<String>,
+    ) -> Result<CaptureResult, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::RelayUndo { id, register })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send relay_undo: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                size: Some(size),
+                ..
+            })) => Ok(CaptureResult { turn_id, size }),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "relay_undo failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected relay_undo response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Empty a relay register.
+    pub async fn relay_clear(&mut self, register: Option<String>) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_control(Message::RelayClear { id, register }, "relay_clear")
+            .await
+    }
+
+    /// Add a relay route, returning it as the broker numbered it.
+    pub async fn route_add(
+        &mut self,
+        route: RouteSpec,
+        disabled: bool,
+    ) -> Result<RouteDescriptor, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::RouteAdd {
+            id,
+            route,
+            disabled,
+        };
+        let routes = self.send_routes_query(msg, "route_add").await?;
+        routes
+            .into_iter()
+            .next()
+            .ok_or_else(|| ClientError::Broker("route_add: no route in response".into()))
+    }
+
+    pub async fn route_list(&mut self) -> Result<Vec<RouteDescriptor>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_routes_query(Message::RouteList { id }, "route_list")
+            .await
+    }
+
+    pub async fn route_remove(&mut self, route: u32) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send_control(Message::RouteRemove { id, route }, "route_remove")
+            .await
+    }
+
+    /// Switch a route on or off.
+    pub async fn route_enable(&mut self, route: u32, enabled: bool) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::RouteEnable { id, route, enabled };
+        self.send_routes_query(msg, "route_enable").await.map(drop)
+    }
+
+    async fn send_routes_query(
+        &mut self,
+        msg: Message,
+        op: &str,
+    ) -> Result<Vec<RouteDescriptor>, ClientError> {
+        self.framed
+            .send(msg)
+            .await
+            .map_err(|e| ClientError::Broker(format!("send {op}: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                routes,
+                ..
+            })) => Ok(routes.unwrap_or_default()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "{op} failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected {op} response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Send a signal to a session's child process group.
+    pub async fn signal(&mut self, session: &str, signal: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::Signal {
+            id,
+            session: session.to_string(),
+            signal: signal.to_string(),
+        };
+        self.send_control(msg, "signal").await
+    }
+
+    /// Ask a session's wrapper to terminate the child.
+    pub async fn kill(&mut self, session: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::Kill {
+            id,
+            session: session.to_string(),
+        };
+        self.send_control(msg, "kill").await
+    }
+
+    /// Set a headless session's window size.
+    pub async fn resize(&mut self, session: &str, cols: u16, rows: u16) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::Resize {
+            id,
+            session: session.to_string(),
+            cols,
+            rows,
+        };
+        self.send_control(msg, "resize").await
+    }
+
+    /// Ask a session's wrapper to record to `path` (absolute).
+    pub async fn record_start(&mut self, session: &str, path: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::RecordStart {
+            id,
+            session: session.to_string(),
+            path: path.to_string(),
+        };
+        self.send_control(msg, "record_start").await
+    }
+
+    /// Ask a session's wrapper to stop recording.
+    pub async fn record_stop(&mut self, session: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::RecordStop {
+            id,
+            session: session.to_string(),
+        };
+        self.send_control(msg, "record_stop").await
+    }
+
+    /// Subscribe to a session's live output. Follow with
+    /// [`next_output`](Self::next_output).
+    pub async fn subscribe(&mut self, session: &str) -> Result<(), ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = Message::Subscribe {
+            id,
+            session: session.to_string(),
+        };
+        self.send_control(msg, "subscribe").await
+    }
+
+    /// Wait for the next chunk of subscribed output. Returns `None`
+    /// once the session ends or the broker closes the connection.
+    pub async fn next_output(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+        match self.framed.next().await {
+            Some(Ok(Message::Output { content, .. })) => Ok(Some(content)),
+            Some(Ok(Message::OutputEnd { .. })) | None => Ok(None),
+            Some(Ok(other)) => Err(ClientError::Broker(format!(
+                "unexpected message while tailing: {other:?}"
+            ))),
+            Some(Err(e)) => Err(ClientError::Broker(format!("receive output: {e}"))),
+        }
+    }
+
+    /// Send a session control message and wait for the broker's ack.
+    async fn send_control(&mut self, msg: Message, op: &str) -> Result<(), ClientError> {
+        self.framed
+            .send(msg)
+            .await
+            .map_err(|e| ClientError::Broker(format!("send {op}: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok, ..
+            })) => Ok(()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "{op} failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected {op} response: {other:?}"
+            ))),
+        }
+    }
+
     /// Get a turn's content and metadata by ID.
-    pub async fn get_turn(&mut self, turn_id: &str) -> Result<GetTurnResult, ClientError> {
+    pub async fn get_turn(
+        &mut self,
+        turn_id: &str,
+    ) -> Result<(String, GetTurnResult), ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -192,19 +671,23 @@ impl BrokerClient {
         match self.framed.next().await {
             Some(Ok(Message::Response {
                 status: Status::Ok,
+                turn_id: Some(turn_id),
                 content: Some(content),
                 timestamp: Some(timestamp),
                 byte_length: Some(byte_length),
                 interrupted: Some(interrupted),
                 truncated: Some(truncated),
                 ..
-            })) => Ok(GetTurnResult {
-                content,
-                timestamp,
-                byte_length,
-                interrupted,
-                truncated,
-            }),
+            })) => Ok((
+                turn_id,
+                GetTurnResult {
+                    content,
+                    timestamp,
+                    byte_length,
+                    interrupted,
+                    truncated,
+                },
+            )),
             Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
                 "get_turn failed: {}",
                 error.unwrap_or_default()
@@ -254,15 +737,123 @@ impl BrokerClient {
         }
     }
 
-    /// Capture a specific turn by ID into the relay buffer.
-    pub async fn capture_by_id(&mut self, turn_id: &str) -> Result<CaptureResult, ClientError> {
+    /// Paste the last `last` turns of `from` (or of a session in
+    /// `snapshot`) into `session`. Returns the replayed turns.
+    pub async fn replay(
+        &mut self,
+        session: &str,
+        from: Option<String>,
+        snapshot: Option<Vec<u8>>,
+        last: u32,
+        frame: bool,
+    ) -> Result<Vec<TurnDescriptor>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::Replay {
+                id,
+                session: session.to_string(),
+                from,
+                snapshot,
+                last,
+                frame,
+            })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send replay: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turns,
+                ..
+            })) => Ok(turns.unwrap_or_default()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "replay failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected replay response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Ask the broker for an encoded snapshot of `sessions` (all when
+    /// empty) and the relay buffer.
+    pub async fn snapshot_save(&mut self, sessions: Vec<String>) -> Result<Vec<u8>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::SnapshotSave { id, sessions })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send snapshot_save: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                content: Some(content),
+                ..
+            })) => Ok(content),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "snapshot_save failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected snapshot_save response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Restore an encoded snapshot. Returns the archived sessions.
+    pub async fn snapshot_load(
+        &mut self,
+        content: Vec<u8>,
+    ) -> Result<Vec<SessionDescriptor>, ClientError> {
         let id = self.next_id;
         self.next_id += 1;
 
+        self.framed
+            .send(Message::SnapshotLoad { id, content })
+            .await
+            .map_err(|e| ClientError::Broker(format!("send snapshot_load: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                sessions,
+                ..
+            })) => Ok(sessions.unwrap_or_default()),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "snapshot_load failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected snapshot_load response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Capture a specific turn by ID into a relay register.
+    pub async fn capture_by_id(
+        &mut self,
+        turn_ids: &[String],
+        separator: Option<String>,
+        register: Option<String>,
+    ) -> Result<CaptureResult, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let (turn_id, more) = turn_ids
+            .split_first()
+            .ok_or_else(|| ClientError::Broker("no turn ID given".into()))?;
         self.framed
             .send(Message::CaptureByID {
                 id,
-                turn_id: turn_id.to_string(),
+                turn_id: turn_id.clone(),
+                turn_ids: more.to_vec(),
+                separator,
+                register,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send capture_by_id: {e}")))?;
@@ -284,12 +875,13 @@ impl BrokerClient {
         }
     }
 
-    /// Deliver relay buffer content to a sink.
+    /// Deliver a relay register's content to a sink.
     pub async fn deliver(
         &mut self,
         sink: &str,
         session: Option<String>,
         path: Option<String>,
+        source: DeliverySource,
     ) -> Result<(), ClientError> {
         let id = self.next_id;
         self.next_id += 1;
@@ -298,8 +890,15 @@ impl BrokerClient {
             .send(Message::Deliver {
                 id,
                 sink: sink.to_string(),
+                sinks: Vec::new(),
                 session,
+                sessions: Vec::new(),
+                role: None,
                 path,
+                turn_id: source.turn_id,
+                separator: source.separator,
+                register: source.register,
+                template: source.template,
             })
             .await
             .map_err(|e| ClientError::Broker(format!("send deliver: {e}")))?;
@@ -317,6 +916,61 @@ impl BrokerClient {
             ))),
         }
     }
+
+    /// Deliver a relay register or turn to several sinks and inject
+    /// targets, returning each target's result.
+    pub async fn deliver_many(
+        &mut self,
+        sinks: Vec<String>,
+        targets: InjectTargets,
+        path: Option<String>,
+        source: DeliverySource,
+    ) -> Result<Vec<DeliveryResult>, ClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut sinks = sinks.into_iter();
+        let request = Message::Deliver {
+            id,
+            sink: sinks.next().unwrap_or_default(),
+            sinks: sinks.collect(),
+            session: None,
+            sessions: targets.sessions,
+            role: targets.role,
+            path,
+            turn_id: source.turn_id,
+            separator: source.separator,
+            register: source.register,
+            template: source.template,
+        };
+        self.send_deliveries(request, "deliver").await
+    }
+
+    /// Send a multi-target request. Per-target results are returned
+    /// even when every target failed; only request-level errors fail.
+    async fn send_deliveries(
+        &mut self,
+        request: Message,
+        op: &str,
+    ) -> Result<Vec<DeliveryResult>, ClientError> {
+        self.framed
+            .send(request)
+            .await
+            .map_err(|e| ClientError::Broker(format!("send {op}: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                results: Some(results),
+                ..
+            })) => Ok(results),
+            Some(Ok(Message::Response { error, .. })) => Err(ClientError::Broker(format!(
+                "{op} failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(ClientError::Broker(format!(
+                "unexpected {op} response: {other:?}"
+            ))),
+        }
+    }
 }
 
 /// Resolve the broker socket path from `$XDG_RUNTIME_DIR`.
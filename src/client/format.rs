@@ -6,29 +6,81 @@
 
 use std::io::{self, Write};
 
-use crate::ipc::protocol::{SessionDescriptor, TurnDescriptor};
+use crate::ipc::protocol::{
+    DeliveryResult, GitContext, RegisterDescriptor, RouteDescriptor, SessionDescriptor,
+    TurnDescriptor,
+};
 
 use super::broker_client::{CaptureResult, GetTurnResult};
 
 /// Print session descriptors as a table to stdout.
+///
+/// Times are shown as ages relative to now (`42s`, `5m`, `3h`); the
+/// command line and working directory come last since they vary most
+/// in width.
 pub fn print_sessions(sessions: &[SessionDescriptor]) {
     if sessions.is_empty() {
         println!("No active sessions");
         return;
     }
 
-    println!("{:<40} {:>8} HAS_TURN", "SESSION", "PID");
-    println!("{}", "-".repeat(60));
+    let now = crate::turn::epoch_millis();
+    println!(
+        "{:<36} {:<12} {:<10} {:<30} {:<8} {:>7} {:>5} {:>6} {:>6} {:>6} {:<10} {:<24} CWD / COMMAND",
+        "SESSION",
+        "NAME",
+        "ROLE",
+        "TITLE",
+        "STATE",
+        "PID",
+        "TURNS",
+        "UP",
+        "TURN",
+        "INPUT",
+        "PATTERN",
+        "LABELS"
+    );
+    println!("{}", "-".repeat(190));
     for s in sessions {
+        let labels: Vec<String> = s.labels.iter().map(|(k, v)| format!("{k}={v}")).collect();
         println!(
-            "{:<40} {:>8} {}",
+            "{:<36} {:<12} {:<10} {:<30} {:<8} {:>7} {:>5} {:>6} {:>6} {:>6} {:<10} {:<24} {} $ {}",
             s.session,
+            s.name.as_deref().unwrap_or("-"),
+            s.role.as_deref().unwrap_or("-"),
+            truncate(s.title.as_deref().unwrap_or("-"), 30),
+            s.state.as_str(),
             s.pid,
-            if s.has_turn { "yes" } else { "no" }
+            s.turn_count,
+            format_age(now, Some(s.started_at)),
+            format_age(now, s.last_turn_at),
+            format_age(now, s.last_input_at),
+            s.pattern,
+            if labels.is_empty() {
+                "-".to_string()
+            } else {
+                labels.join(",")
+            },
+            s.cwd.as_deref().unwrap_or("?"),
+            s.command.join(" "),
         );
+        if let Some(ended) = ended_summary(now, s) {
+            println!("{:<36} {ended}", "");
+        }
     }
 }
 
+/// Describe how a tombstoned session ended, e.g.
+/// `ended 2m ago: killed by SIGSEGV`. `None` for live sessions.
+fn ended_summary(now: u64, s: &SessionDescriptor) -> Option<String> {
+    let ended_at = s.ended_at?;
+    let reason = s.exit_reason.as_deref().unwrap_or("ended");
+    Some(format!(
+        "ended {} ago: {reason}",
+        format_age(now, Some(ended_at))
+    ))
+}
+
 /// Print turn descriptors as a table to stdout.
 pub fn print_turns(turns: &[TurnDescriptor]) {
     if turns.is_empty() {
@@ -36,19 +88,51 @@ pub fn print_turns(turns: &[TurnDescriptor]) {
         return;
     }
 
-    println!("{:<24} {:>10} {:>16} FLAGS", "TURN_ID", "SIZE", "TIMESTAMP");
-    println!("{}", "-".repeat(70));
+    println!(
+        "{:<24} {:>10} {:>16} {:>8} {:>6} {:<22} GIT",
+        "TURN_ID", "SIZE", "TIMESTAMP", "DURATION", "LINES", "FLAGS"
+    );
+    println!("{}", "-".repeat(110));
     for t in turns {
         println!(
-            "{:<24} {:>10} {:>16} {}",
+            "{:<24} {:>10} {:>16} {:>8} {:>6} {:<22} {}",
             t.turn_id,
             t.byte_length,
             t.timestamp,
+            format_duration(t.context.duration_ms),
+            t.context
+                .line_count
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
             format_flags(t.interrupted, t.truncated),
+            format_git(t.context.git.as_ref()),
         );
     }
 }
 
+/// Format a turn's response time: `850ms`, `12.3s`, `4m05s`.
+fn format_duration(ms: Option<u64>) -> String {
+    match ms {
+        None => "-".to_string(),
+        Some(ms @ 0..1000) => format!("{ms}ms"),
+        Some(ms @ 1000..60_000) => format!("{:.1}s", ms as f64 / 1000.0),
+        Some(ms) => format!("{}m{:02}s", ms / 60_000, ms / 1000 % 60),
+    }
+}
+
+/// Format git context as `branch@sha7`, with `*` when dirty.
+fn format_git(git: Option<&GitContext>) -> String {
+    let Some(git) = git else {
+        return "-".to_string();
+    };
+    let branch = git.branch.as_deref().unwrap_or("(detached)");
+    let head = git
+        .head
+        .as_deref()
+        .map_or("-", |sha| &sha[..sha.len().min(7)]);
+    let dirty = if git.dirty == Some(true) { "*" } else { "" };
+    format!("{branch}@{head}{dirty}")
+}
+
 /// Print turn content and metadata.
 ///
 /// Metadata header goes to stderr, raw content to stdout. With
@@ -79,8 +163,20 @@ pub fn print_turn(
 }
 
 /// Print capture/capture-by-id result.
-pub fn print_capture(result: &CaptureResult) {
-    println!("Captured {} ({} bytes)", result.turn_id, result.size);
+pub fn print_capture(result: &CaptureResult, register: Option<&str>) {
+    match register {
+        Some(register) => println!(
+            "Captured {} ({} bytes) into register {register}",
+            result.turn_id, result.size
+        ),
+        None => println!("Captured {} ({} bytes)", result.turn_id, result.size),
+    }
+}
+
+/// Print `gather --into-relay` success.
+pub fn print_gather(turn_id: &str, size: usize, register: Option<&str>) {
+    let into = register.map_or_else(|| "relay buffer".to_string(), |r| format!("register {r}"));
+    println!("Gathered {turn_id} ({size} bytes) into {into}");
 }
 
 /// Print paste success.
@@ -88,11 +184,193 @@ pub fn print_paste(session: &str) {
     println!("Pasted to session {session}");
 }
 
+/// Print each target's result of a multi-target paste or deliver.
+pub fn print_delivery_results(results: &[DeliveryResult]) {
+    for result in results {
+        match result.error {
+            None => println!("Delivered to {}", result.target),
+            Some(ref error) => println!("Failed {}: {error}", result.target),
+        }
+    }
+}
+
+/// Print `relay set` success.
+pub fn print_relay_set(result: &CaptureResult, register: Option<&str>) {
+    let into = register.map_or_else(|| "relay buffer".to_string(), |r| format!("register {r}"));
+    println!("Set {into} to {} ({} bytes)", result.turn_id, result.size);
+}
+
+/// Print `relay clear` success.
+pub fn print_relay_cleared(register: Option<&str>) {
+    match register {
+        Some(register) => println!("Cleared register {register}"),
+        None => println!("Cleared relay buffer"),
+    }
+}
+
+/// Print `relay undo` success.
+pub fn print_relay_undo(result: &CaptureResult, register: Option<&str>) {
+    let what = register.map_or_else(|| "relay buffer".to_string(), |r| format!("register {r}"));
+    println!(
+        "Restored {what} to {} ({} bytes)",
+        result.turn_id, result.size
+    );
+}
+
+/// Print relay registers as a table to stdout.
+pub fn print_registers(registers: &[RegisterDescriptor]) {
+    if registers.is_empty() {
+        println!("No registers hold content");
+        return;
+    }
+    print_register_table(registers);
+}
+
+/// Print the relay history, most recent first, as a table to stdout.
+pub fn print_relay_history(history: &[RegisterDescriptor]) {
+    if history.is_empty() {
+        println!("No relay history");
+        return;
+    }
+    print_register_table(history);
+}
+
+fn print_register_table(registers: &[RegisterDescriptor]) {
+    let now = crate::turn::epoch_millis();
+    println!(
+        "{:<12} {:<24} {:>10} {:>6}",
+        "REGISTER", "TURN_ID", "SIZE", "AGE"
+    );
+    println!("{}", "-".repeat(55));
+    for r in registers {
+        println!(
+            "{:<12} {:<24} {:>10} {:>6}",
+            r.register.as_deref().unwrap_or("(unnamed)"),
+            r.turn_id,
+            r.size,
+            format_age(now, Some(r.timestamp)),
+        );
+    }
+}
+
+/// Print routes as a table to stdout.
+pub fn print_routes(routes: &[RouteDescriptor]) {
+    if routes.is_empty() {
+        println!("No routes");
+        return;
+    }
+    println!(
+        "{:<6} {:<32} {:<5} {:<7} {:>6}  FILTER",
+        "ROUTE", "PATH", "ON", "ENABLED", "FIRED"
+    );
+    println!("{}", "-".repeat(69));
+    for r in routes {
+        let spec = &r.spec;
+        let mut filter = Vec::new();
+        if let Some(ref pattern) = spec.pattern {
+            filter.push(format!("grep={pattern}"));
+        }
+        if spec.min_size > 0 {
+            filter.push(format!("min-size={}", spec.min_size));
+        }
+        if spec.skip_interrupted {
+            filter.push("skip-interrupted".into());
+        }
+        if spec.wait_idle {
+            filter.push("wait-idle".into());
+        }
+        if spec.template.is_some() {
+            filter.push("template".into());
+        }
+        let line = format!(
+            "{:<6} {:<32} {:<5} {:<7} {:>6}  {}",
+            r.route,
+            format!("{} -> {}", spec.source, spec.target),
+            spec.on.as_str(),
+            if r.enabled { "yes" } else { "no" },
+            r.fired,
+            filter.join(" "),
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Print a route change (added, removed, switched on or off).
+pub fn print_route(route: u32, what: &str) {
+    println!("Route {route}: {what}");
+}
+
+/// Print session control success (signal, kill, resize).
+pub fn print_control(session: &str, what: &str) {
+    println!("Session {session}: {what}");
+}
+
+/// Note on stderr that a tailed session's output ended, keeping
+/// stdout to the session's own bytes.
+pub fn print_output_end(session: &str) {
+    eprintln!("Session {session}: output ended");
+}
+
+/// Print the turns replayed into a session.
+pub fn print_replay(session: &str, turns: &[TurnDescriptor]) {
+    let ids: Vec<&str> = turns.iter().map(|t| t.turn_id.as_str()).collect();
+    println!(
+        "Replayed {} turn(s) into session {session}: {}",
+        turns.len(),
+        ids.join(", ")
+    );
+}
+
 /// Print deliver success.
 pub fn print_deliver(sink: &str) {
     println!("Delivered to {sink} sink");
 }
 
+/// Print snapshot save success.
+pub fn print_snapshot_saved(path: &std::path::Path, size: usize, encrypted: bool) {
+    let how = if encrypted { ", encrypted" } else { "" };
+    println!("Saved snapshot to {} ({size} bytes{how})", path.display());
+}
+
+/// Print the sessions restored from a snapshot.
+pub fn print_snapshot_loaded(sessions: &[SessionDescriptor]) {
+    println!("Loaded {} archived session(s)", sessions.len());
+    for s in sessions {
+        let label = match s.name {
+            Some(ref name) => format!("{name} ({})", s.session),
+            None => s.session.clone(),
+        };
+        println!("  {label}: {} turn(s)", s.turn_count);
+    }
+}
+
+/// Truncate `s` to at most `width` characters, marking the cut with `…`.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(width.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+/// Format the time elapsed since `then` (epoch millis) as a short age.
+///
+/// `None` (or a zero timestamp from an older wrapper) is shown as `-`.
+fn format_age(now: u64, then: Option<u64>) -> String {
+    let then = match then {
+        Some(t) if t > 0 => t,
+        _ => return "-".to_string(),
+    };
+    let secs = now.saturating_sub(then) / 1000;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
 /// Format interrupted/truncated flags as a comma-separated string.
 fn format_flags(interrupted: bool, truncated: bool) -> String {
     let mut flags = Vec::new();
@@ -128,6 +406,67 @@ mod tests {
         assert_eq!(format_flags(false, true), "truncated");
     }
 
+    #[test]
+    fn truncate_long_and_short() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate("a much longer title", 10), "a much lo…");
+        // Counts characters, not bytes.
+        assert_eq!(truncate("ééééé", 5), "ééééé");
+    }
+
+    #[test]
+    fn ended_summary_only_for_tombstones() {
+        let now = 1_000_000_000;
+        let mut s = SessionDescriptor::default();
+        assert_eq!(ended_summary(now, &s), None);
+        s.ended_at = Some(now - 120_000);
+        s.exit_reason = Some("killed by SIGSEGV".into());
+        assert_eq!(
+            ended_summary(now, &s).as_deref(),
+            Some("ended 2m ago: killed by SIGSEGV")
+        );
+    }
+
+    #[test]
+    fn format_age_units() {
+        let now = 1_000_000_000;
+        assert_eq!(format_age(now, None), "-");
+        assert_eq!(format_age(now, Some(0)), "-");
+        assert_eq!(format_age(now, Some(now - 42_000)), "42s");
+        assert_eq!(format_age(now, Some(now - 5 * 60_000)), "5m");
+        assert_eq!(format_age(now, Some(now - 3 * 3_600_000)), "3h");
+        assert_eq!(format_age(now, Some(now - 2 * 86_400_000)), "2d");
+        // Clock skew (timestamp in the future) is clamped to zero.
+        assert_eq!(format_age(now, Some(now + 5_000)), "0s");
+    }
+
+    #[test]
+    fn format_duration_units() {
+        assert_eq!(format_duration(None), "-");
+        assert_eq!(format_duration(Some(850)), "850ms");
+        assert_eq!(format_duration(Some(12_340)), "12.3s");
+        assert_eq!(format_duration(Some(245_000)), "4m05s");
+    }
+
+    #[test]
+    fn format_git_branch_and_sha() {
+        let git = GitContext {
+            root: "/repo".into(),
+            branch: Some("main".into()),
+            head: Some("0123456789abcdef".into()),
+            dirty: Some(true),
+        };
+        assert_eq!(format_git(Some(&git)), "main@0123456*");
+        let detached = GitContext {
+            branch: None,
+            dirty: Some(false),
+            ..git
+        };
+        assert_eq!(format_git(Some(&detached)), "(detached)@0123456");
+        assert_eq!(format_git(None), "-");
+    }
+
     #[test]
     fn format_flags_both() {
         assert_eq!(format_flags(true, true), "interrupted,truncated");
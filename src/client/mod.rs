@@ -3,13 +3,24 @@
 //! Provides one-shot commands that connect to the broker, perform a
 //! single request, print the result, and exit. Covers all v0 and v1
 //! broker operations: session queries, capture/paste, turn registry
-//! lookups, and sink delivery.
+//! lookups, sink delivery and snapshots. `tail` is the exception: it stays
+//! connected and streams a session's output until the session ends.
 
 mod broker_client;
 mod format;
+mod snapshot;
 
-use crate::cli::ClientAction;
-use broker_client::BrokerClient;
+use std::io::{Read, Write};
+
+use crate::cli::{
+    ClientAction, RecordAction, RelayAction, RouteAction, SessionTarget, SnapshotAction, WaitIdle,
+};
+use crate::ipc::protocol::{AgentState, DeliveryResult, RouteSpec, SessionDescriptor, Status};
+use crate::resolver::x11::X11Shared;
+use crate::resolver::x11::session::X11SessionResolver;
+use crate::resolver::{ResolverError, SessionResolver};
+use crate::turn::ansi::AnsiStripper;
+use broker_client::{BrokerClient, DeliverySource, InjectTargets};
 
 /// Client error type.
 #[derive(Debug, thiserror::Error)]
@@ -18,6 +29,12 @@ pub enum ClientError {
     Broker(String),
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("snapshot: {0}")]
+    Snapshot(String),
+    #[error(transparent)]
+    Resolver(#[from] ResolverError),
+    #[error("timed out waiting for session {0} to be idle")]
+    WaitTimeout(String),
 }
 
 /// Run the client command.
@@ -28,11 +45,18 @@ pub async fn run(action: ClientAction) -> Result<(), ClientError> {
     let mut broker = BrokerClient::connect().await?;
 
     match action {
-        ClientAction::ListSessions => {
-            let sessions = broker.list_sessions().await?;
+        ClientAction::ListSessions { state, all } => {
+            // Ended sessions are only listed on request; asking for
+            // the exited state implies it.
+            let all = all || matches!(state, Some(AgentState::Exited | AgentState::Archived));
+            let mut sessions = broker.list_sessions(all).await?;
+            if let Some(state) = state {
+                sessions.retain(|s| s.state == state);
+            }
             format::print_sessions(&sessions);
         }
-        ClientAction::ListTurns { session, limit } => {
+        ClientAction::ListTurns { target, limit } => {
+            let session = resolve_target(&mut broker, target).await?;
             let turns = broker.list_turns(&session, limit).await?;
             format::print_turns(&turns);
         }
@@ -40,104 +64,749 @@ pub async fn run(action: ClientAction) -> Result<(), ClientError> {
             turn_id,
             metadata_only,
         } => {
-            let result = broker.get_turn(&turn_id).await?;
+            let reference = resolve_reference(&mut broker, turn_id).await?;
+            let (turn_id, result) = broker.get_turn(&reference).await?;
             format::print_turn(&turn_id, &result, metadata_only)?;
         }
-        ClientAction::Capture { session } => {
-            let result = broker.capture(&session).await?;
-            format::print_capture(&result);
+        ClientAction::Capture {
+            target,
+            wait,
+            register,
+        } => {
+            let session = resolve_target(&mut broker, target).await?;
+            wait_if_asked(&mut broker, &session, wait).await?;
+            let result = broker.capture(&session, register.clone()).await?;
+            format::print_capture(&result, register.as_deref());
+        }
+        ClientAction::CaptureByID {
+            turn_ids,
+            separator,
+            register,
+        } => {
+            let mut references = Vec::with_capacity(turn_ids.len());
+            for turn_id in turn_ids {
+                references.push(resolve_reference(&mut broker, turn_id).await?);
+            }
+            let result = broker
+                .capture_by_id(&references, separator, register.clone())
+                .await?;
+            format::print_capture(&result, register.as_deref());
+        }
+        ClientAction::CaptureLines {
+            target,
+            wait,
+            last,
+            grep,
+            register,
+        } => {
+            let session = resolve_target(&mut broker, target).await?;
+            wait_if_asked(&mut broker, &session, wait).await?;
+            let result = broker
+                .capture_lines(&session, last, grep, register.clone())
+                .await?;
+            format::print_capture(&result, register.as_deref());
+        }
+        ClientAction::Tail { target, strip_ansi } => {
+            let session = resolve_target(&mut broker, target).await?;
+            broker.subscribe(&session).await?;
+            let mut stripper = strip_ansi.then(AnsiStripper::new);
+            let mut stdout = std::io::stdout().lock();
+            while let Some(chunk) = broker.next_output().await? {
+                let chunk = match stripper.as_mut() {
+                    Some(stripper) => stripper.strip(&chunk),
+                    None => chunk,
+                };
+                stdout.write_all(&chunk)?;
+                stdout.flush()?;
+            }
+            format::print_output_end(&session);
         }
-        ClientAction::CaptureByID { turn_id } => {
-            let result = broker.capture_by_id(&turn_id).await?;
-            format::print_capture(&result);
+        ClientAction::Paste {
+            mut sessions,
+            role,
+            all_with_role,
+            wait_idle,
+            timeout,
+            register,
+            template,
+        } => {
+            if let Some(role) = role {
+                sessions.push(resolve_role(&mut broker, &role).await?);
+            }
+            if wait_idle {
+                let role = all_with_role.as_deref();
+                wait_until_all_idle(&mut broker, &sessions, role, timeout).await?;
+            }
+            if let [session] = sessions.as_slice()
+                && all_with_role.is_none()
+            {
+                broker.paste(session, register, template).await?;
+                format::print_paste(session);
+            } else {
+                let targets = InjectTargets {
+                    sessions,
+                    role: all_with_role,
+                };
+                let results = broker.paste_many(targets, register, template).await?;
+                report_deliveries(&results)?;
+            }
+        }
+        ClientAction::Replay {
+            from,
+            from_session,
+            last,
+            into,
+            frame,
+            wait_idle,
+            timeout,
+        } => {
+            // A path to an existing file is a snapshot; anything else
+            // names a session.
+            let (from, snapshot) = if std::path::Path::new(&from).is_file() {
+                let sealed = std::fs::read(&from)?;
+                let passphrase = snapshot::is_encrypted(&sealed)
+                    .then(|| snapshot::passphrase(false))
+                    .transpose()?;
+                (
+                    from_session,
+                    Some(snapshot::open(&sealed, passphrase.as_deref())?),
+                )
+            } else if from_session.is_some() {
+                return Err(ClientError::Broker(format!(
+                    "--from-session needs --from to be a snapshot file; {from:?} is not a file"
+                )));
+            } else {
+                (Some(from), None)
+            };
+            if wait_idle {
+                wait_until_idle(&mut broker, &into, idle_deadline(timeout)).await?;
+            }
+            let turns = broker.replay(&into, from, snapshot, last, frame).await?;
+            format::print_replay(&into, &turns);
         }
-        ClientAction::Paste { session } => {
-            broker.paste(&session).await?;
-            format::print_paste(&session);
+        ClientAction::Gather {
+            sources,
+            role,
+            style,
+            into_relay,
+            register,
+        } => {
+            let mut references = Vec::new();
+            for source in sources {
+                references.push(resolve_reference(&mut broker, gather_reference(source)).await?);
+            }
+            if !role.is_empty() {
+                let sessions = broker.list_sessions(false).await?;
+                for role in &role {
+                    for session in sessions_with_role(&sessions, role)? {
+                        references.push(format!("{session}:@latest"));
+                    }
+                }
+            }
+            if references.is_empty() {
+                return Err(ClientError::Broker("no turns to gather".into()));
+            }
+            let (turn_id, content) = broker
+                .gather(references, style, into_relay, register.clone())
+                .await?;
+            if into_relay {
+                format::print_gather(&turn_id, content.len(), register.as_deref());
+            } else {
+                std::io::stdout().lock().write_all(&content)?;
+            }
         }
         ClientAction::Deliver {
-            sink,
-            session,
+            sinks,
+            session: mut sessions,
+            role,
+            all_with_role,
             path,
+            wait_idle,
+            timeout,
+            turn,
+            separator,
+            register,
+            template,
         } => {
-            validate_deliver_args(&sink, &session, &path)?;
-            broker.deliver(&sink, session, path).await?;
-            format::print_deliver(&sink);
+            let targeted = !sessions.is_empty() || role.is_some() || all_with_role.is_some();
+            validate_deliver_args(&sinks, targeted, &path)?;
+            if let Some(role) = role {
+                sessions.push(resolve_role(&mut broker, &role).await?);
+            }
+            let source = DeliverySource {
+                turn_id: match turn {
+                    Some(turn) => Some(resolve_reference(&mut broker, turn).await?),
+                    None => None,
+                },
+                separator,
+                register,
+                template,
+            };
+            if wait_idle {
+                let role = all_with_role.as_deref();
+                wait_until_all_idle(&mut broker, &sessions, role, timeout).await?;
+            }
+            if let [sink] = sinks.as_slice()
+                && sessions.len() <= 1
+                && all_with_role.is_none()
+            {
+                broker.deliver(sink, sessions.pop(), path, source).await?;
+                format::print_deliver(sink);
+            } else {
+                let targets = InjectTargets {
+                    sessions,
+                    role: all_with_role,
+                };
+                let results = broker.deliver_many(sinks, targets, path, source).await?;
+                report_deliveries(&results)?;
+            }
+        }
+        ClientAction::Registers => {
+            let registers = broker.list_registers().await?;
+            format::print_registers(&registers);
+        }
+        ClientAction::Signal { target, signal } => {
+            let session = resolve_target(&mut broker, target).await?;
+            broker.signal(&session, signal.as_str()).await?;
+            format::print_control(&session, &format!("sent {}", signal.as_str()));
+        }
+        ClientAction::Kill { target } => {
+            let session = resolve_target(&mut broker, target).await?;
+            broker.kill(&session).await?;
+            format::print_control(&session, "terminate requested");
+        }
+        ClientAction::Resize {
+            target,
+            size: (cols, rows),
+        } => {
+            let session = resolve_target(&mut broker, target).await?;
+            broker.resize(&session, cols, rows).await?;
+            format::print_control(&session, &format!("resize to {cols}x{rows} requested"));
+        }
+        ClientAction::Relay {
+            action:
+                RelayAction::Show {
+                    metadata_only,
+                    register,
+                },
+        } => {
+            let (turn_id, result) = broker.relay_show(register).await?;
+            format::print_turn(&turn_id, &result, metadata_only)?;
+        }
+        ClientAction::Relay {
+            action:
+                RelayAction::Set {
+                    file,
+                    source,
+                    register,
+                },
+        } => {
+            let (content, default_source) = match file {
+                Some(ref path) => (
+                    std::fs::read(path)?,
+                    path.file_name()
+                        .map_or_else(|| "file".into(), |n| n.to_string_lossy().into_owned()),
+                ),
+                None => {
+                    let mut content = Vec::new();
+                    std::io::stdin().lock().read_to_end(&mut content)?;
+                    (content, "stdin".to_string())
+                }
+            };
+            let source = source.unwrap_or(default_source);
+            let result = broker
+                .relay_set(content, register.clone(), Some(source))
+                .await?;
+            format::print_relay_set(&result, register.as_deref());
+        }
+        ClientAction::Relay {
+            action: RelayAction::Clear { register },
+        } => {
+            broker.relay_clear(register.clone()).await?;
+            format::print_relay_cleared(register.as_deref());
+        }
+        ClientAction::Relay {
+            action: RelayAction::Undo { register },
+        } => {
+            let result = broker.relay_undo(register.clone()).await?;
+            format::print_relay_undo(&result, register.as_deref());
+        }
+        ClientAction::Relay {
+            action: RelayAction::History,
+        } => {
+            let history = broker.relay_history().await?;
+            format::print_relay_history(&history);
+        }
+        ClientAction::Route {
+            action:
+                RouteAction::Add {
+                    source,
+                    arrow,
+                    target,
+                    on,
+                    template,
+                    grep,
+                    min_size,
+                    skip_interrupted,
+                    wait_idle,
+                    disabled,
+                },
+        } => {
+            let path: Vec<String> = [Some(source), arrow, target]
+                .into_iter()
+                .flatten()
+                .collect();
+            let (source, target) = route_endpoints(&path)?;
+            let spec = RouteSpec {
+                source,
+                target,
+                on,
+                template,
+                pattern: grep,
+                min_size,
+                skip_interrupted,
+                wait_idle,
+            };
+            let route = broker.route_add(spec, disabled).await?;
+            let what = format!("{} -> {} added", route.spec.source, route.spec.target);
+            format::print_route(route.route, &what);
+        }
+        ClientAction::Route {
+            action: RouteAction::List,
+        } => {
+            let routes = broker.route_list().await?;
+            format::print_routes(&routes);
+        }
+        ClientAction::Route {
+            action: RouteAction::Rm { route },
+        } => {
+            broker.route_remove(route).await?;
+            format::print_route(route, "removed");
+        }
+        ClientAction::Route {
+            action: RouteAction::Enable { route },
+        } => {
+            broker.route_enable(route, true).await?;
+            format::print_route(route, "enabled");
+        }
+        ClientAction::Route {
+            action: RouteAction::Disable { route },
+        } => {
+            broker.route_enable(route, false).await?;
+            format::print_route(route, "disabled");
+        }
+        ClientAction::Record {
+            action: RecordAction::Start { target, path },
+        } => {
+            let session = resolve_target(&mut broker, target).await?;
+            // The wrapper resolves the path, so send it absolute.
+            let path = std::path::absolute(path)?;
+            broker
+                .record_start(&session, &path.to_string_lossy())
+                .await?;
+            format::print_control(&session, &format!("recording to {}", path.display()));
+        }
+        ClientAction::Record {
+            action: RecordAction::Stop { target },
+        } => {
+            let session = resolve_target(&mut broker, target).await?;
+            broker.record_stop(&session).await?;
+            format::print_control(&session, "recording stop requested");
+        }
+        ClientAction::Snapshot {
+            action:
+                SnapshotAction::Save {
+                    file,
+                    sessions,
+                    encrypt,
+                },
+        } => {
+            let passphrase = encrypt.then(|| snapshot::passphrase(true)).transpose()?;
+            let content = broker.snapshot_save(sessions).await?;
+            let sealed = snapshot::seal(&content, passphrase.as_deref())?;
+            snapshot::write_file(&file, &sealed)?;
+            format::print_snapshot_saved(&file, sealed.len(), encrypt);
+        }
+        ClientAction::Snapshot {
+            action: SnapshotAction::Load { file },
+        } => {
+            let sealed = std::fs::read(&file)?;
+            let passphrase = snapshot::is_encrypted(&sealed)
+                .then(|| snapshot::passphrase(false))
+                .transpose()?;
+            let content = snapshot::open(&sealed, passphrase.as_deref())?;
+            let sessions = broker.snapshot_load(content).await?;
+            format::print_snapshot_loaded(&sessions);
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a [`SessionTarget`] to a session ID or name.
+///
+/// An explicit session is passed through unchanged (the broker resolves
+/// names). A role is looked up via `list-sessions`.
+async fn resolve_target(
+    broker: &mut BrokerClient,
+    target: SessionTarget,
+) -> Result<String, ClientError> {
+    let session = match (target.session, target.role) {
+        (Some(session), _) => session,
+        (None, Some(role)) => resolve_role(broker, &role).await?,
+        // clap enforces one of the two.
+        (None, None) => {
+            return Err(ClientError::Broker(
+                "a session or --role is required".into(),
+            ));
+        }
+    };
+    Ok(session)
+}
+
+/// Apply a capture command's [`WaitIdle`] flags to a resolved session.
+async fn wait_if_asked(
+    broker: &mut BrokerClient,
+    session: &str,
+    wait: WaitIdle,
+) -> Result<(), ClientError> {
+    if wait.wait_idle {
+        wait_until_idle(broker, session, idle_deadline(wait.timeout)).await?;
+    }
+    Ok(())
+}
+
+/// Resolve the `@focused` source of a turn reference to the session in
+/// the focused window. Other references pass through unchanged; the
+/// broker resolves them (CONTRACT_REGISTRY.md §Turn references).
+async fn resolve_reference(
+    broker: &mut BrokerClient,
+    reference: String,
+) -> Result<String, ClientError> {
+    let Some(selector) = reference.strip_prefix("@focused:") else {
+        return Ok(reference);
+    };
+    let sessions = broker.list_sessions(false).await?;
+    let shared = X11Shared::connect()?;
+    let session = X11SessionResolver::new(&shared)
+        .focused_session(&sessions)?
+        .ok_or_else(|| ResolverError::Session("no session owns the focused window".into()))?;
+    Ok(format!("{session}:{selector}"))
+}
+
+/// Poll interval for `--wait-idle`.
+const WAIT_IDLE_POLL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Block until the session (ID or name) reports the idle state.
+async fn wait_until_idle(
+    broker: &mut BrokerClient,
+    session: &str,
+    deadline: tokio::time::Instant,
+) -> Result<(), ClientError> {
+    loop {
+        let sessions = broker.list_sessions(false).await?;
+        let descriptor = find_session(&sessions, session)
+            .ok_or_else(|| ClientError::Broker("session_not_found".into()))?;
+        if descriptor.state == AgentState::Idle {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(ClientError::WaitTimeout(session.to_string()));
         }
+        tokio::time::sleep(WAIT_IDLE_POLL).await;
     }
+}
 
+/// When a `--wait-idle` with `--timeout` seconds gives up.
+fn idle_deadline(timeout: u64) -> tokio::time::Instant {
+    tokio::time::Instant::now() + std::time::Duration::from_secs(timeout)
+}
+
+/// Block until every listed session, and every live session with
+/// `role`, reports the idle state.
+async fn wait_until_all_idle(
+    broker: &mut BrokerClient,
+    sessions: &[String],
+    role: Option<&str>,
+    timeout: u64,
+) -> Result<(), ClientError> {
+    let deadline = idle_deadline(timeout);
+    for session in sessions {
+        wait_until_idle(broker, session, deadline).await?;
+    }
+    if let Some(role) = role {
+        let live = broker.list_sessions(false).await?;
+        for descriptor in live.iter().filter(|s| s.role.as_deref() == Some(role)) {
+            wait_until_idle(broker, &descriptor.session, deadline).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Print a multi-target paste or deliver's results. Any failed target
+/// fails the command, after the successful ones are reported.
+fn report_deliveries(results: &[DeliveryResult]) -> Result<(), ClientError> {
+    format::print_delivery_results(results);
+    let failed = results.iter().filter(|r| r.status != Status::Ok).count();
+    if failed > 0 {
+        return Err(ClientError::Broker(format!(
+            "{failed} of {} targets failed",
+            results.len()
+        )));
+    }
     Ok(())
 }
 
+/// Find a session descriptor by session ID or name.
+fn find_session<'a>(sessions: &'a [SessionDescriptor], key: &str) -> Option<&'a SessionDescriptor> {
+    sessions
+        .iter()
+        .find(|s| s.session == key || s.name.as_deref() == Some(key))
+}
+
+/// Resolve a role to the ID of the single session holding it.
+async fn resolve_role(broker: &mut BrokerClient, role: &str) -> Result<String, ClientError> {
+    let sessions = broker.list_sessions(false).await?;
+    select_by_role(&sessions, role)
+}
+
+/// Pick the one session with `role`. Zero or several matches are errors
+/// so that a role never silently targets the wrong session.
+fn select_by_role(sessions: &[SessionDescriptor], role: &str) -> Result<String, ClientError> {
+    let mut matches = sessions.iter().filter(|s| s.role.as_deref() == Some(role));
+    match (matches.next(), matches.next()) {
+        (Some(s), None) => Ok(s.session.clone()),
+        (None, _) => Err(ClientError::Broker(format!(
+            "no session with role {role:?}"
+        ))),
+        (Some(_), Some(_)) => Err(ClientError::Broker(format!(
+            "multiple sessions with role {role:?}; address one by ID or name"
+        ))),
+    }
+}
+
+/// Every session with `role` that has a turn, oldest first. A role
+/// that matches no session is an error.
+fn sessions_with_role(
+    sessions: &[SessionDescriptor],
+    role: &str,
+) -> Result<Vec<String>, ClientError> {
+    let mut matches: Vec<&SessionDescriptor> = sessions
+        .iter()
+        .filter(|s| s.role.as_deref() == Some(role))
+        .collect();
+    if matches.is_empty() {
+        return Err(ClientError::Broker(format!(
+            "no session with role {role:?}"
+        )));
+    }
+    matches.retain(|s| s.has_turn);
+    matches.sort_by(|a, b| (a.started_at, &a.session).cmp(&(b.started_at, &b.session)));
+    Ok(matches.into_iter().map(|s| s.session.clone()).collect())
+}
+
+/// A `gather` source as a turn reference: a bare session (or
+/// `@focused`) stands for its latest turn.
+fn gather_reference(source: String) -> String {
+    if source.contains(':') || source.starts_with("@relay") {
+        source
+    } else {
+        format!("{source}:@latest")
+    }
+}
+
+/// Split a `route add` path — `planner -> implementer` as one or
+/// three arguments, or `planner implementer` — into source and target.
+fn route_endpoints(path: &[String]) -> Result<(String, String), ClientError> {
+    let joined = path.join(" ");
+    let (source, target) = match joined.split_once("->") {
+        Some((source, target)) => (source.trim(), target.trim()),
+        None => joined.split_once(' ').unwrap_or((&joined, "")),
+    };
+    if source.is_empty() || target.is_empty() || source.contains(' ') || target.contains(' ') {
+        return Err(ClientError::Broker(format!(
+            "expected <source> -> <target>, got {joined:?}"
+        )));
+    }
+    Ok((source.to_string(), target.to_string()))
+}
+
 /// Validate deliver arguments before sending to the broker.
 ///
-/// Checks cross-field constraints: inject requires `--session`, file
-/// requires `--path`. Unknown sink names are rejected.
+/// Checks cross-field constraints for every sink: inject requires a
+/// target (`--session`, `--role` or `--all-with-role`), file requires
+/// `--path`. Unknown sink names are rejected.
 fn validate_deliver_args(
-    sink: &str,
-    session: &Option<String>,
+    sinks: &[String],
+    targeted: bool,
     path: &Option<String>,
 ) -> Result<(), ClientError> {
-    match sink {
-        "clipboard" => Ok(()),
-        "inject" => {
-            if session.is_none() {
-                Err(ClientError::Broker(
-                    "--session is required for inject sink".into(),
-                ))
-            } else {
-                Ok(())
+    for sink in sinks {
+        match sink.as_str() {
+            "clipboard" => {}
+            "inject" => {
+                if !targeted {
+                    return Err(ClientError::Broker(
+                        "--session, --role or --all-with-role is required for inject sink".into(),
+                    ));
+                }
             }
-        }
-        "file" => {
-            if path.is_none() {
-                Err(ClientError::Broker(
-                    "--path is required for file sink".into(),
-                ))
-            } else {
-                Ok(())
+            "file" => {
+                if path.is_none() {
+                    return Err(ClientError::Broker(
+                        "--path is required for file sink".into(),
+                    ));
+                }
+            }
+            other => {
+                return Err(ClientError::Broker(format!(
+                    "unknown sink: {other} (expected: clipboard, file, inject)"
+                )));
             }
         }
-        other => Err(ClientError::Broker(format!(
-            "unknown sink: {other} (expected: clipboard, file, inject)"
-        ))),
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sinks(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
     #[test]
     fn validate_deliver_clipboard_ok() {
-        assert!(validate_deliver_args("clipboard", &None, &None).is_ok());
+        assert!(validate_deliver_args(&sinks(&["clipboard"]), false, &None).is_ok());
     }
 
     #[test]
     fn validate_deliver_inject_ok() {
-        assert!(validate_deliver_args("inject", &Some("s1".into()), &None).is_ok());
+        assert!(validate_deliver_args(&sinks(&["inject"]), true, &None).is_ok());
     }
 
     #[test]
     fn validate_deliver_inject_missing_session() {
-        let err = validate_deliver_args("inject", &None, &None).unwrap_err();
+        let err = validate_deliver_args(&sinks(&["inject"]), false, &None).unwrap_err();
         assert!(err.to_string().contains("--session"));
     }
 
+    #[test]
+    fn validate_deliver_checks_every_sink() {
+        let path = Some("/tmp/out".to_string());
+        assert!(
+            validate_deliver_args(&sinks(&["clipboard", "file", "inject"]), true, &path).is_ok()
+        );
+        let err = validate_deliver_args(&sinks(&["clipboard", "file"]), false, &None).unwrap_err();
+        assert!(err.to_string().contains("--path"));
+    }
+
+    fn session_with_role(id: &str, role: Option<&str>) -> SessionDescriptor {
+        SessionDescriptor {
+            session: id.into(),
+            pid: 1,
+            has_turn: false,
+            role: role.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn select_by_role_single_match() {
+        let sessions = vec![
+            session_with_role("s1", Some("planner")),
+            session_with_role("s2", Some("reviewer")),
+            session_with_role("s3", None),
+        ];
+        assert_eq!(select_by_role(&sessions, "reviewer").unwrap(), "s2");
+    }
+
+    #[test]
+    fn select_by_role_no_match() {
+        let sessions = vec![session_with_role("s1", Some("planner"))];
+        let err = select_by_role(&sessions, "reviewer").unwrap_err();
+        assert!(err.to_string().contains("no session"));
+    }
+
+    #[test]
+    fn select_by_role_ambiguous() {
+        let sessions = vec![
+            session_with_role("s1", Some("reviewer")),
+            session_with_role("s2", Some("reviewer")),
+        ];
+        let err = select_by_role(&sessions, "reviewer").unwrap_err();
+        assert!(err.to_string().contains("multiple"));
+    }
+
+    #[test]
+    fn sessions_with_role_all_matches() {
+        let with_turn = |id, role| SessionDescriptor {
+            has_turn: true,
+            ..session_with_role(id, Some(role))
+        };
+        let sessions = vec![
+            with_turn("s2", "reviewer"),
+            with_turn("s1", "reviewer"),
+            with_turn("s4", "planner"),
+            session_with_role("s3", Some("reviewer")),
+        ];
+        assert_eq!(
+            sessions_with_role(&sessions, "reviewer").unwrap(),
+            ["s1", "s2"]
+        );
+        assert!(sessions_with_role(&sessions, "tester").is_err());
+    }
+
+    #[test]
+    fn gather_reference_defaults_to_latest() {
+        assert_eq!(gather_reference("planner".into()), "planner:@latest");
+        assert_eq!(gather_reference("@focused".into()), "@focused:@latest");
+        assert_eq!(gather_reference("planner:-1".into()), "planner:-1");
+        assert_eq!(gather_reference("@relay".into()), "@relay");
+    }
+
+    #[test]
+    fn find_session_by_id_or_name() {
+        let mut named = session_with_role("s2", None);
+        named.name = Some("planner".into());
+        let sessions = vec![session_with_role("s1", None), named];
+        assert_eq!(find_session(&sessions, "s1").unwrap().session, "s1");
+        assert_eq!(find_session(&sessions, "planner").unwrap().session, "s2");
+        assert!(find_session(&sessions, "nope").is_none());
+    }
+
+    #[test]
+    fn route_endpoints_accepts_arrow_forms() {
+        let path = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let expected = ("planner".to_string(), "implementer".to_string());
+        for args in [
+            &["planner", "->", "implementer"][..],
+            &["planner -> implementer"],
+            &["planner->implementer"],
+            &["planner", "implementer"],
+        ] {
+            assert_eq!(route_endpoints(&path(args)).unwrap(), expected, "{args:?}");
+        }
+        for args in [&["planner"][..], &["planner", "->"], &["a", "b", "c"]] {
+            assert!(route_endpoints(&path(args)).is_err(), "{args:?}");
+        }
+    }
+
     #[test]
     fn validate_deliver_file_ok() {
-        assert!(validate_deliver_args("file", &None, &Some("/tmp/out".into())).is_ok());
+        assert!(validate_deliver_args(&sinks(&["file"]), false, &Some("/tmp/out".into())).is_ok());
     }
 
     #[test]
     fn validate_deliver_file_missing_path() {
-        let err = validate_deliver_args("file", &None, &None).unwrap_err();
+        let err = validate_deliver_args(&sinks(&["file"]), false, &None).unwrap_err();
         assert!(err.to_string().contains("--path"));
     }
 
     #[test]
     fn validate_deliver_unknown_sink() {
-        let err = validate_deliver_args("foobar", &None, &None).unwrap_err();
+        let err = validate_deliver_args(&sinks(&["foobar"]), false, &None).unwrap_err();
         assert!(err.to_string().contains("unknown sink"));
     }
 }
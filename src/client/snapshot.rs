@@ -0,0 +1,296 @@
+//! Snapshot files — the broker's encoded snapshot behind a small
+//! header, optionally encrypted with a passphrase.
+//!
+//! Layout: `CLIPSNAP`, a format version byte, an encryption byte, then
+//! either the snapshot as is (`0`) or (`1`) Argon2id parameters
+//! (memory KiB, iterations, lanes; u32 LE), a 16-byte salt, a 12-byte
+//! nonce and the ChaCha20-Poly1305 ciphertext. The header is
+//! authenticated with the ciphertext. See CONTRACT_BROKER.md §Snapshots.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use nix::sys::termios::{self, LocalFlags, SetArg};
+
+use super::ClientError;
+
+const MAGIC: &[u8; 8] = b"CLIPSNAP";
+const FORMAT_VERSION: u8 = 1;
+const PLAIN: u8 = 0;
+const ENCRYPTED: u8 = 1;
+/// Argon2id memory, iterations and lanes, u32 LE each.
+const PARAMS_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Argon2id cost parameters, stored in the file so they can change
+/// without breaking older snapshots.
+#[derive(Debug, Clone, Copy)]
+struct Kdf {
+    memory_kib: u32,
+    iterations: u32,
+    lanes: u32,
+}
+
+/// Highest costs accepted from a file, so a crafted header cannot make
+/// the client allocate or spin without bound.
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_LANES: u32 = 16;
+
+impl Kdf {
+    /// Reject costs beyond the caps before any key is derived.
+    fn check(self) -> Result<Self, ClientError> {
+        let limits = [
+            ("memory cost", self.memory_kib, MAX_MEMORY_KIB),
+            ("iterations", self.iterations, MAX_ITERATIONS),
+            ("lanes", self.lanes, MAX_LANES),
+        ];
+        for (name, value, max) in limits {
+            if value > max {
+                return Err(ClientError::Snapshot(format!(
+                    "key {name} {value} exceeds {max}"
+                )));
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Cost for new files: the Argon2 crate's recommended defaults.
+const KDF: Kdf = Kdf {
+    memory_kib: Params::DEFAULT_M_COST,
+    iterations: Params::DEFAULT_T_COST,
+    lanes: Params::DEFAULT_P_COST,
+};
+
+/// Environment variable read for the passphrase instead of prompting.
+pub const PASSPHRASE_ENV: &str = "CLIPPY_SNAPSHOT_PASSPHRASE";
+
+/// Write a snapshot file, readable by the owner only (it holds
+/// session content).
+pub fn write_file(path: &Path, file: &[u8]) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(file)
+}
+
+/// Get the snapshot passphrase from [`PASSPHRASE_ENV`], or prompt for
+/// it on the terminal without echo (twice when `confirm`).
+pub fn passphrase(confirm: bool) -> Result<String, ClientError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = prompt("Snapshot passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(ClientError::Snapshot("empty passphrase".into()));
+    }
+    if confirm && prompt("Confirm passphrase: ")? != passphrase {
+        return Err(ClientError::Snapshot("passphrases do not match".into()));
+    }
+    Ok(passphrase)
+}
+
+/// Read one line from the controlling terminal with echo turned off.
+fn prompt(label: &str) -> Result<String, ClientError> {
+    let mut tty = File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| {
+            ClientError::Snapshot(format!(
+                "cannot prompt for a passphrase ({e}); set {PASSPHRASE_ENV}"
+            ))
+        })?;
+    let original = termios::tcgetattr(&tty).map_err(io::Error::from)?;
+    let mut silent = original.clone();
+    silent.local_flags.remove(LocalFlags::ECHO);
+    termios::tcsetattr(&tty, SetArg::TCSANOW, &silent).map_err(io::Error::from)?;
+
+    let mut line = String::new();
+    let read = tty
+        .write_all(label.as_bytes())
+        .and_then(|()| BufReader::new(&tty).read_line(&mut line));
+    // Restore echo even if the read failed.
+    let restored = termios::tcsetattr(&tty, SetArg::TCSANOW, &original);
+    tty.write_all(b"\n")?;
+    read?;
+    restored.map_err(io::Error::from)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Does the file need a passphrase to open?
+pub fn is_encrypted(file: &[u8]) -> bool {
+    file.len() > MAGIC.len() + 1 && file.starts_with(MAGIC) && file[MAGIC.len() + 1] == ENCRYPTED
+}
+
+/// Wrap an encoded snapshot in the file format, encrypting it when a
+/// passphrase is given.
+pub fn seal(snapshot: &[u8], passphrase: Option<&str>) -> Result<Vec<u8>, ClientError> {
+    seal_with(snapshot, passphrase, KDF)
+}
+
+fn seal_with(snapshot: &[u8], passphrase: Option<&str>, kdf: Kdf) -> Result<Vec<u8>, ClientError> {
+    let mut file = MAGIC.to_vec();
+    file.push(FORMAT_VERSION);
+    let Some(passphrase) = passphrase else {
+        file.push(PLAIN);
+        file.extend_from_slice(snapshot);
+        return Ok(file);
+    };
+    file.push(ENCRYPTED);
+    for value in [kdf.memory_kib, kdf.iterations, kdf.lanes] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    file.extend_from_slice(&salt);
+    file.extend_from_slice(&nonce);
+
+    let cipher = cipher(passphrase, &salt, kdf)?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: snapshot,
+                aad: &file,
+            },
+        )
+        .map_err(|_| ClientError::Snapshot("encryption failed".into()))?;
+    file.extend_from_slice(&ciphertext);
+    Ok(file)
+}
+
+/// Unwrap a snapshot file, decrypting it with `passphrase` if it is
+/// encrypted. A wrong passphrase and a corrupted file are
+/// indistinguishable and both fail.
+pub fn open(file: &[u8], passphrase: Option<&str>) -> Result<Vec<u8>, ClientError> {
+    let invalid = || ClientError::Snapshot("not a clippy snapshot file".into());
+    let rest = file.strip_prefix(MAGIC.as_slice()).ok_or_else(invalid)?;
+    let (&version, rest) = rest.split_first().ok_or_else(invalid)?;
+    if version != FORMAT_VERSION {
+        return Err(ClientError::Snapshot(format!(
+            "unsupported snapshot file version {version}"
+        )));
+    }
+    let (&encryption, body) = rest.split_first().ok_or_else(invalid)?;
+    match encryption {
+        PLAIN => Ok(body.to_vec()),
+        ENCRYPTED => {
+            let passphrase =
+                passphrase.ok_or_else(|| ClientError::Snapshot("snapshot is encrypted".into()))?;
+            let header_len = MAGIC.len() + 2 + PARAMS_LEN + SALT_LEN + NONCE_LEN;
+            if file.len() < header_len {
+                return Err(invalid());
+            }
+            let (header, ciphertext) = file.split_at(header_len);
+            let mut params = body[..PARAMS_LEN]
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().expect("4-byte chunk")));
+            let kdf = Kdf {
+                memory_kib: params.next().expect("3 params"),
+                iterations: params.next().expect("3 params"),
+                lanes: params.next().expect("3 params"),
+            }
+            .check()?;
+            let salt = &body[PARAMS_LEN..PARAMS_LEN + SALT_LEN];
+            let nonce = &body[PARAMS_LEN + SALT_LEN..PARAMS_LEN + SALT_LEN + NONCE_LEN];
+            cipher(passphrase, salt, kdf)?
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: header,
+                    },
+                )
+                .map_err(|_| ClientError::Snapshot("wrong passphrase or corrupted snapshot".into()))
+        }
+        other => Err(ClientError::Snapshot(format!(
+            "unknown snapshot encryption {other}"
+        ))),
+    }
+}
+
+/// Derive the file key from the passphrase.
+fn cipher(passphrase: &str, salt: &[u8], kdf: Kdf) -> Result<ChaCha20Poly1305, ClientError> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.lanes, Some(32))
+        .map_err(|e| ClientError::Snapshot(format!("invalid key parameters: {e}")))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| ClientError::Snapshot(format!("key derivation failed: {e}")))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests run fast in debug builds.
+    const TEST_KDF: Kdf = Kdf {
+        memory_kib: 64,
+        iterations: 1,
+        lanes: 1,
+    };
+
+    #[test]
+    fn plain_round_trip() {
+        let file = seal(b"snapshot", None).unwrap();
+        assert!(!is_encrypted(&file));
+        assert_eq!(open(&file, None).unwrap(), b"snapshot");
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let file = seal_with(b"snapshot", Some("hunter2"), TEST_KDF).unwrap();
+        assert!(is_encrypted(&file));
+        assert!(!file.windows(8).any(|w| w == b"snapshot"));
+        assert_eq!(open(&file, Some("hunter2")).unwrap(), b"snapshot");
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let file = seal_with(b"snapshot", Some("hunter2"), TEST_KDF).unwrap();
+        assert!(open(&file, Some("hunter3")).is_err());
+        assert!(open(&file, None).is_err());
+    }
+
+    #[test]
+    fn tampered_header_fails() {
+        let mut file = seal_with(b"snapshot", Some("hunter2"), TEST_KDF).unwrap();
+        // Flip a salt byte: the key changes and the tag no longer verifies.
+        file[MAGIC.len() + 2 + PARAMS_LEN] ^= 1;
+        assert!(open(&file, Some("hunter2")).is_err());
+    }
+
+    #[test]
+    fn rejects_excessive_costs() {
+        let file = seal_with(b"snapshot", Some("hunter2"), TEST_KDF).unwrap();
+        // Memory, iterations, lanes: each past its cap fails up front.
+        for index in 0..3 {
+            let mut file = file.clone();
+            let at = MAGIC.len() + 2 + index * 4;
+            file[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            let err = open(&file, Some("hunter2")).unwrap_err().to_string();
+            assert!(err.contains("exceeds"), "{err}");
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(open(b"{\"version\":2}", None).is_err());
+        assert!(open(b"CLIPSNAP", None).is_err());
+    }
+}
@@ -85,7 +85,7 @@ impl BrokerClient {
         self.next_id += 1;
 
         self.framed
-            .send(Message::ListSessions { id })
+            .send(Message::ListSessions { id, all: false })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send list_sessions: {e}")))?;
 
@@ -110,10 +110,15 @@ impl BrokerClient {
         }
     }
 
-    /// Capture the latest turn from a session into the relay buffer.
+    /// Capture the latest turn from a session into a relay register
+    /// (`None`: the unnamed one).
     ///
     /// Returns the byte size of the captured content on success.
-    pub async fn capture(&mut self, session: &str) -> Result<u32, HotkeyError> {
+    pub async fn capture(
+        &mut self,
+        session: &str,
+        register: Option<&str>,
+    ) -> Result<u32, HotkeyError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -121,6 +126,7 @@ impl BrokerClient {
             .send(Message::Capture {
                 id,
                 session: session.to_string(),
+                register: register.map(str::to_string),
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send capture: {e}")))?;
@@ -141,8 +147,47 @@ impl BrokerClient {
         }
     }
 
-    /// Deliver relay buffer content to the clipboard sink.
-    pub async fn deliver_clipboard(&mut self) -> Result<(), HotkeyError> {
+    /// Restore a relay register's previous content.
+    ///
+    /// Returns the restored turn ID and byte size on success.
+    pub async fn relay_undo(
+        &mut self,
+        register: Option<&str>,
+    ) -> Result<(String, u32), HotkeyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.framed
+            .send(Message::RelayUndo {
+                id,
+                register: register.map(str::to_string),
+            })
+            .await
+            .map_err(|e| HotkeyError::Broker(format!("send relay_undo: {e}")))?;
+
+        match self.framed.next().await {
+            Some(Ok(Message::Response {
+                status: Status::Ok,
+                turn_id: Some(turn_id),
+                size: Some(size),
+                ..
+            })) => Ok((turn_id, size)),
+            Some(Ok(Message::Response { error, .. })) => Err(HotkeyError::Broker(format!(
+                "relay_undo failed: {}",
+                error.unwrap_or_default()
+            ))),
+            other => Err(HotkeyError::Broker(format!(
+                "unexpected relay_undo response: {other:?}"
+            ))),
+        }
+    }
+
+    /// Deliver a relay register's content to the clipboard sink.
+    pub async fn deliver_clipboard(
+        &mut self,
+        register: Option<&str>,
+        template: Option<&str>,
+    ) -> Result<(), HotkeyError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -150,8 +195,15 @@ impl BrokerClient {
             .send(Message::Deliver {
                 id,
                 sink: "clipboard".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: None,
+                turn_id: None,
+                separator: None,
+                register: register.map(str::to_string),
+                template: template.map(str::to_string),
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send deliver_clipboard: {e}")))?;
@@ -170,8 +222,14 @@ impl BrokerClient {
         }
     }
 
-    /// Paste relay buffer content to a session (inject into its PTY).
-    pub async fn paste(&mut self, session: &str) -> Result<(), HotkeyError> {
+    /// Paste a relay register's content to a session (inject into its
+    /// PTY).
+    pub async fn paste(
+        &mut self,
+        session: &str,
+        register: Option<&str>,
+        template: Option<&str>,
+    ) -> Result<(), HotkeyError> {
         let id = self.next_id;
         self.next_id += 1;
 
@@ -179,6 +237,10 @@ impl BrokerClient {
             .send(Message::Paste {
                 id,
                 session: session.to_string(),
+                sessions: Vec::new(),
+                role: None,
+                register: register.map(str::to_string),
+                template: template.map(str::to_string),
             })
             .await
             .map_err(|e| HotkeyError::Broker(format!("send paste: {e}")))?;
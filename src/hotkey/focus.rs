@@ -159,6 +159,7 @@ mod tests {
             session: "s1".into(),
             pid: my_pid,
             has_turn: false,
+            ..Default::default()
         }];
 
         // Our parent should be an ancestor of our PID.
@@ -173,6 +174,7 @@ mod tests {
             session: "s1".into(),
             pid: 1, // init — window PID 999999 is not an ancestor of PID 1
             has_turn: false,
+            ..Default::default()
         }];
 
         let result = resolve_session(999_999, &sessions);
@@ -190,11 +192,13 @@ mod tests {
                 session: "s1".into(),
                 pid: my_pid,
                 has_turn: false,
+                ..Default::default()
             },
             SessionDescriptor {
                 session: "s2".into(),
                 pid: my_pid,
                 has_turn: true,
+                ..Default::default()
             },
         ];
 
@@ -216,6 +220,7 @@ mod tests {
             session: "s1".into(),
             pid: my_pid,
             has_turn: false,
+            ..Default::default()
         }];
 
         let result = resolve_session(my_pid, &sessions);
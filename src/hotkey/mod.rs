@@ -16,6 +16,7 @@ use tokio::signal::unix::{SignalKind, signal as tokio_signal};
 
 use broker_client::BrokerClient;
 
+use crate::ipc::protocol::SessionDescriptor;
 use crate::resolver::{HotkeyEvent, HotkeyProvider, KeyBinding, ResolverError, SessionResolver};
 
 /// Hotkey client errors.
@@ -33,6 +34,16 @@ pub enum HotkeyError {
     Io(#[from] std::io::Error),
 }
 
+/// What the hotkeys relay through: the register they capture into and
+/// paste from (`None`: the unnamed one), and the named templates paste
+/// and clipboard delivery render through.
+#[derive(Debug, Default)]
+pub struct RelayOptions {
+    pub register: Option<String>,
+    pub paste_template: Option<String>,
+    pub clipboard_template: Option<String>,
+}
+
 /// Run the hotkey client.
 ///
 /// This is the main entry point called from `main.rs` for the `hotkey`
@@ -53,6 +64,8 @@ pub async fn run(
     capture_key: String,
     paste_key: String,
     clipboard_key: Option<String>,
+    undo_key: Option<String>,
+    relay: RelayOptions,
     session_resolver: &dyn SessionResolver,
     hotkey_provider: &mut dyn HotkeyProvider,
 ) -> Result<(), HotkeyError> {
@@ -64,9 +77,14 @@ pub async fn run(
     let capture_binding = KeyBinding { spec: capture_key };
     let paste_binding = KeyBinding { spec: paste_key };
     let clipboard_binding = clipboard_key.map(|key| KeyBinding { spec: key });
+    let undo_binding = undo_key.map(|key| KeyBinding { spec: key });
 
-    let registration =
-        hotkey_provider.register(&capture_binding, &paste_binding, clipboard_binding.as_ref())?;
+    let registration = hotkey_provider.register(
+        &capture_binding,
+        &paste_binding,
+        clipboard_binding.as_ref(),
+        undo_binding.as_ref(),
+    )?;
 
     // CONTRACT_HOTKEY.md §149-150: if no bindings succeed, exit.
     if registration.bindings_ok == 0 {
@@ -103,7 +121,9 @@ pub async fn run(
                     break;
                 };
 
-                if let Err(e) = dispatch_action(event, session_resolver, &mut broker).await {
+                if let Err(e) =
+                    dispatch_action(event, &relay, session_resolver, &mut broker).await
+                {
                     // Check if this is a broker disconnect.
                     if is_broker_error(&e) {
                         tracing::error!(error = %e, "broker disconnected — shutting down");
@@ -158,11 +178,28 @@ pub async fn run(
 }
 
 /// Dispatch a hotkey event: resolve focused session, send request to broker.
+///
+/// Capture, paste and clipboard use `relay`'s register and templates.
 async fn dispatch_action(
     event: HotkeyEvent,
+    relay: &RelayOptions,
     session_resolver: &dyn SessionResolver,
     broker: &mut BrokerClient,
 ) -> Result<(), HotkeyError> {
+    let register = relay.register.as_deref();
+    // Suffix naming the register in feedback lines.
+    let into = register
+        .map(|r| format!(" (register {r})"))
+        .unwrap_or_default();
+
+    // Undo acts on the relay buffer only; no focused session needed.
+    if let HotkeyEvent::Undo = event {
+        let (turn_id, size) = broker.relay_undo(register).await?;
+        tracing::info!(turn_id = %turn_id, size, register, "relay restored");
+        eprintln!("restored relay buffer to {turn_id} ({size} bytes){into}");
+        return Ok(());
+    }
+
     // 1. List sessions from broker.
     let sessions = broker.list_sessions().await?;
 
@@ -176,29 +213,51 @@ async fn dispatch_action(
         .focused_session(&sessions)?
         .ok_or_else(|| ResolverError::Session("no clippy session in focused window".into()))?;
 
+    // Human-readable label for feedback lines.
+    let label = sessions
+        .iter()
+        .find(|s| s.session == session_id)
+        .map(session_label)
+        .unwrap_or_else(|| session_id.clone());
+
     // 3. Send action to broker.
     match event {
         HotkeyEvent::Capture => {
-            let size = broker.capture(&session_id).await?;
-            tracing::info!(session = %session_id, size, "captured");
-            eprintln!("captured {size} bytes from session {session_id}");
+            let size = broker.capture(&session_id, register).await?;
+            tracing::info!(session = %session_id, size, register, "captured");
+            eprintln!("captured {size} bytes from session {label}{into}");
         }
         HotkeyEvent::Paste => {
-            broker.paste(&session_id).await?;
-            tracing::info!(session = %session_id, "pasted");
-            eprintln!("pasted to session {session_id}");
+            broker
+                .paste(&session_id, register, relay.paste_template.as_deref())
+                .await?;
+            tracing::info!(session = %session_id, register, "pasted");
+            eprintln!("pasted to session {label}{into}");
         }
         HotkeyEvent::Clipboard => {
-            let size = broker.capture(&session_id).await?;
-            broker.deliver_clipboard().await?;
+            let size = broker.capture(&session_id, register).await?;
+            broker
+                .deliver_clipboard(register, relay.clipboard_template.as_deref())
+                .await?;
             tracing::info!(session = %session_id, size, "captured to clipboard");
-            eprintln!("captured {size} bytes to clipboard from session {session_id}");
+            eprintln!("captured {size} bytes to clipboard from session {label}");
         }
+        HotkeyEvent::Undo => unreachable!("handled before focus resolution"),
     }
 
     Ok(())
 }
 
+/// Describe a session for feedback: its name (or ID), plus the
+/// terminal title when the agent has set one.
+fn session_label(session: &SessionDescriptor) -> String {
+    let base = session.name.as_deref().unwrap_or(&session.session);
+    match session.title.as_deref() {
+        Some(title) => format!("{base} ({title})"),
+        None => base.to_string(),
+    }
+}
+
 /// Check if an error indicates a broker connection failure.
 fn is_broker_error(e: &HotkeyError) -> bool {
     matches!(e, HotkeyError::Broker(_))
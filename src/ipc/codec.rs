@@ -167,8 +167,9 @@ impl Encoder<Message> for FrameCodec {
 /// Result of attempting to decode a raw frame into a protocol message.
 #[derive(Debug)]
 pub enum DecodeResult {
-    /// Successfully decoded a known message variant.
-    Ok(Message),
+    /// Successfully decoded a known message variant (boxed: `Message`
+    /// is far larger than the other variants).
+    Ok(Box<Message>),
     /// Unknown type — extracted envelope for error response echoing.
     UnknownType(RawEnvelope),
     /// Completely malformed — could not even extract `{type, id}`.
@@ -182,7 +183,7 @@ pub enum DecodeResult {
 /// 3. If both fail, return [`DecodeResult::Malformed`].
 pub fn decode_frame(payload: &[u8]) -> DecodeResult {
     match rmp_serde::from_slice::<Message>(payload) {
-        Ok(msg) => DecodeResult::Ok(msg),
+        Ok(msg) => DecodeResult::Ok(Box::new(msg)),
         Err(_) => match rmp_serde::from_slice::<RawEnvelope>(payload) {
             Ok(envelope) => DecodeResult::UnknownType(envelope),
             Err(e) => DecodeResult::Malformed(e),
@@ -238,10 +239,32 @@ mod tests {
                 session: "s1".into(),
                 pid: 42,
                 pattern: "generic".into(),
+                name: None,
+                role: None,
+                labels: Default::default(),
+                command: vec!["claude".into()],
+                started_at: 1000,
+            },
+            Message::InputActivity {
+                id: 1,
+                session: "s1".into(),
+                timestamp: 1000,
+            },
+            Message::SessionState {
+                id: 1,
+                session: "s1".into(),
+                state: AgentState::Busy,
+            },
+            Message::SessionTitle {
+                id: 1,
+                session: "s1".into(),
+                title: "refactor parser".into(),
             },
             Message::Deregister {
                 id: 2,
                 session: "s1".into(),
+                exit_code: Some(1),
+                reason: Some("exited with code 1".into()),
             },
             Message::TurnCompleted {
                 id: 3,
@@ -249,20 +272,145 @@ mod tests {
                 content: b"turn content".to_vec(),
                 interrupted: false,
                 timestamp: 1000,
+                seq: None,
+                context: Default::default(),
+                replayed: true,
             },
             Message::Capture {
                 id: 4,
                 session: "s1".into(),
+                register: None,
             },
             Message::Paste {
                 id: 5,
                 session: "s1".into(),
+                sessions: Vec::new(),
+                role: None,
+                register: Some("a".into()),
+                template: Some("review".into()),
             },
             Message::Inject {
                 id: 0,
                 content: b"inject bytes".to_vec(),
             },
-            Message::ListSessions { id: 6 },
+            Message::CaptureLines {
+                id: 5,
+                session: "s1".into(),
+                last: 80,
+                grep: Some("error".into()),
+                register: None,
+            },
+            Message::ScrollbackLines {
+                id: 5,
+                session: "s1".into(),
+                query: 1,
+                content: b"error: boom\n".to_vec(),
+            },
+            Message::Subscribe {
+                id: 5,
+                session: "s1".into(),
+            },
+            Message::OutputTap {
+                id: 0,
+                session: "s1".into(),
+                enabled: true,
+            },
+            Message::Output {
+                id: 0,
+                session: "s1".into(),
+                content: b"\x1b[1mhi\x1b[0m".to_vec(),
+            },
+            Message::OutputEnd {
+                id: 0,
+                session: "s1".into(),
+            },
+            Message::Signal {
+                id: 5,
+                session: "s1".into(),
+                signal: "SIGINT".into(),
+            },
+            Message::Kill {
+                id: 5,
+                session: "s1".into(),
+            },
+            Message::Resize {
+                id: 5,
+                session: "s1".into(),
+                cols: 120,
+                rows: 40,
+            },
+            Message::RecordStart {
+                id: 5,
+                session: "s1".into(),
+                path: "/tmp/s1.cast".into(),
+            },
+            Message::RecordStop {
+                id: 5,
+                session: "s1".into(),
+            },
+            Message::SnapshotSave {
+                id: 5,
+                sessions: vec!["planner".into()],
+            },
+            Message::SnapshotLoad {
+                id: 5,
+                content: vec![0x80],
+            },
+            Message::Replay {
+                id: 5,
+                session: "implementer".into(),
+                from: Some("planner".into()),
+                snapshot: Some(vec![0x80]),
+                last: 3,
+                frame: true,
+            },
+            Message::ListRegisters { id: 6 },
+            Message::RelayShow {
+                id: 6,
+                register: None,
+            },
+            Message::RelaySet {
+                id: 6,
+                content: b"notes".to_vec(),
+                register: Some("a".into()),
+                source: Some("notes.md".into()),
+            },
+            Message::RelayClear {
+                id: 6,
+                register: None,
+            },
+            Message::RelayUndo {
+                id: 6,
+                register: Some("a".into()),
+            },
+            Message::RelayHistory { id: 6 },
+            Message::Gather {
+                id: 46,
+                turn_ids: vec!["planner:@latest".into(), "reviewer:3".into()],
+                style: SectionStyle::Markdown,
+                into_relay: true,
+                register: None,
+            },
+            Message::RouteAdd {
+                id: 47,
+                route: RouteSpec {
+                    source: "planner".into(),
+                    target: "implementer".into(),
+                    template: Some("{{content}}\n".into()),
+                    pattern: Some("^PLAN".into()),
+                    min_size: 10,
+                    skip_interrupted: true,
+                    wait_idle: true,
+                    ..Default::default()
+                },
+                disabled: true,
+            },
+            Message::RouteEnable {
+                id: 48,
+                route: 1,
+                enabled: false,
+            },
+            Message::ListSessions { id: 6, all: false },
             Message::GetTurn {
                 id: 7,
                 turn_id: "s1:1".into(),
@@ -275,12 +423,22 @@ mod tests {
             Message::CaptureByID {
                 id: 9,
                 turn_id: "s1:2".into(),
+                turn_ids: Vec::new(),
+                separator: None,
+                register: None,
             },
             Message::Deliver {
                 id: 10,
                 sink: "clipboard".into(),
+                sinks: Vec::new(),
                 session: None,
+                sessions: Vec::new(),
+                role: None,
                 path: None,
+                turn_id: None,
+                separator: None,
+                register: None,
+                template: None,
             },
             Message::Response {
                 id: 1,
@@ -295,6 +453,9 @@ mod tests {
                 interrupted: None,
                 truncated: None,
                 turns: None,
+                registers: None,
+                results: None,
+                routes: None,
             },
         ];
 
@@ -315,7 +476,7 @@ mod tests {
 
     #[test]
     fn partial_payload_returns_none() {
-        let msg = Message::ListSessions { id: 1 };
+        let msg = Message::ListSessions { id: 1, all: false };
         let mut full = encode_message(&msg);
 
         // Take only the header + half the payload.
@@ -333,10 +494,11 @@ mod tests {
 
     #[test]
     fn multiple_messages_in_buffer() {
-        let msg1 = Message::ListSessions { id: 1 };
+        let msg1 = Message::ListSessions { id: 1, all: false };
         let msg2 = Message::Capture {
             id: 2,
             session: "s1".into(),
+            register: None,
         };
 
         let mut buf = BytesMut::new();
@@ -360,6 +522,9 @@ mod tests {
             content: content.clone(),
             interrupted: true,
             timestamp: 1000,
+            seq: None,
+            context: Default::default(),
+            replayed: false,
         };
 
         let mut buf = encode_message(&msg);
@@ -396,7 +561,7 @@ mod tests {
 
     #[test]
     fn frame_length_header_is_big_endian() {
-        let msg = Message::ListSessions { id: 0 };
+        let msg = Message::ListSessions { id: 0, all: false };
         let buf = encode_message(&msg);
 
         // Read the first 4 bytes as big-endian u32.
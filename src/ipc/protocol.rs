@@ -3,6 +3,8 @@
 //! All messages are MessagePack-encoded maps with at minimum `type` and `id`
 //! fields. See CONTRACT_BROKER.md §Wire Protocol.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// All wire protocol messages.
@@ -30,10 +32,37 @@ pub enum Message {
         session: String,
         pid: u32,
         pattern: String,
+        /// Optional human-readable session name, unique per broker.
+        /// Accepted anywhere a session ID is accepted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// Optional session role (planner, implementer, reviewer …).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        /// Arbitrary key/value labels (`wrap --label k=v`).
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        labels: BTreeMap<String, String>,
+        /// Wrapped command line (argv).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        command: Vec<String>,
+        /// Unix epoch millis when the child was spawned. Defaults to 0
+        /// when absent; the broker falls back to receipt time.
+        #[serde(default)]
+        started_at: u64,
     },
 
     #[serde(rename = "deregister")]
-    Deregister { id: u32, session: String },
+    Deregister {
+        id: u32,
+        session: String,
+        /// Child exit code (128 + N when killed by signal N).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        /// Human-readable reason the session ended, e.g.
+        /// `"exited with code 1"` or `"killed by SIGSEGV"`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
 
     // -- Turn storage --
     #[serde(rename = "turn_completed")]
@@ -48,14 +77,76 @@ pub enum Message {
         /// to receipt time.
         #[serde(default)]
         timestamp: u64,
+        /// Wrapper-assigned per-session sequence number. When present
+        /// the broker uses it for the turn ID, so history replayed after
+        /// a reconnect keeps its IDs. Absent: the broker assigns one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        /// Timing and environment of the turn; empty from older wrappers.
+        #[serde(default, skip_serializing_if = "TurnContext::is_empty")]
+        context: TurnContext,
+        /// Sent as part of the history replay after (re)registration.
+        /// Replayed turns are stored but fire no routes.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        replayed: bool,
+    },
+
+    /// User input was submitted to the child (Enter or an inject).
+    #[serde(rename = "input_activity")]
+    InputActivity {
+        id: u32,
+        session: String,
+        /// Unix epoch millis of the input.
+        timestamp: u64,
+    },
+
+    /// The session's agent state changed (turn detector transition).
+    #[serde(rename = "session_state")]
+    SessionState {
+        id: u32,
+        session: String,
+        state: AgentState,
+    },
+
+    /// The child set its terminal title (OSC 0/2). Empty clears it.
+    #[serde(rename = "session_title")]
+    SessionTitle {
+        id: u32,
+        session: String,
+        title: String,
     },
 
     // -- Capture / Paste --
     #[serde(rename = "capture")]
-    Capture { id: u32, session: String },
+    Capture {
+        id: u32,
+        session: String,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
 
     #[serde(rename = "paste")]
-    Paste { id: u32, session: String },
+    Paste {
+        id: u32,
+        /// Target session; may be empty when `sessions` or `role` name
+        /// the targets.
+        #[serde(default)]
+        session: String,
+        /// More target sessions. With `sessions` or `role`, the response
+        /// reports a result per target in `results`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        sessions: Vec<String>,
+        /// Also target every live session with this role.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+        /// Named template to render the content through.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
 
     // -- Unsolicited commands (broker → wrapper) --
     #[serde(rename = "inject")]
@@ -65,9 +156,82 @@ pub enum Message {
         content: Vec<u8>,
     },
 
+    // -- Output tap --
+    /// Subscribe to a session's live PTY output. The broker then sends
+    /// the subscriber `output` messages (id 0) until `output_end`.
+    #[serde(rename = "subscribe")]
+    Subscribe { id: u32, session: String },
+
+    /// Broker → wrapper: start (`enabled`) or stop sending `output`.
+    /// Sent when the first subscriber arrives and the last one leaves.
+    #[serde(rename = "output_tap")]
+    OutputTap {
+        id: u32,
+        session: String,
+        enabled: bool,
+    },
+
+    /// A chunk of raw PTY output: wrapper → broker while the tap is
+    /// enabled, then broker → each subscriber with id 0.
+    #[serde(rename = "output")]
+    Output {
+        id: u32,
+        session: String,
+        #[serde(with = "serde_bytes")]
+        content: Vec<u8>,
+    },
+
+    /// Broker → subscriber: the session ended; no more output follows.
+    #[serde(rename = "output_end")]
+    OutputEnd { id: u32, session: String },
+
+    // -- Session control (client → broker, forwarded to the wrapper
+    //    with id 0) --
+    /// Send a signal to the session's child process group.
+    #[serde(rename = "signal")]
+    Signal {
+        id: u32,
+        session: String,
+        /// Signal name, e.g. `"SIGINT"`.
+        signal: String,
+    },
+
+    /// Terminate the session gracefully: SIGTERM, then SIGKILL if the
+    /// child has not exited after a grace period.
+    #[serde(rename = "kill")]
+    Kill { id: u32, session: String },
+
+    /// Set the child's window size (headless sessions only).
+    #[serde(rename = "resize")]
+    Resize {
+        id: u32,
+        session: String,
+        cols: u16,
+        rows: u16,
+    },
+
+    /// Start recording the session to an asciicast v2 file at `path`
+    /// (absolute, on the wrapper's host), replacing any recording in
+    /// progress.
+    #[serde(rename = "record_start")]
+    RecordStart {
+        id: u32,
+        session: String,
+        path: String,
+    },
+
+    /// Stop the session's recording, if any.
+    #[serde(rename = "record_stop")]
+    RecordStop { id: u32, session: String },
+
     // -- Query --
     #[serde(rename = "list_sessions")]
-    ListSessions { id: u32 },
+    ListSessions {
+        id: u32,
+        /// Include ended (tombstoned) sessions.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        all: bool,
+    },
 
     // -- Turn registry (v1) --
     #[serde(rename = "get_turn")]
@@ -82,19 +246,211 @@ pub enum Message {
     },
 
     #[serde(rename = "capture_by_id")]
-    CaptureByID { id: u32, turn_id: String },
+    CaptureByID {
+        id: u32,
+        turn_id: String,
+        /// More turn IDs or references of the same session, captured
+        /// after `turn_id` into one combined payload.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        turn_ids: Vec<String>,
+        /// Placed between combined turns; `{turn_id}` is replaced with
+        /// the ID of the turn that follows.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        separator: Option<String>,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    /// Capture the last lines of a session's scrollback into the relay
+    /// buffer. The broker forwards it to the wrapper with `id` set to a
+    /// query token, which the wrapper echoes in `scrollback_lines`.
+    #[serde(rename = "capture_lines")]
+    CaptureLines {
+        id: u32,
+        session: String,
+        /// Number of lines, counted after `grep` filtering.
+        last: u32,
+        /// Only keep lines matching this regex.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        grep: Option<String>,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    /// Wrapper reply to a forwarded `capture_lines`.
+    #[serde(rename = "scrollback_lines")]
+    ScrollbackLines {
+        id: u32,
+        session: String,
+        /// Query token from the forwarded `capture_lines`.
+        query: u32,
+        /// Selected lines, ANSI-stripped, each terminated by `\n`.
+        #[serde(with = "serde_bytes")]
+        content: Vec<u8>,
+    },
+
+    // -- Snapshots --
+    /// Snapshot sessions (IDs or names; all when empty) and the relay
+    /// buffer. The response carries the encoded snapshot in `content`.
+    #[serde(rename = "snapshot_save")]
+    SnapshotSave {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        sessions: Vec<String>,
+    },
+
+    /// Restore an encoded snapshot as read-only archived sessions. The
+    /// response lists them in `sessions`.
+    #[serde(rename = "snapshot_load")]
+    SnapshotLoad {
+        id: u32,
+        #[serde(with = "serde_bytes")]
+        content: Vec<u8>,
+    },
+
+    /// Inject a source's last `last` turns, oldest first, into
+    /// `session` as one paste. The source is the session `from`, or a
+    /// session in `snapshot` (`from` may be omitted if it holds one).
+    #[serde(rename = "replay")]
+    Replay {
+        id: u32,
+        session: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+        snapshot: Option<Vec<u8>>,
+        last: u32,
+        /// Put a header line naming each turn before its content.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        frame: bool,
+    },
 
     // -- Sink delivery (v1) --
     #[serde(rename = "deliver")]
     Deliver {
         id: u32,
         sink: String,
+        /// More sinks to deliver the same content to.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        sinks: Vec<String>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         session: Option<String>,
+        /// More inject targets.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        sessions: Vec<String>,
+        /// Also inject into every live session with this role.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role: Option<String>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         path: Option<String>,
+        /// Turn reference to deliver directly instead of a register.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        turn_id: Option<String>,
+        /// Separator between the turns of a ranged `turn_id`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        separator: Option<String>,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+        /// Named template to render the content through.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
     },
 
+    // -- Registers --
+    /// List the non-empty relay registers. The response carries them
+    /// in `registers`, the unnamed register first.
+    #[serde(rename = "list_registers")]
+    ListRegisters { id: u32 },
+
+    // -- Relay buffer --
+    /// Read a relay register's content and metadata. The response is
+    /// shaped like a `get_turn` response.
+    #[serde(rename = "relay_show")]
+    RelayShow {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    /// Put arbitrary content into a relay register, with metadata made
+    /// up by the broker (pseudo turn ID `<source>:set`).
+    #[serde(rename = "relay_set")]
+    RelaySet {
+        id: u32,
+        #[serde(with = "serde_bytes")]
+        content: Vec<u8>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+        /// Where the content came from, e.g. a file name (default
+        /// `"external"`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<String>,
+    },
+
+    /// Empty a relay register.
+    #[serde(rename = "relay_clear")]
+    RelayClear {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    /// Put back what a relay register held before its last capture,
+    /// set or clear.
+    #[serde(rename = "relay_undo")]
+    RelayUndo {
+        id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    /// List the relay history, most recent first, in `registers`.
+    #[serde(rename = "relay_history")]
+    RelayHistory { id: u32 },
+
+    /// Gather turns of any sessions into one payload with a labelled
+    /// section per turn. The response is shaped like `relay_show`;
+    /// with `into_relay` the payload is also stored in a register.
+    #[serde(rename = "gather")]
+    Gather {
+        id: u32,
+        /// Turn IDs or references, one section per turn they name.
+        turn_ids: Vec<String>,
+        #[serde(default)]
+        style: SectionStyle,
+        #[serde(default)]
+        into_relay: bool,
+        /// Named register to use instead of the unnamed one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        register: Option<String>,
+    },
+
+    // -- Routes (any role) --
+    /// Add a route that relays a source session's turns into a target
+    /// session. The response carries the new route in `routes`.
+    #[serde(rename = "route_add")]
+    RouteAdd {
+        id: u32,
+        route: RouteSpec,
+        /// Add the route switched off.
+        #[serde(default)]
+        disabled: bool,
+    },
+
+    /// List the routes in `routes`, in the order they were added.
+    #[serde(rename = "route_list")]
+    RouteList { id: u32 },
+
+    #[serde(rename = "route_remove")]
+    RouteRemove { id: u32, route: u32 },
+
+    /// Switch a route on or off. The response carries it in `routes`.
+    #[serde(rename = "route_enable")]
+    RouteEnable { id: u32, route: u32, enabled: bool },
+
     // -- Generic response --
     #[serde(rename = "response")]
     Response {
@@ -122,6 +478,15 @@ pub enum Message {
         // -- ListTurns descriptors (v1) --
         #[serde(default, skip_serializing_if = "Option::is_none")]
         turns: Option<Vec<TurnDescriptor>>,
+        // -- ListRegisters descriptors --
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        registers: Option<Vec<RegisterDescriptor>>,
+        // -- Multi-target paste and deliver results --
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        results: Option<Vec<DeliveryResult>>,
+        // -- Route descriptors --
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        routes: Option<Vec<RouteDescriptor>>,
     },
 }
 
@@ -141,12 +506,108 @@ pub enum Status {
     Error,
 }
 
+/// Agent state of a session, derived from the wrapper's turn detector.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentState {
+    /// No prompt seen yet.
+    #[default]
+    Starting,
+    /// At the prompt, awaiting user input.
+    Idle,
+    /// Input submitted; the agent is producing a response.
+    Busy,
+    /// The session ended; only its retained turns remain (tombstone).
+    Exited,
+    /// Loaded from a snapshot; read-only (CONTRACT_BROKER.md §Snapshots).
+    Archived,
+}
+
+impl AgentState {
+    /// Wire name, as used in `list-sessions` output and CLI filters.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentState::Starting => "starting",
+            AgentState::Idle => "idle",
+            AgentState::Busy => "busy",
+            AgentState::Exited => "exited",
+            AgentState::Archived => "archived",
+        }
+    }
+}
+
+/// How a gathered payload labels and delimits its sections.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionStyle {
+    /// `<turn session="..." turn_id="...">` ... `</turn>`.
+    #[default]
+    Xml,
+    /// A `## <session> (<turn_id>)` header before each section.
+    Markdown,
+}
+
+impl SectionStyle {
+    /// Wire name, as used in CLI options.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SectionStyle::Xml => "xml",
+            SectionStyle::Markdown => "markdown",
+        }
+    }
+}
+
 /// Session descriptor returned in list_sessions responses.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SessionDescriptor {
     pub session: String,
     pub pid: u32,
     pub has_turn: bool,
+    /// Session name from `wrap --name`, if one was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Session role from `wrap --role`, if one was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Session labels from `wrap --label k=v`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Wrapped command line (argv).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// Child's current working directory, read at query time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Prompt pattern preset name or custom regex.
+    #[serde(default)]
+    pub pattern: String,
+    /// Unix epoch millis when the session started.
+    #[serde(default)]
+    pub started_at: u64,
+    /// Number of turns completed since registration (including evicted).
+    #[serde(default)]
+    pub turn_count: u64,
+    /// Unix epoch millis of the latest completed turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_turn_at: Option<u64>,
+    /// Unix epoch millis of the latest submitted input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_input_at: Option<u64>,
+    /// Latest agent state reported by the wrapper.
+    #[serde(default)]
+    pub state: AgentState,
+    /// Current terminal title set by the child (OSC 0/2).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Unix epoch millis when the session ended (tombstones only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
+    /// Child exit code reported in `deregister`, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why the session ended (tombstones only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_reason: Option<String>,
 }
 
 /// Turn descriptor returned in list_turns responses (metadata only, no content).
@@ -157,6 +618,138 @@ pub struct TurnDescriptor {
     pub byte_length: u32,
     pub interrupted: bool,
     pub truncated: bool,
+    #[serde(default, skip_serializing_if = "TurnContext::is_empty")]
+    pub context: TurnContext,
+}
+
+/// Relay register descriptor returned in list_registers responses.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RegisterDescriptor {
+    /// Register name; `None` for the unnamed register.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    /// Turn ID (or pseudo ID) of the content held.
+    pub turn_id: String,
+    /// Content size in bytes.
+    pub size: u32,
+    /// Unix epoch millis of the captured turn.
+    pub timestamp: u64,
+}
+
+/// Outcome of one target of a multi-target paste or deliver.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeliveryResult {
+    /// `inject:<session>`, `clipboard` or `file:<path>`.
+    pub target: String,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What makes a route fire.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteTrigger {
+    /// Each turn the source completes.
+    #[default]
+    Turn,
+}
+
+impl RouteTrigger {
+    /// Wire name, as used in CLI options.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouteTrigger::Turn => "turn",
+        }
+    }
+}
+
+/// A route as requested by `route_add`: where turns come from and go,
+/// which turns qualify, and how they are delivered.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteSpec {
+    /// Source session ID or name.
+    pub source: String,
+    /// Target session ID or name.
+    pub target: String,
+    #[serde(default)]
+    pub on: RouteTrigger,
+    /// Text injected instead of the bare turn, rendered as a delivery
+    /// template (`{{content}}`, `{{session}}`, ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Only relay turns whose text (ANSI stripped) matches this regex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Only relay turns of at least this many bytes.
+    #[serde(default)]
+    pub min_size: u32,
+    /// Do not relay interrupted turns.
+    #[serde(default)]
+    pub skip_interrupted: bool,
+    /// Hold each delivery until the target is idle.
+    #[serde(default)]
+    pub wait_idle: bool,
+}
+
+/// Route descriptor returned in route responses.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteDescriptor {
+    /// Route number, used by `route_remove` and `route_enable`.
+    pub route: u32,
+    pub spec: RouteSpec,
+    pub enabled: bool,
+    /// Number of turns relayed so far.
+    pub fired: u64,
+}
+
+/// When and where a turn was produced, reported by the wrapper.
+///
+/// Every field is optional: older wrappers send none, and the wrapper
+/// omits what it cannot determine (no input before the turn, cwd not
+/// readable, not in a git repository).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TurnContext {
+    /// Unix epoch millis when the input that started the turn was
+    /// submitted (Enter or inject).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submitted_at: Option<u64>,
+    /// Millis from input submission to turn completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Number of lines in the turn content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<u32>,
+    /// The child's working directory when the turn completed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Git state of `cwd`, if it is inside a repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitContext>,
+}
+
+impl TurnContext {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Git repository state for a turn's working directory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GitContext {
+    /// Repository (worktree) root directory.
+    pub root: String,
+    /// Checked-out branch; absent when HEAD is detached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Full HEAD commit SHA; absent before the first commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    /// Whether tracked files differ from the index (unstaged changes;
+    /// staged ones are not seen); absent when not checked (very large
+    /// index, or too slow to read).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
 }
 
 /// Protocol version for v0.
@@ -225,6 +818,27 @@ mod tests {
             session: "abc-123".into(),
             pid: 4567,
             pattern: "generic".into(),
+            name: None,
+            role: None,
+            labels: Default::default(),
+            command: Vec::new(),
+            started_at: 0,
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn register_with_name_round_trip() {
+        let msg = Message::Register {
+            id: 1,
+            session: "abc-123".into(),
+            pid: 4567,
+            pattern: "claude".into(),
+            name: Some("planner".into()),
+            role: None,
+            labels: Default::default(),
+            command: Vec::new(),
+            started_at: 0,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -234,10 +848,38 @@ mod tests {
         let msg = Message::Deregister {
             id: 2,
             session: "abc-123".into(),
+            exit_code: Some(139),
+            reason: Some("killed by SIGSEGV".into()),
         };
         assert_eq!(round_trip(&msg), msg);
     }
 
+    #[test]
+    fn deregister_v0_compat_no_exit_status() {
+        #[derive(Serialize)]
+        struct V0Deregister {
+            #[serde(rename = "type")]
+            msg_type: &'static str,
+            id: u32,
+            session: String,
+        }
+        let v0 = V0Deregister {
+            msg_type: "deregister",
+            id: 2,
+            session: "abc-123".into(),
+        };
+        let bytes = rmp_serde::to_vec_named(&v0).unwrap();
+        let decoded: Message = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(matches!(
+            decoded,
+            Message::Deregister {
+                exit_code: None,
+                reason: None,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn turn_completed_v0_compat_no_timestamp() {
         // v0 wrappers omit timestamp — field must default to 0.
@@ -267,12 +909,18 @@ mod tests {
                 content,
                 interrupted,
                 timestamp,
+                seq,
+                context,
+                replayed,
             } => {
                 assert_eq!(id, 5);
                 assert_eq!(session, "s1");
                 assert_eq!(content, b"hello");
                 assert!(!interrupted);
                 assert_eq!(timestamp, 0, "missing timestamp must default to 0");
+                assert_eq!(seq, None, "missing seq must default to None");
+                assert!(context.is_empty(), "missing context must default to empty");
+                assert!(!replayed, "missing replayed must default to false");
             }
             _ => panic!("expected TurnCompleted"),
         }
@@ -286,6 +934,9 @@ mod tests {
             content: b"hello world\nline 2\n".to_vec(),
             interrupted: false,
             timestamp: 1000,
+            seq: None,
+            context: Default::default(),
+            replayed: false,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -300,6 +951,9 @@ mod tests {
             content: binary_content.clone(),
             interrupted: true,
             timestamp: 1000,
+            seq: None,
+            context: Default::default(),
+            replayed: false,
         };
         let decoded = round_trip(&msg);
         match decoded {
@@ -315,6 +969,7 @@ mod tests {
         let msg = Message::Capture {
             id: 5,
             session: "abc-123".into(),
+            register: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -324,6 +979,10 @@ mod tests {
         let msg = Message::Paste {
             id: 6,
             session: "abc-123".into(),
+            sessions: Vec::new(),
+            role: None,
+            register: None,
+            template: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -339,7 +998,7 @@ mod tests {
 
     #[test]
     fn list_sessions_round_trip() {
-        let msg = Message::ListSessions { id: 7 };
+        let msg = Message::ListSessions { id: 7, all: false };
         assert_eq!(round_trip(&msg), msg);
     }
 
@@ -358,6 +1017,9 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -377,6 +1039,9 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -393,11 +1058,13 @@ mod tests {
                     session: "s1".into(),
                     pid: 100,
                     has_turn: true,
+                    ..Default::default()
                 },
                 SessionDescriptor {
                     session: "s2".into(),
                     pid: 200,
                     has_turn: false,
+                    ..Default::default()
                 },
             ]),
             turn_id: None,
@@ -407,6 +1074,9 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -426,6 +1096,9 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -445,6 +1118,35 @@ mod tests {
             interrupted: None,
             truncated: None,
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
+        };
+        assert_eq!(round_trip(&msg), msg);
+    }
+
+    #[test]
+    fn turn_completed_context_round_trip() {
+        let msg = Message::TurnCompleted {
+            id: 3,
+            session: "s1".into(),
+            content: b"done".to_vec(),
+            interrupted: false,
+            timestamp: 5000,
+            seq: Some(2),
+            context: TurnContext {
+                submitted_at: Some(1000),
+                duration_ms: Some(4000),
+                line_count: Some(1),
+                cwd: Some("/repo/src".into()),
+                git: Some(GitContext {
+                    root: "/repo".into(),
+                    branch: Some("main".into()),
+                    head: Some("0123456789abcdef0123456789abcdef01234567".into()),
+                    dirty: Some(false),
+                }),
+            },
+            replayed: false,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -457,6 +1159,7 @@ mod tests {
             byte_length: 256,
             interrupted: false,
             truncated: false,
+            context: Default::default(),
         };
         let encoded = rmp_serde::to_vec_named(&td).unwrap();
         let decoded: TurnDescriptor = rmp_serde::from_slice(&encoded).unwrap();
@@ -516,6 +1219,9 @@ mod tests {
         let msg = Message::CaptureByID {
             id: 13,
             turn_id: "s1:2".into(),
+            turn_ids: Vec::new(),
+            separator: None,
+            register: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -525,8 +1231,15 @@ mod tests {
         let msg = Message::Deliver {
             id: 20,
             sink: "inject".into(),
+            sinks: Vec::new(),
             session: Some("s1".into()),
+            sessions: Vec::new(),
+            role: None,
             path: None,
+            turn_id: None,
+            separator: None,
+            register: None,
+            template: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -536,8 +1249,15 @@ mod tests {
         let msg = Message::Deliver {
             id: 21,
             sink: "file".into(),
+            sinks: Vec::new(),
             session: None,
+            sessions: Vec::new(),
+            role: None,
             path: Some("/tmp/turn.txt".into()),
+            turn_id: None,
+            separator: None,
+            register: None,
+            template: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -557,6 +1277,9 @@ mod tests {
             interrupted: Some(false),
             truncated: Some(false),
             turns: None,
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -582,6 +1305,7 @@ mod tests {
                     byte_length: 100,
                     interrupted: false,
                     truncated: false,
+                    context: Default::default(),
                 },
                 TurnDescriptor {
                     turn_id: "s1:1".into(),
@@ -589,8 +1313,12 @@ mod tests {
                     byte_length: 50,
                     interrupted: true,
                     truncated: false,
+                    context: Default::default(),
                 },
             ]),
+            registers: None,
+            results: None,
+            routes: None,
         };
         assert_eq!(round_trip(&msg), msg);
     }
@@ -20,17 +20,53 @@ async fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Command::Wrap { pattern, command } => match pty::run_session(pattern, command).await {
-            Ok(code) => std::process::exit(code),
-            Err(e) => {
-                tracing::error!(error = %e, "wrap failed");
-                eprintln!("clippyctl wrap: {e}");
-                std::process::exit(1);
+        Command::Wrap {
+            pattern,
+            name,
+            role,
+            labels,
+            export_env,
+            headless,
+            size: (cols, rows),
+            output_log,
+            history,
+            broker_status,
+            scrollback,
+            record,
+            command,
+        } => {
+            let options = pty::WrapOptions {
+                pattern,
+                name,
+                role,
+                labels: labels.into_iter().collect(),
+                export_env,
+                headless: headless.then_some(pty::HeadlessOptions {
+                    cols,
+                    rows,
+                    output_log,
+                }),
+                history_depth: history as usize,
+                broker_status,
+                scrollback_lines: scrollback as usize,
+                record,
+            };
+            match pty::run_session(options, command).await {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    tracing::error!(error = %e, "wrap failed");
+                    eprintln!("clippyctl wrap: {e}");
+                    std::process::exit(1);
+                }
             }
-        },
+        }
         Command::Broker {
             ring_depth,
             max_turn_size,
+            tombstone_ttl,
+            tombstone_limit,
+            templates,
+            template_files,
         } => {
             let depth = usize::try_from(ring_depth).unwrap_or_else(|_| {
                 eprintln!("clippyctl broker: --ring-depth value too large for this platform");
@@ -40,6 +76,26 @@ async fn main() {
                 depth,
                 max_turn_bytes: max_turn_size,
             };
+            let tombstones = broker::state::TombstoneConfig {
+                ttl_ms: tombstone_ttl.saturating_mul(1000),
+                max: tombstone_limit,
+            };
+            let mut template_config = broker::state::TemplateConfig::default();
+            template_config.templates.extend(templates);
+            for (name, path) in template_files {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {e}", path.display()))
+                    .and_then(|text| {
+                        broker::template::validate(&text)
+                            .map(|()| text)
+                            .map_err(|e| format!("template {name:?}: {e}"))
+                    })
+                    .unwrap_or_else(|e| {
+                        eprintln!("clippyctl broker: {e}");
+                        std::process::exit(1);
+                    });
+                template_config.templates.insert(name, text);
+            }
             // Construct clipboard writer closure from X11ClipboardProvider.
             let clipboard = resolver::x11::clipboard::X11ClipboardProvider::new();
             let clipboard_writer: broker::ClipboardWriterFn = Box::new(move |content| {
@@ -49,7 +105,8 @@ async fn main() {
                     .map_err(|e| format!("clipboard_failed: {e}"))
             });
 
-            if let Err(e) = broker::run(config, clipboard_writer).await {
+            if let Err(e) = broker::run(config, tombstones, template_config, clipboard_writer).await
+            {
                 tracing::error!(error = %e, "broker failed");
                 eprintln!("clippyctl broker: {e}");
                 std::process::exit(1);
@@ -59,6 +116,10 @@ async fn main() {
             capture_key,
             paste_key,
             clipboard_key,
+            undo_key,
+            register,
+            paste_template,
+            clipboard_template,
         } => {
             // Construct X11 resolver adapters.
             let shared = match resolver::x11::X11Shared::connect() {
@@ -76,6 +137,12 @@ async fn main() {
                 capture_key,
                 paste_key,
                 clipboard_key,
+                undo_key,
+                hotkey::RelayOptions {
+                    register,
+                    paste_template,
+                    clipboard_template,
+                },
                 &session_resolver,
                 &mut hotkey_provider,
             )
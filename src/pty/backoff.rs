@@ -0,0 +1,62 @@
+//! Exponential backoff for broker reconnect attempts.
+
+use std::time::Duration;
+
+/// Delay before the first reconnect attempt.
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on the delay between attempts.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Doubling backoff from [`INITIAL_DELAY`] up to [`MAX_DELAY`].
+#[derive(Debug)]
+pub struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self {
+            next: INITIAL_DELAY,
+        }
+    }
+
+    /// Return the delay before the next attempt and double it for the
+    /// one after, capped at [`MAX_DELAY`].
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_DELAY);
+        delay
+    }
+
+    /// Start over from the initial delay (after a successful connect).
+    pub fn reset(&mut self) {
+        self.next = INITIAL_DELAY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_up_to_cap() {
+        let mut b = Backoff::new();
+        assert_eq!(b.next_delay(), Duration::from_millis(500));
+        assert_eq!(b.next_delay(), Duration::from_secs(1));
+        assert_eq!(b.next_delay(), Duration::from_secs(2));
+        for _ in 0..10 {
+            b.next_delay();
+        }
+        assert_eq!(b.next_delay(), MAX_DELAY);
+    }
+
+    #[test]
+    fn reset_restarts_sequence() {
+        let mut b = Backoff::new();
+        b.next_delay();
+        b.next_delay();
+        b.reset();
+        assert_eq!(b.next_delay(), INITIAL_DELAY);
+    }
+}
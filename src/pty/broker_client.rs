@@ -5,39 +5,85 @@
 //! is unreachable. See CONTRACT_PTY.md §104–123, CONTRACT_BROKER.md
 //! §Wire Protocol.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use futures::stream::SplitSink;
 use futures::stream::SplitStream;
 use futures::{SinkExt, StreamExt};
 use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio_util::codec::Framed;
 
 use crate::ipc::codec::LengthPrefixedCodec;
-use crate::ipc::protocol::{Message, PROTOCOL_VERSION, Role, Status};
-use crate::turn::Turn;
+use crate::ipc::protocol::{AgentState, Message, PROTOCOL_VERSION, Role, Status};
 
 use super::PtyError;
+use super::child::ChildExit;
+use super::history::{HistoryEntry, TurnHistory};
+
+/// Session details sent to the broker in `Register`.
+#[derive(Debug, Clone)]
+pub struct Registration {
+    pub session_id: String,
+    pub pid: u32,
+    pub pattern: String,
+    /// Optional session name (`wrap --name`).
+    pub name: Option<String>,
+    /// Optional session role (`wrap --role`).
+    pub role: Option<String>,
+    /// Session labels (`wrap --label k=v`).
+    pub labels: BTreeMap<String, String>,
+    /// Wrapped command line.
+    pub command: Vec<String>,
+    /// Unix epoch millis when the child was spawned.
+    pub started_at: u64,
+}
+
+/// Write half of the broker connection, owned by the writer task.
+type BrokerSink = SplitSink<Framed<UnixStream, LengthPrefixedCodec>, Message>;
+
+/// Requests queued for the writer task. Senders wait for room, as they
+/// would wait on the socket, bounded by the caller's timeout.
+const OUTBOX_CAPACITY: usize = 64;
+
+/// Output tap chunks queued for the writer task. A chunk that does not
+/// fit is dropped rather than waited for.
+const OUTPUT_CAPACITY: usize = 64;
 
 /// Broker client for the PTY wrapper.
 ///
 /// Splits the framed connection into separate sink/stream halves so
-/// that `stream.next()` can be polled in the `select!` loop while
-/// `sink.send()` is called from the output handler.
+/// that `stream.next()` can be polled in the `select!` loop. The sink
+/// is owned by a writer task fed through two bounded queues, so tapped
+/// output never waits on the socket in the I/O loop.
 pub struct BrokerClient {
-    sink: SplitSink<Framed<UnixStream, LengthPrefixedCodec>, Message>,
+    outbox: mpsc::Sender<Message>,
+    output: mpsc::Sender<Message>,
+    writer: Option<JoinHandle<()>>,
     stream: SplitStream<Framed<UnixStream, LengthPrefixedCodec>>,
     next_id: u32,
     session_id: String,
 }
 
+impl Drop for BrokerClient {
+    fn drop(&mut self) {
+        // A writer stuck on a broker that stopped reading must not
+        // outlive the connection.
+        if let Some(writer) = self.writer.take() {
+            writer.abort();
+        }
+    }
+}
+
 impl BrokerClient {
     /// Connect to the broker, perform handshake, and register the session.
     ///
     /// Returns `Err` if the broker is unreachable, handshake fails, or
     /// registration fails. The caller should log the error and continue
     /// in standalone mode.
-    pub async fn connect(session_id: &str, pid: u32, pattern: &str) -> Result<Self, PtyError> {
+    pub async fn connect(registration: &Registration) -> Result<Self, PtyError> {
         // Resolve socket path.
         let socket_path = resolve_socket_path()?;
 
@@ -82,9 +128,14 @@ impl BrokerClient {
         framed
             .send(Message::Register {
                 id: 1,
-                session: session_id.to_string(),
-                pid,
-                pattern: pattern.to_string(),
+                session: registration.session_id.clone(),
+                pid: registration.pid,
+                pattern: registration.pattern.clone(),
+                name: registration.name.clone(),
+                role: registration.role.clone(),
+                labels: registration.labels.clone(),
+                command: registration.command.clone(),
+                started_at: registration.started_at,
             })
             .await
             .map_err(|e| PtyError::Broker(format!("send register: {e}")))?;
@@ -93,6 +144,12 @@ impl BrokerClient {
             Some(Ok(Message::Response {
                 status: Status::Ok, ..
             })) => {}
+            Some(Ok(Message::Response { error, .. }))
+                if error.as_deref() == Some("duplicate_name") =>
+            {
+                let name = registration.name.clone().unwrap_or_default();
+                return Err(PtyError::NameInUse(name));
+            }
             Some(Ok(Message::Response { error, .. })) => {
                 return Err(PtyError::Broker(format!(
                     "register failed: {}",
@@ -107,60 +164,177 @@ impl BrokerClient {
         }
 
         let (sink, stream) = framed.split();
+        let (outbox, outbox_rx) = mpsc::channel(OUTBOX_CAPACITY);
+        let (output, output_rx) = mpsc::channel(OUTPUT_CAPACITY);
+        let writer = tokio::spawn(write_messages(sink, outbox_rx, output_rx));
 
         Ok(Self {
-            sink,
+            outbox,
+            output,
+            writer: Some(writer),
             stream,
             next_id: 2, // 0=Hello, 1=Register
-            session_id: session_id.to_string(),
+            session_id: registration.session_id.clone(),
         })
     }
 
     /// Send a completed turn to the broker (fire-and-forget).
     ///
-    /// Writes the `TurnCompleted` message to the sink and returns
-    /// immediately without waiting for the response ack. The ack
+    /// Queues the `TurnCompleted` message for the writer task and
+    /// returns without waiting for the response ack. The ack
     /// arrives on the stream and is handled in the select! broker
     /// arm (as an ignored `Response`).
     ///
     /// This avoids blocking the I/O loop (CONTRACT_PTY.md §46, §49)
     /// and prevents inject messages from being dropped during the
     /// ack wait.
-    pub async fn send_turn(&mut self, turn: &Turn) -> Result<(), PtyError> {
+    ///
+    /// The wrapper-assigned `seq` is sent so the broker's turn ID
+    /// matches the wrapper's history; `replayed` marks a history
+    /// replay, which fires no routes.
+    pub async fn send_turn(
+        &mut self,
+        entry: &HistoryEntry,
+        replayed: bool,
+    ) -> Result<(), PtyError> {
         let id = self.next_id;
         self.next_id += 1;
 
-        self.sink
+        self.outbox
             .send(Message::TurnCompleted {
                 id,
                 session: self.session_id.clone(),
-                content: turn.content.clone(),
-                interrupted: turn.interrupted,
-                timestamp: turn.timestamp,
+                content: entry.turn.content.clone(),
+                interrupted: entry.turn.interrupted,
+                timestamp: entry.turn.timestamp,
+                seq: Some(entry.seq),
+                context: entry.context.clone(),
+                replayed,
             })
             .await
             .map_err(|e| PtyError::Broker(format!("send turn: {e}")))
     }
 
-    /// Send deregister and close the connection.
+    /// Replay the wrapper's turn history, oldest first, after
+    /// (re)registration so the broker rebuilds the same turn IDs.
+    pub async fn replay(&mut self, history: &TurnHistory) -> Result<(), PtyError> {
+        for entry in history.iter() {
+            self.send_turn(entry, true).await?;
+        }
+        Ok(())
+    }
+
+    /// Report submitted input to the broker (fire-and-forget).
+    ///
+    /// Like [`send_turn`](Self::send_turn), the ack is ignored in the
+    /// select! broker arm.
+    pub async fn send_input_activity(&mut self, timestamp: u64) -> Result<(), PtyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.outbox
+            .send(Message::InputActivity {
+                id,
+                session: self.session_id.clone(),
+                timestamp,
+            })
+            .await
+            .map_err(|e| PtyError::Broker(format!("send input activity: {e}")))
+    }
+
+    /// Report an agent state transition to the broker (fire-and-forget).
+    pub async fn send_state(&mut self, state: AgentState) -> Result<(), PtyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.outbox
+            .send(Message::SessionState {
+                id,
+                session: self.session_id.clone(),
+                state,
+            })
+            .await
+            .map_err(|e| PtyError::Broker(format!("send session state: {e}")))
+    }
+
+    /// Report a terminal title change to the broker (fire-and-forget).
+    pub async fn send_title(&mut self, title: &str) -> Result<(), PtyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.outbox
+            .send(Message::SessionTitle {
+                id,
+                session: self.session_id.clone(),
+                title: title.to_string(),
+            })
+            .await
+            .map_err(|e| PtyError::Broker(format!("send session title: {e}")))
+    }
+
+    /// Answer a broker `capture_lines` query (fire-and-forget).
+    pub async fn send_scrollback_lines(
+        &mut self,
+        query: u32,
+        content: Vec<u8>,
+    ) -> Result<(), PtyError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.outbox
+            .send(Message::ScrollbackLines {
+                id,
+                session: self.session_id.clone(),
+                query,
+                content,
+            })
+            .await
+            .map_err(|e| PtyError::Broker(format!("send scrollback lines: {e}")))
+    }
+
+    /// Queue a chunk of PTY output for the broker's tap subscribers.
+    ///
+    /// `output` is a notification (`id: 0`) the broker does not answer.
+    /// Returns `false` if the chunk was dropped because the queue is
+    /// full or the writer is gone: tapped output is best-effort.
+    pub fn send_output(&self, content: Vec<u8>) -> bool {
+        self.output
+            .try_send(Message::Output {
+                id: 0,
+                session: self.session_id.clone(),
+                content,
+            })
+            .is_ok()
+    }
+
+    /// Send deregister with the child's exit status and close the
+    /// connection once everything queued before it is written.
     ///
     /// Best-effort — errors are logged but not propagated since we're
     /// shutting down anyway.
-    pub async fn deregister(&mut self) {
+    pub async fn deregister(mut self, exit: ChildExit) {
         let id = self.next_id;
         self.next_id += 1;
 
         if let Err(e) = self
-            .sink
+            .outbox
             .send(Message::Deregister {
                 id,
                 session: self.session_id.clone(),
+                exit_code: Some(exit.code()),
+                reason: Some(exit.reason()),
             })
             .await
         {
             tracing::debug!(error = %e, "deregister send failed");
         }
-        // Don't wait for response — we're exiting.
+        // Closing the queues lets the writer finish and exit. Don't
+        // wait for the response — we're exiting.
+        let writer = self.writer.take();
+        drop(self);
+        if let Some(writer) = writer {
+            let _ = writer.await;
+        }
     }
 
     /// Get a mutable reference to the stream half for `select!` polling.
@@ -172,8 +346,29 @@ impl BrokerClient {
     }
 }
 
+/// Write queued messages to the broker until both queues close or a
+/// write fails. Requests go first; tapped output fills the gaps.
+async fn write_messages(
+    mut sink: BrokerSink,
+    mut outbox: mpsc::Receiver<Message>,
+    mut output: mpsc::Receiver<Message>,
+) {
+    loop {
+        let message = tokio::select! {
+            biased;
+            Some(message) = outbox.recv() => message,
+            Some(message) = output.recv() => message,
+            else => break,
+        };
+        if let Err(e) = sink.send(message).await {
+            tracing::debug!(error = %e, "broker write failed");
+            break;
+        }
+    }
+}
+
 /// Resolve the broker socket path from `$XDG_RUNTIME_DIR`.
-fn resolve_socket_path() -> Result<PathBuf, PtyError> {
+pub fn resolve_socket_path() -> Result<PathBuf, PtyError> {
     let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
         .map_err(|_| PtyError::Broker("$XDG_RUNTIME_DIR not set".into()))?;
     Ok(PathBuf::from(runtime_dir)
@@ -9,8 +9,9 @@ use std::os::fd::{AsRawFd, OwnedFd};
 use nix::libc;
 
 use nix::pty::{Winsize, openpty};
+use nix::sys::signal::Signal;
 use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
-use nix::unistd::{ForkResult, Pid, execvp, fork, setsid};
+use nix::unistd::{ForkResult, Pid, execvp, execvpe, fork, setsid};
 
 use super::PtyError;
 
@@ -29,11 +30,19 @@ pub struct ChildProcess {
 /// controlling terminal, and execs the command. The master fd is
 /// returned in non-blocking mode for async I/O.
 ///
+/// `extra_env` is added to the inherited environment (overriding any
+/// existing values). When empty, the environment is inherited
+/// unmodified per CONTRACT_PTY.md §Environment.
+///
 /// # Safety
 ///
 /// Uses `fork()` internally. Only async-signal-safe operations are
 /// performed between fork and exec/exit in the child branch.
-pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess, PtyError> {
+pub fn spawn_child(
+    command: &[String],
+    winsize: &Winsize,
+    extra_env: &[(String, String)],
+) -> Result<ChildProcess, PtyError> {
     if command.is_empty() {
         return Err(PtyError::Exec("empty command".into()));
     }
@@ -49,6 +58,14 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    // Build the full environment up front — setenv is not
+    // async-signal-safe, so it cannot run between fork and exec.
+    let c_env = if extra_env.is_empty() {
+        None
+    } else {
+        Some(build_env(extra_env)?)
+    };
+
     // Allocate PTY pair with initial dimensions matching user's terminal.
     let pty = openpty(Some(winsize), None).map_err(PtyError::PtyAlloc)?;
     let master = pty.master;
@@ -110,7 +127,14 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
             }
 
             // Exec the command — replaces process image.
-            let _ = execvp(&c_args[0], &c_args);
+            match c_env {
+                Some(ref env) => {
+                    let _ = execvpe(&c_args[0], &c_args, env);
+                }
+                None => {
+                    let _ = execvp(&c_args[0], &c_args);
+                }
+            }
 
             // If exec failed, exit with 127 (command not found convention).
             unsafe { libc::_exit(127) };
@@ -118,14 +142,32 @@ pub fn spawn_child(command: &[String], winsize: &Winsize) -> Result<ChildProcess
     }
 }
 
-/// Wait for the child process to exit and return its exit code.
-///
-/// For signal-terminated children, returns 128 + signal number
-/// per standard convention.
-///
-/// This should be called after the I/O loop exits (PTY EOF or
-/// graceful shutdown). Uses blocking `waitpid` — the child has
-/// already exited or is about to.
+/// Build a `KEY=VALUE` environment from the current process
+/// environment with `extra` entries added or overridden.
+fn build_env(extra: &[(String, String)]) -> Result<Vec<CString>, PtyError> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut env = Vec::new();
+    for (key, value) in std::env::vars_os() {
+        if extra.iter().any(|(k, _)| key.as_bytes() == k.as_bytes()) {
+            continue;
+        }
+        let mut entry = key.as_bytes().to_vec();
+        entry.push(b'=');
+        entry.extend_from_slice(value.as_bytes());
+        // Inherited entries cannot contain NUL; skip defensively.
+        if let Ok(c) = CString::new(entry) {
+            env.push(c);
+        }
+    }
+    for (key, value) in extra {
+        let c = CString::new(format!("{key}={value}"))
+            .map_err(|_| PtyError::Exec(format!("environment contains null byte: {key:?}")))?;
+        env.push(c);
+    }
+    Ok(env)
+}
+
 /// Default window size for tests.
 #[cfg(test)]
 fn test_winsize() -> Winsize {
@@ -137,11 +179,59 @@ fn test_winsize() -> Winsize {
     }
 }
 
-pub fn wait_for_exit(pid: Pid) -> Result<i32, PtyError> {
+/// How the child process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildExit {
+    Exited(i32),
+    Signaled(Signal),
+}
+
+impl ChildExit {
+    /// Exit code: the child's status, or 128 + signal number for
+    /// signal-terminated children per standard convention.
+    pub fn code(self) -> i32 {
+        match self {
+            ChildExit::Exited(code) => code,
+            ChildExit::Signaled(sig) => 128 + sig as i32,
+        }
+    }
+
+    /// Human-readable reason, reported to the broker in `deregister`.
+    pub fn reason(self) -> String {
+        match self {
+            ChildExit::Exited(code) => format!("exited with code {code}"),
+            ChildExit::Signaled(sig) => format!("killed by {}", sig.as_str()),
+        }
+    }
+}
+
+/// Wait for the child process to exit and return how it ended.
+///
+/// This should be called after the I/O loop exits (PTY EOF or
+/// graceful shutdown). Uses blocking `waitpid` — the child has
+/// already exited or is about to.
+pub fn wait_for_exit(pid: Pid) -> Result<ChildExit, PtyError> {
+    wait_for_exit_until(pid, None).map(|exit| exit.expect("no deadline"))
+}
+
+/// Like [`wait_for_exit`], but give up after `timeout`: `None` if the
+/// child is still running then.
+pub fn wait_for_exit_within(
+    pid: Pid,
+    timeout: std::time::Duration,
+) -> Result<Option<ChildExit>, PtyError> {
+    wait_for_exit_until(pid, Some(std::time::Instant::now() + timeout))
+}
+
+fn wait_for_exit_until(
+    pid: Pid,
+    deadline: Option<std::time::Instant>,
+) -> Result<Option<ChildExit>, PtyError> {
     loop {
         match waitpid(pid, Some(WaitPidFlag::WNOHANG)).map_err(PtyError::Signal)? {
-            WaitStatus::Exited(_, code) => return Ok(code),
-            WaitStatus::Signaled(_, sig, _) => return Ok(128 + sig as i32),
+            WaitStatus::Exited(_, code) => return Ok(Some(ChildExit::Exited(code))),
+            WaitStatus::Signaled(_, sig, _) => return Ok(Some(ChildExit::Signaled(sig))),
+            _ if deadline.is_some_and(|at| std::time::Instant::now() >= at) => return Ok(None),
             WaitStatus::StillAlive => {
                 // Child still running — brief sleep then retry.
                 // This path is rare (PTY EOF usually means child exited).
@@ -162,7 +252,7 @@ mod tests {
     #[test]
     fn empty_command_rejected() {
         let ws = test_winsize();
-        let err = spawn_child(&[], &ws).unwrap_err();
+        let err = spawn_child(&[], &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("empty command")),
             "expected Exec error, got: {err}"
@@ -173,7 +263,7 @@ mod tests {
     fn nul_byte_in_argument_rejected() {
         let ws = test_winsize();
         let cmd = vec!["echo".into(), "hello\0world".into()];
-        let err = spawn_child(&cmd, &ws).unwrap_err();
+        let err = spawn_child(&cmd, &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("null byte")),
             "expected Exec error about null byte, got: {err}"
@@ -184,7 +274,7 @@ mod tests {
     fn nul_byte_in_first_argument_rejected() {
         let ws = test_winsize();
         let cmd = vec!["\0bad".into()];
-        let err = spawn_child(&cmd, &ws).unwrap_err();
+        let err = spawn_child(&cmd, &ws, &[]).unwrap_err();
         assert!(
             matches!(err, PtyError::Exec(ref msg) if msg.contains("null byte")),
             "expected Exec error about null byte, got: {err}"
@@ -194,25 +284,58 @@ mod tests {
     #[test]
     fn spawn_true_exits_zero() {
         let ws = test_winsize();
-        let child = spawn_child(&["true".into()], &ws).unwrap();
+        let child = spawn_child(&["true".into()], &ws, &[]).unwrap();
         let code = wait_for_exit(child.pid).unwrap();
-        assert_eq!(code, 0);
+        assert_eq!(code, ChildExit::Exited(0));
     }
 
     #[test]
     fn spawn_false_exits_nonzero() {
         let ws = test_winsize();
-        let child = spawn_child(&["false".into()], &ws).unwrap();
+        let child = spawn_child(&["false".into()], &ws, &[]).unwrap();
         let code = wait_for_exit(child.pid).unwrap();
-        assert_eq!(code, 1);
+        assert_eq!(code, ChildExit::Exited(1));
     }
 
     #[test]
     fn nonexistent_command_exits_127() {
         let ws = test_winsize();
-        let child = spawn_child(&["__clippy_nonexistent_cmd_12345__".into()], &ws).unwrap();
+        let child = spawn_child(&["__clippy_nonexistent_cmd_12345__".into()], &ws, &[]).unwrap();
         let code = wait_for_exit(child.pid).unwrap();
-        assert_eq!(code, 127);
+        assert_eq!(code, ChildExit::Exited(127));
+    }
+
+    #[test]
+    fn wait_within_gives_up_on_a_running_child() {
+        let ws = test_winsize();
+        let child = spawn_child(&["sleep".into(), "5".into()], &ws, &[]).unwrap();
+        let waited = wait_for_exit_within(child.pid, std::time::Duration::from_millis(50));
+        assert_eq!(waited.unwrap(), None);
+        nix::sys::signal::kill(child.pid, Signal::SIGKILL).unwrap();
+        let exit = wait_for_exit_within(child.pid, std::time::Duration::from_secs(5));
+        assert_eq!(exit.unwrap(), Some(ChildExit::Signaled(Signal::SIGKILL)));
+    }
+
+    #[test]
+    fn child_exit_code_and_reason() {
+        assert_eq!(ChildExit::Exited(2).code(), 2);
+        assert_eq!(ChildExit::Exited(2).reason(), "exited with code 2");
+        let killed = ChildExit::Signaled(Signal::SIGSEGV);
+        assert_eq!(killed.code(), 139);
+        assert_eq!(killed.reason(), "killed by SIGSEGV");
+    }
+
+    #[test]
+    fn build_env_overrides_and_appends() {
+        let env = build_env(&[
+            ("CLIPPY_SESSION_ID".into(), "abc".into()),
+            ("PATH".into(), "/override".into()),
+        ])
+        .unwrap();
+        let env: Vec<String> = env.into_iter().map(|c| c.into_string().unwrap()).collect();
+        assert!(env.contains(&"CLIPPY_SESSION_ID=abc".to_string()));
+        assert_eq!(env.iter().filter(|e| e.starts_with("PATH=")).count(), 1);
+        assert!(env.contains(&"PATH=/override".to_string()));
     }
 
     #[test]
@@ -231,6 +354,7 @@ mod tests {
                 "c".into(),
             ],
             &ws,
+            &[],
         )
         .unwrap();
 
@@ -0,0 +1,355 @@
+//! Git context for a turn's working directory, read from `.git`.
+//!
+//! The wrapper runs this on every completed turn, so it reads the
+//! repository files directly instead of spawning `git`: HEAD and refs
+//! (loose or packed) for the branch and commit, and the index for a
+//! stat-based check for unstaged changes (tracked files whose worktree
+//! copy differs from the index). Staged changes are not seen: that
+//! would mean comparing the index with HEAD's tree, whose objects are
+//! compressed. Untracked files do not count either. See
+//! CONTRACT_PTY.md §Turn context.
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::ipc::protocol::GitContext;
+
+/// Largest index (in entries) checked for dirtiness; beyond this the
+/// stat pass would stall the I/O loop and `dirty` is left unknown.
+const MAX_DIRTY_CHECK_ENTRIES: usize = 10_000;
+
+/// Longest chain of symbolic refs followed.
+const MAX_REF_DEPTH: usize = 5;
+
+/// Git state for `dir`, or `None` if it is not inside a repository.
+pub fn git_context(dir: &Path) -> Option<GitContext> {
+    let (root, git_dir) = find_repo(dir)?;
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(path) => git_dir.join(path.trim()),
+        Err(_) => git_dir.clone(),
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let (branch, sha) = match head.strip_prefix("ref: ") {
+        Some(name) => (
+            name.strip_prefix("refs/heads/").map(str::to_string),
+            resolve_ref(&git_dir, &common_dir, name, 0),
+        ),
+        None => (None, Some(head.to_string())),
+    };
+
+    let dirty = match fs::read(git_dir.join("index")) {
+        Ok(index) => index_dirty(&root, &index),
+        // No index yet (fresh repository): nothing is tracked.
+        Err(_) => sha.is_none().then_some(false),
+    };
+
+    Some(GitContext {
+        root: root.display().to_string(),
+        branch,
+        head: sha,
+        dirty,
+    })
+}
+
+/// Find the enclosing worktree root and its git directory. `.git` may
+/// be a directory or, for linked worktrees and submodules, a file
+/// containing `gitdir: <path>`.
+fn find_repo(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for root in dir.ancestors() {
+        let dot_git = root.join(".git");
+        if dot_git.is_dir() {
+            return Some((root.to_path_buf(), dot_git));
+        }
+        if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir: ")?;
+            return Some((root.to_path_buf(), root.join(git_dir)));
+        }
+    }
+    None
+}
+
+/// Resolve a ref name to a commit SHA: loose ref files (per-worktree
+/// first), then `packed-refs`.
+fn resolve_ref(git_dir: &Path, common_dir: &Path, name: &str, depth: usize) -> Option<String> {
+    if depth > MAX_REF_DEPTH {
+        return None;
+    }
+    for dir in [git_dir, common_dir] {
+        if let Ok(content) = fs::read_to_string(dir.join(name)) {
+            let content = content.trim();
+            return match content.strip_prefix("ref: ") {
+                Some(target) => resolve_ref(git_dir, common_dir, target, depth + 1),
+                None => Some(content.to_string()),
+            };
+        }
+    }
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .find_map(|line| {
+            let (sha, ref_name) = line.split_once(' ')?;
+            (ref_name == name).then(|| sha.to_string())
+        })
+}
+
+/// Compare the index's cached stat data with the worktree (unstaged
+/// changes only). Returns `None` for an unreadable or oversized index.
+///
+/// Like git's own first pass, a file whose size or mtime changed counts
+/// as modified even if its content is the same.
+fn index_dirty(root: &Path, index: &[u8]) -> Option<bool> {
+    if index.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = be32(index, 4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let count = be32(index, 8)? as usize;
+    if count > MAX_DIRTY_CHECK_ENTRIES {
+        return None;
+    }
+
+    let mut pos = 12;
+    let mut path: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let entry = pos;
+        let mtime_s = be32(index, entry + 8)?;
+        let mtime_ns = be32(index, entry + 12)?;
+        let mode = be32(index, entry + 24)?;
+        let size = be32(index, entry + 36)?;
+        let flags = be16(index, entry + 60)?;
+        pos = entry + 62;
+
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            let extended = be16(index, pos)?;
+            skip_worktree = extended & 0x4000 != 0;
+            pos += 2;
+        }
+
+        if version == 4 {
+            // Path is prefix-compressed against the previous entry.
+            let (strip, len) = varint(index.get(pos..)?)?;
+            pos += len;
+            let keep = path.len().checked_sub(strip)?;
+            path.truncate(keep);
+            let end = pos + index.get(pos..)?.iter().position(|&b| b == 0)?;
+            path.extend_from_slice(&index[pos..end]);
+            pos = end + 1;
+        } else {
+            let end = pos + index.get(pos..)?.iter().position(|&b| b == 0)?;
+            path.clear();
+            path.extend_from_slice(&index[pos..end]);
+            // Entries are NUL-padded to a multiple of 8 bytes.
+            pos = entry + (end - entry + 8) / 8 * 8;
+        }
+
+        let stage = (flags >> 12) & 0x3;
+        if stage != 0 {
+            // Unresolved merge conflict.
+            return Some(true);
+        }
+        let is_gitlink = mode & 0o170000 == 0o160000;
+        if skip_worktree || is_gitlink {
+            continue;
+        }
+        let file = root.join(std::str::from_utf8(&path).ok()?);
+        let Ok(meta) = fs::symlink_metadata(&file) else {
+            return Some(true);
+        };
+        if meta.len() as u32 != size
+            || meta.mtime() as u32 != mtime_s
+            || meta.mtime_nsec() as u32 != mtime_ns
+        {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// Decode git's offset varint. Returns `(value, bytes read)`.
+fn varint(data: &[u8]) -> Option<(usize, usize)> {
+    let mut read = 0;
+    let mut byte = *data.get(read)?;
+    read += 1;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = *data.get(read)?;
+        read += 1;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Some((value, read))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn repo(head: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git/refs/heads")).unwrap();
+        fs::write(dir.path().join(".git/HEAD"), head).unwrap();
+        dir
+    }
+
+    /// Build a version 2 index with one entry per path, using the
+    /// file's current stat data.
+    fn write_index(root: &Path, paths: &[&str]) {
+        let mut index = b"DIRC".to_vec();
+        index.extend_from_slice(&2u32.to_be_bytes());
+        index.extend_from_slice(&(paths.len() as u32).to_be_bytes());
+        for path in paths {
+            let meta = fs::metadata(root.join(path)).unwrap();
+            let start = index.len();
+            let mut fields = [0u32; 10];
+            fields[2] = meta.mtime() as u32;
+            fields[3] = meta.mtime_nsec() as u32;
+            fields[6] = 0o100644;
+            fields[9] = meta.len() as u32;
+            for field in fields {
+                index.extend_from_slice(&field.to_be_bytes());
+            }
+            index.extend_from_slice(&[0u8; 20]);
+            index.extend_from_slice(&(path.len() as u16).to_be_bytes());
+            index.extend_from_slice(path.as_bytes());
+            let len = index.len() - start;
+            index.resize(start + (len + 8) / 8 * 8, 0);
+        }
+        fs::write(root.join(".git/index"), index).unwrap();
+    }
+
+    #[test]
+    fn branch_and_loose_ref() {
+        let dir = repo("ref: refs/heads/main\n");
+        fs::write(dir.path().join(".git/refs/heads/main"), format!("{SHA}\n")).unwrap();
+        let sub = dir.path().join("src");
+        fs::create_dir(&sub).unwrap();
+        let git = git_context(&sub).unwrap();
+        assert_eq!(git.root, dir.path().display().to_string());
+        assert_eq!(git.branch.as_deref(), Some("main"));
+        assert_eq!(git.head.as_deref(), Some(SHA));
+    }
+
+    #[test]
+    fn packed_ref() {
+        let dir = repo("ref: refs/heads/feature\n");
+        fs::write(
+            dir.path().join(".git/packed-refs"),
+            format!("# pack-refs with: peeled\n{SHA} refs/heads/feature\n^{SHA}\n"),
+        )
+        .unwrap();
+        let git = git_context(dir.path()).unwrap();
+        assert_eq!(git.head.as_deref(), Some(SHA));
+    }
+
+    #[test]
+    fn detached_head() {
+        let dir = repo(&format!("{SHA}\n"));
+        let git = git_context(dir.path()).unwrap();
+        assert_eq!(git.branch, None);
+        assert_eq!(git.head.as_deref(), Some(SHA));
+    }
+
+    #[test]
+    fn unborn_branch_is_clean() {
+        let dir = repo("ref: refs/heads/main\n");
+        let git = git_context(dir.path()).unwrap();
+        assert_eq!(git.head, None);
+        assert_eq!(git.dirty, Some(false));
+    }
+
+    #[test]
+    fn linked_worktree() {
+        let main = repo("ref: refs/heads/main\n");
+        fs::write(
+            main.path().join(".git/refs/heads/topic"),
+            format!("{SHA}\n"),
+        )
+        .unwrap();
+        let wt_git = main.path().join(".git/worktrees/wt");
+        fs::create_dir_all(&wt_git).unwrap();
+        fs::write(wt_git.join("HEAD"), "ref: refs/heads/topic\n").unwrap();
+        fs::write(wt_git.join("commondir"), "../..\n").unwrap();
+
+        let wt = tempfile::tempdir().unwrap();
+        fs::write(
+            wt.path().join(".git"),
+            format!("gitdir: {}\n", wt_git.display()),
+        )
+        .unwrap();
+        let git = git_context(wt.path()).unwrap();
+        assert_eq!(git.root, wt.path().display().to_string());
+        assert_eq!(git.branch.as_deref(), Some("topic"));
+        assert_eq!(git.head.as_deref(), Some(SHA));
+    }
+
+    #[test]
+    fn dirty_when_tracked_file_changes() {
+        let dir = repo(&format!("{SHA}\n"));
+        fs::write(dir.path().join("a.txt"), "one").unwrap();
+        fs::write(dir.path().join("b.txt"), "two").unwrap();
+        write_index(dir.path(), &["a.txt", "b.txt"]);
+        assert_eq!(git_context(dir.path()).unwrap().dirty, Some(false));
+
+        fs::write(dir.path().join("b.txt"), "changed").unwrap();
+        assert_eq!(git_context(dir.path()).unwrap().dirty, Some(true));
+    }
+
+    #[test]
+    fn dirty_when_tracked_file_deleted() {
+        let dir = repo(&format!("{SHA}\n"));
+        fs::write(dir.path().join("a.txt"), "one").unwrap();
+        write_index(dir.path(), &["a.txt"]);
+        fs::remove_file(dir.path().join("a.txt")).unwrap();
+        assert_eq!(git_context(dir.path()).unwrap().dirty, Some(true));
+    }
+
+    #[test]
+    fn staged_changes_do_not_count() {
+        let dir = repo(&format!("{SHA}\n"));
+        fs::write(dir.path().join("a.txt"), "one").unwrap();
+        write_index(dir.path(), &["a.txt"]);
+        // `git add` after an edit: the index matches the worktree again.
+        fs::write(dir.path().join("a.txt"), "changed").unwrap();
+        write_index(dir.path(), &["a.txt"]);
+        assert_eq!(git_context(dir.path()).unwrap().dirty, Some(false));
+    }
+
+    #[test]
+    fn untracked_files_do_not_count() {
+        let dir = repo(&format!("{SHA}\n"));
+        fs::write(dir.path().join("a.txt"), "one").unwrap();
+        write_index(dir.path(), &["a.txt"]);
+        fs::write(dir.path().join("new.txt"), "untracked").unwrap();
+        assert_eq!(git_context(dir.path()).unwrap().dirty, Some(false));
+    }
+
+    #[test]
+    fn not_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(git_context(dir.path()), None);
+    }
+
+    #[test]
+    fn varint_decoding() {
+        assert_eq!(varint(&[0x05]), Some((5, 1)));
+        // Git's offset encoding: 0x80 0x00 is 128.
+        assert_eq!(varint(&[0x80, 0x00]), Some((128, 2)));
+    }
+}
@@ -0,0 +1,104 @@
+//! Wrapper-side turn history — replayed to the broker after reconnect.
+//!
+//! The broker keeps all state in memory, so a broker restart (or a
+//! dropped connection, which implicitly deregisters the session) loses
+//! the session's registry. The wrapper keeps its own bounded ring of
+//! recent turns with their original sequence numbers and timestamps
+//! and replays it on registration, so the broker rebuilds the same
+//! turn IDs. See CONTRACT_PTY.md §Turn Detector Integration.
+
+use std::collections::VecDeque;
+
+use crate::ipc::protocol::TurnContext;
+use crate::turn::Turn;
+
+/// A completed turn with its wrapper-assigned sequence number.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// Per-session sequence number, starting at 1.
+    pub seq: u64,
+    pub turn: Turn,
+    /// Timing and environment captured at completion.
+    pub context: TurnContext,
+}
+
+/// Bounded ring of recent turns, oldest first.
+#[derive(Debug)]
+pub struct TurnHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl TurnHistory {
+    /// Create an empty history holding at most `capacity` turns
+    /// (minimum 1).
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 1,
+        }
+    }
+
+    /// Record a completed turn, assigning the next sequence number.
+    /// Evicts the oldest turn when full.
+    pub fn push(&mut self, turn: Turn, context: TurnContext) -> &HistoryEntry {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            seq: self.next_seq,
+            turn,
+            context,
+        });
+        self.next_seq += 1;
+        self.entries.back().expect("just pushed")
+    }
+
+    /// Iterate retained turns oldest first (replay order).
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(content: &str, timestamp: u64) -> Turn {
+        Turn {
+            content: content.as_bytes().to_vec(),
+            interrupted: false,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn assigns_increasing_seqs() {
+        let mut h = TurnHistory::new(4);
+        assert_eq!(h.push(turn("a", 1000), TurnContext::default()).seq, 1);
+        assert_eq!(h.push(turn("b", 2000), TurnContext::default()).seq, 2);
+        let seqs: Vec<u64> = h.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [1, 2]);
+    }
+
+    #[test]
+    fn evicts_oldest_and_keeps_seqs() {
+        let mut h = TurnHistory::new(2);
+        h.push(turn("a", 1000), TurnContext::default());
+        h.push(turn("b", 2000), TurnContext::default());
+        h.push(turn("c", 3000), TurnContext::default());
+        let kept: Vec<(u64, u64)> = h.iter().map(|e| (e.seq, e.turn.timestamp)).collect();
+        assert_eq!(kept, [(2, 2000), (3, 3000)]);
+    }
+
+    #[test]
+    fn zero_capacity_holds_one() {
+        let mut h = TurnHistory::new(0);
+        h.push(turn("a", 1000), TurnContext::default());
+        h.push(turn("b", 2000), TurnContext::default());
+        assert_eq!(h.iter().count(), 1);
+    }
+}
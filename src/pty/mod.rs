@@ -6,12 +6,20 @@
 //!
 //! See CONTRACT_PTY.md.
 
+mod backoff;
 mod broker_client;
 mod child;
+mod git;
+mod history;
+mod recording;
+mod scrollback;
 mod terminal;
+mod title;
 
-use std::io;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
 use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::path::{Path, PathBuf};
 
 use nix::libc;
 
@@ -20,13 +28,22 @@ use nix::sys::signal::{self, Signal};
 use nix::unistd::Pid;
 use tokio::io::unix::AsyncFd;
 use tokio::signal::unix::{SignalKind, signal as tokio_signal};
+use tokio::sync::mpsc;
 use tokio::time;
 
-use broker_client::BrokerClient;
-use child::{spawn_child, wait_for_exit};
-use terminal::{TerminalGuard, get_terminal_size, propagate_window_size};
+use backoff::Backoff;
+use broker_client::{BrokerClient, Registration};
+use child::{spawn_child, wait_for_exit, wait_for_exit_within};
+use history::TurnHistory;
+use recording::{FileRecorder, Header};
+use scrollback::Scrollback;
+use terminal::{
+    TerminalGuard, get_terminal_size, propagate_window_size, pty_window_size, set_window_size,
+};
+use title::TitleTracker;
 
-use crate::turn::{TurnDetector, TurnError, TurnEvent};
+use crate::ipc::protocol::{AgentState, TurnContext};
+use crate::turn::{DetectorState, Turn, TurnDetector, TurnError, TurnEvent};
 
 /// PTY wrapper errors.
 #[derive(Debug, thiserror::Error)]
@@ -45,10 +62,56 @@ pub enum PtyError {
     TurnDetector(#[from] TurnError),
     #[error("broker: {0}")]
     Broker(String),
+    #[error("session name {0:?} is already in use")]
+    NameInUse(String),
     #[error("signal error: {0}")]
     Signal(nix::Error),
 }
 
+/// Options for [`run_session`], collected from `clippyctl wrap` flags.
+#[derive(Debug, Clone)]
+pub struct WrapOptions {
+    /// Prompt pattern preset name or custom regex.
+    pub pattern: String,
+    /// Optional session name, unique per broker (`--name`).
+    pub name: Option<String>,
+    /// Optional session role (`--role`).
+    pub role: Option<String>,
+    /// Session labels (`--label k=v`, repeatable).
+    pub labels: BTreeMap<String, String>,
+    /// Export `CLIPPY_SESSION_ID` and `CLIPPY_SOCKET` to the child
+    /// (`--export-env`). Off by default per CONTRACT_PTY.md §Environment.
+    pub export_env: bool,
+    /// Run without a controlling terminal (`--headless`).
+    pub headless: Option<HeadlessOptions>,
+    /// Number of recent turns kept locally and replayed to the broker
+    /// after reconnecting (`--history`).
+    pub history_depth: usize,
+    /// Print a one-line notice on stderr when the broker connection is
+    /// lost or restored (`--broker-status`).
+    pub broker_status: bool,
+    /// Number of recent output lines kept for `capture-lines`
+    /// (`--scrollback`).
+    pub scrollback_lines: usize,
+    /// Record the session to this asciicast file from the start
+    /// (`--record`).
+    pub record: Option<PathBuf>,
+}
+
+/// Headless mode settings (`wrap --headless`).
+///
+/// The wrapper does not touch stdin or the user's terminal; the child
+/// is driven entirely through broker injects (CONTRACT_PTY.md §Headless).
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    /// Fixed PTY width.
+    pub cols: u16,
+    /// Fixed PTY height.
+    pub rows: u16,
+    /// Append child output here; discarded when `None`.
+    pub output_log: Option<PathBuf>,
+}
+
 /// Run a PTY-wrapped session for the given command with turn detection.
 ///
 /// This is the main entry point called from `main.rs` for the `wrap`
@@ -65,12 +128,14 @@ pub enum PtyError {
 /// - SIGWINCH → TIOCSWINSZ, not forwarded (§200–211)
 /// - Late registration with local turn buffer (§119, §155–158)
 /// - Exit with child's code (§169–178)
-pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, PtyError> {
+/// - Opt-in cooperation environment only with `--export-env` (§Environment)
+/// - Headless mode: fixed size, no stdin/raw mode, inject-only (§Headless)
+pub async fn run_session(options: WrapOptions, command: Vec<String>) -> Result<i32, PtyError> {
     // Generate session ID.
     let session_id = uuid::Uuid::new_v4().to_string();
 
     // Initialize turn detector (fail early on invalid pattern).
-    let mut turn_detector = TurnDetector::new(&pattern)?;
+    let mut turn_detector = TurnDetector::new(&options.pattern)?;
 
     // Install signal handlers BEFORE entering raw mode.
     let mut sig_int = tokio_signal(SignalKind::interrupt())?;
@@ -81,47 +146,168 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
     let mut sig_tstp = tokio_signal(SignalKind::from_raw(libc::SIGTSTP))?;
     let mut sig_cont = tokio_signal(SignalKind::from_raw(libc::SIGCONT))?;
 
-    // Get terminal dimensions for the child PTY.
-    let winsize = get_terminal_size()?;
+    // Get terminal dimensions for the child PTY (fixed when headless).
+    let winsize = match options.headless {
+        Some(ref h) => nix::pty::Winsize {
+            ws_row: h.rows,
+            ws_col: h.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        },
+        None => get_terminal_size()?,
+    };
+
+    // Headless output log, opened before spawning so a bad path fails early.
+    let mut output_log = match options
+        .headless
+        .as_ref()
+        .and_then(|h| h.output_log.as_ref())
+    {
+        Some(path) => Some(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => None,
+    };
+
+    // Opt-in recording, also started before spawning to fail early.
+    let mut recorder = match options.record {
+        Some(ref path) => Some(start_recording(
+            path,
+            (winsize.ws_col, winsize.ws_row),
+            &command,
+        )?),
+        None => None,
+    };
+
+    // Opt-in cooperation environment for the child.
+    let mut extra_env = Vec::new();
+    if options.export_env {
+        extra_env.push(("CLIPPY_SESSION_ID".to_string(), session_id.clone()));
+        match broker_client::resolve_socket_path() {
+            Ok(path) => extra_env.push(("CLIPPY_SOCKET".to_string(), path.display().to_string())),
+            Err(e) => tracing::warn!(error = %e, "CLIPPY_SOCKET not exported"),
+        }
+    }
 
     // Spawn child process with PTY.
-    let child_result = spawn_child(&command, &winsize)?;
+    let started_at = crate::turn::epoch_millis();
+    let child_result = spawn_child(&command, &winsize, &extra_env)?;
     let child_pid = child_result.pid;
     let master_fd = child_result.master.as_raw_fd();
 
     tracing::info!(
         session = %session_id,
+        name = ?options.name,
         pid = child_pid.as_raw(),
         command = ?command,
         "session started"
     );
 
+    let registration = Registration {
+        session_id: session_id.clone(),
+        pid: child_pid.as_raw() as u32,
+        pattern: options.pattern.clone(),
+        name: options.name.clone(),
+        role: options.role.clone(),
+        labels: options.labels.clone(),
+        command: command.clone(),
+        started_at,
+    };
+
     // Enter raw mode (RAII guard ensures restore on any exit path).
-    let terminal_guard = TerminalGuard::enter_raw_mode()?;
+    // Headless sessions have no terminal to manage.
+    let terminal_guard = match options.headless {
+        Some(_) => None,
+        None => Some(TerminalGuard::enter_raw_mode()?),
+    };
 
     // Attempt to connect to broker (optional — standalone if unreachable).
-    let mut broker_client =
-        match BrokerClient::connect(&session_id, child_pid.as_raw() as u32, &pattern).await {
-            Ok(client) => {
-                tracing::info!("connected to broker");
-                Some(client)
-            }
-            Err(e) => {
-                tracing::warn!(error = %e, "broker unavailable — running standalone");
-                None
-            }
-        };
+    let mut broker_client = match BrokerClient::connect(&registration).await {
+        Ok(client) => {
+            tracing::info!("connected to broker");
+            Some(client)
+        }
+        Err(e @ PtyError::NameInUse(_)) => {
+            // Retrying cannot succeed while the other session holds the
+            // name, and a standalone session would never get it either.
+            hang_up(child_pid);
+            return Err(e);
+        }
+        Err(e) if options.headless.is_some() => {
+            // Injects are the only input path; without a broker the
+            // session could never be driven.
+            hang_up(child_pid);
+            let reason = match e {
+                PtyError::Broker(msg) => msg,
+                other => other.to_string(),
+            };
+            return Err(PtyError::Broker(format!(
+                "required for --headless ({reason})"
+            )));
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "broker unavailable — running standalone");
+            status_line(options.broker_status, "broker unavailable, retrying");
+            None
+        }
+    };
+
+    // Timer-driven reconnect: while disconnected, retry with
+    // exponential backoff so idle sessions reappear after a broker
+    // restart without waiting for a turn.
+    let mut backoff = Backoff::new();
+    let mut reconnect_at = broker_client
+        .is_none()
+        .then(|| time::Instant::now() + backoff.next_delay());
+
+    // Set by a broker `kill`: SIGKILL the child if it outlives the grace.
+    let mut kill_deadline: Option<time::Instant> = None;
 
     // Wrap PTY master in AsyncFd for tokio integration.
     // We need to keep `child_result.master` alive (owns the fd).
     let pty_async = AsyncFd::new(child_result.master)?;
 
-    // Non-owning stdin wrapper (don't close on drop).
-    let stdin_async = AsyncFd::new(StdinFd)?;
+    // Non-owning stdin wrapper (don't close on drop). Not polled when
+    // headless — stdin may be closed, a file, or /dev/null.
+    let stdin_async = match options.headless {
+        Some(_) => None,
+        None => Some(AsyncFd::new(StdinFd)?),
+    };
+
+    // Recent completed turns with wrapper-assigned sequence numbers —
+    // replayed on late registration so the broker rebuilds the same
+    // turn IDs (CONTRACT_PTY.md §119).
+    let mut history = TurnHistory::new(options.history_depth);
+
+    // Agent state last reported to the broker. Reset on disconnect so
+    // the current state is re-sent after late registration.
+    let mut reported_state: Option<AgentState> = None;
+
+    // Terminal title from OSC 0/2, and the title last reported to the
+    // broker (reset on disconnect, like `reported_state`).
+    let mut title_tracker = TitleTracker::new();
+    let mut current_title: Option<String> = None;
+    let mut reported_title: Option<String> = None;
+
+    // Recent output lines, for broker `capture_lines` queries.
+    let mut scrollback = Scrollback::new(options.scrollback_lines);
 
-    // Latest completed turn buffer — retained locally for late
-    // registration when broker is unreachable (CONTRACT_PTY.md §119).
-    let mut latest_turn: Option<crate::turn::Turn> = None;
+    // When the input that started the current turn was submitted;
+    // taken into the turn's context when it completes.
+    let mut turn_submitted_at: Option<u64> = None;
+
+    // Completed turns get their context off the I/O loop and come back
+    // in completion order. While any is out, the agent state is not
+    // reported, so the broker sees a turn before the session turns idle.
+    let (context_tx, mut context_rx) = spawn_context_reader(child_pid);
+    let mut turns_in_flight = 0usize;
+
+    // Set by the broker while at least one client follows this
+    // session's output (`client tail`). Reset on disconnect.
+    let mut output_tap = false;
 
     // -- Main I/O loop --
     let mut stdin_buf = [0u8; 8192];
@@ -131,10 +317,26 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
         // Pending turns to send after select! (avoids borrow conflicts).
         // Vec instead of Option: a single read chunk can emit multiple turns.
         let mut pending_turns: Vec<crate::turn::Turn> = Vec::new();
+        // Set when input is submitted (Enter or inject), reported after select!.
+        let mut input_submitted = false;
+        // Set when the broker connection drops or a reconnect is due.
+        let mut broker_lost = false;
+        let mut reconnect_due = false;
+        // Reply to a broker `capture_lines` query: (query token, lines).
+        let mut scrollback_reply: Option<(u32, Vec<u8>)> = None;
+        // Output to forward to the broker's tap subscribers.
+        let mut tapped_output: Option<Vec<u8>> = None;
+        // A completed turn back from the context reader.
+        let mut ready_turn: Option<(Turn, TurnContext)> = None;
 
         tokio::select! {
             // -- User stdin → PTY master --
-            guard = stdin_async.readable() => {
+            guard = async {
+                match stdin_async.as_ref() {
+                    Some(stdin) => stdin.readable().await,
+                    None => std::future::pending().await,
+                }
+            } => {
                 let mut guard = guard?;
                 match guard.try_io(|_| {
                     nix_read(libc::STDIN_FILENO, &mut stdin_buf)
@@ -146,10 +348,11 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                     Ok(Ok(n)) => {
                         // Forward to PTY master unmodified.
                         nix_write_all(master_fd, &stdin_buf[..n])?;
+                        record(&mut recorder, |r| r.input(&stdin_buf[..n]));
 
                         // Detect Enter key → notify turn detector.
-                        if stdin_buf[..n].iter().any(|&b| b == b'\r' || b == b'\n') {
-                            turn_detector.notify_user_input();
+                        if submit_input(&mut turn_detector, &stdin_buf[..n]) {
+                            input_submitted = true;
                         }
                     }
                     Ok(Err(e)) => break Err(e.into()),
@@ -168,8 +371,22 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                         break Ok(());
                     }
                     Ok(Ok(n)) => {
-                        // Forward to stdout unmodified.
-                        nix_write_all(libc::STDOUT_FILENO, &pty_buf[..n])?;
+                        // Forward to stdout unmodified (or the headless log).
+                        if options.headless.is_none() {
+                            nix_write_all(libc::STDOUT_FILENO, &pty_buf[..n])?;
+                        } else if let Some(ref mut log) = output_log {
+                            log.write_all(&pty_buf[..n])?;
+                        }
+
+                        // Observe title changes (the bytes above are unchanged).
+                        if let Some(title) = title_tracker.feed(&pty_buf[..n]) {
+                            current_title = Some(title);
+                        }
+                        scrollback.feed(&pty_buf[..n]);
+                        record(&mut recorder, |r| r.output(&pty_buf[..n]));
+                        if output_tap {
+                            tapped_output = Some(pty_buf[..n].to_vec());
+                        }
 
                         // Feed to turn detector.
                         let events = turn_detector.feed_output(&pty_buf[..n]);
@@ -206,6 +423,73 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                         // Write injected bytes to PTY master input.
                         tracing::debug!(len = content.len(), "inject received");
                         nix_write_all(master_fd, &content)?;
+                        record(&mut recorder, |r| r.input(&content));
+                        // Injects are the only input of a headless
+                        // session, so they start turns like typed input.
+                        if submit_input(&mut turn_detector, &content) {
+                            input_submitted = true;
+                        }
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::CaptureLines { id, last, grep, .. })) => {
+                        // The broker validated the regex; an invalid one
+                        // here selects nothing rather than everything.
+                        let lines = match grep.as_deref().map(regex::Regex::new) {
+                            None => scrollback.last_lines(last as usize, None),
+                            Some(Ok(re)) => scrollback.last_lines(last as usize, Some(&re)),
+                            Some(Err(_)) => Vec::new(),
+                        };
+                        let mut content = Vec::new();
+                        for line in lines {
+                            content.extend_from_slice(line.as_bytes());
+                            content.push(b'\n');
+                        }
+                        scrollback_reply = Some((id, content));
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::Signal { signal, .. })) => {
+                        match signal.parse::<Signal>() {
+                            Ok(sig) => {
+                                tracing::debug!(%signal, "signal received from broker");
+                                if sig == Signal::SIGINT {
+                                    turn_detector.notify_interrupt();
+                                }
+                                forward_signal(child_pid, sig)?;
+                            }
+                            Err(_) => tracing::warn!(%signal, "unknown signal from broker"),
+                        }
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::Kill { .. })) => {
+                        tracing::info!("terminate requested by broker");
+                        forward_signal(child_pid, Signal::SIGTERM)?;
+                        kill_deadline.get_or_insert(time::Instant::now() + KILL_GRACE);
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::Resize { cols, rows, .. })) => {
+                        if options.headless.is_some() {
+                            match set_window_size(master_fd, cols, rows) {
+                                Ok(()) => record(&mut recorder, |r| r.resize(cols, rows)),
+                                Err(e) => tracing::warn!(error = %e, "resize from broker failed"),
+                            }
+                        } else {
+                            tracing::warn!("ignoring resize: window size follows the terminal");
+                        }
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::RecordStart { path, .. })) => {
+                        stop_recording(&mut recorder);
+                        let size = pty_window_size(master_fd)
+                            .unwrap_or((winsize.ws_col, winsize.ws_row));
+                        match start_recording(Path::new(&path), size, &command) {
+                            Ok(r) => {
+                                tracing::info!(%path, "recording started");
+                                recorder = Some(r);
+                            }
+                            Err(e) => tracing::warn!(error = %e, %path, "cannot start recording"),
+                        }
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::RecordStop { .. })) => {
+                        stop_recording(&mut recorder);
+                    }
+                    Some(Ok(crate::ipc::protocol::Message::OutputTap { enabled, .. })) => {
+                        tracing::debug!(enabled, "output tap toggled by broker");
+                        output_tap = enabled;
                     }
                     Some(Ok(crate::ipc::protocol::Message::Response { .. })) => {
                         // Ack to a previous request — ignore.
@@ -215,15 +499,44 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                     }
                     Some(Err(e)) => {
                         tracing::warn!(error = %e, "broker codec error — disconnecting");
-                        broker_client = None;
+                        broker_lost = true;
                     }
                     None => {
                         tracing::warn!("broker disconnected");
-                        broker_client = None;
+                        broker_lost = true;
                     }
                 }
             }
 
+            // -- Completed turn with its context --
+            Some(turn) = context_rx.recv() => {
+                ready_turn = Some(turn);
+            }
+
+            // -- Escalation after a broker-requested terminate --
+            _ = async {
+                match kill_deadline {
+                    Some(at) => time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                tracing::warn!("child still running after terminate — sending SIGKILL");
+                kill_deadline = None;
+                if let Err(e) = forward_signal(child_pid, Signal::SIGKILL) {
+                    tracing::warn!(error = %e, "SIGKILL failed");
+                }
+            }
+
+            // -- Broker reconnect timer --
+            _ = async {
+                match reconnect_at {
+                    Some(at) => time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                reconnect_due = true;
+            }
+
             // -- Signal handlers --
             _ = sig_int.recv() => {
                 turn_detector.notify_interrupt();
@@ -243,9 +556,11 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                 forward_signal(child_pid, Signal::SIGQUIT)?;
             }
 
-            _ = sig_winch.recv() => {
+            _ = sig_winch.recv(), if terminal_guard.is_some() => {
                 if let Err(e) = propagate_window_size(master_fd) {
                     tracing::warn!(error = %e, "SIGWINCH handling failed");
+                } else if let Ok((cols, rows)) = pty_window_size(master_fd) {
+                    record(&mut recorder, |r| r.resize(cols, rows));
                 }
             }
 
@@ -254,14 +569,18 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
                 forward_signal(child_pid, Signal::SIGTSTP)?;
                 // Restore terminal before suspending so the user's shell
                 // works while we're stopped.
-                if let Err(e) = terminal_guard.restore() {
+                if let Some(ref guard) = terminal_guard
+                    && let Err(e) = guard.restore()
+                {
                     tracing::warn!(error = %e, "terminal restore before SIGTSTP failed");
                 }
                 // Raise SIGSTOP on self — tokio consumed SIGTSTP, so we
                 // use SIGSTOP to actually suspend the process.
                 signal::kill(Pid::this(), Signal::SIGSTOP).map_err(PtyError::Signal)?;
                 // Execution resumes here after SIGCONT — re-enter raw mode.
-                if let Err(e) = terminal_guard.reenter_raw() {
+                if let Some(ref guard) = terminal_guard
+                    && let Err(e) = guard.reenter_raw()
+                {
                     tracing::warn!(error = %e, "terminal re-raw after resume failed");
                 }
             }
@@ -275,47 +594,145 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
         // Send pending turns (outside select! to avoid borrow conflicts).
         // All broker I/O is bounded by a timeout so it cannot stall the
         // main I/O loop (CONTRACT_PTY.md §46, §49).
-        if !pending_turns.is_empty() {
-            // Always update the local latest-turn buffer (for late registration).
-            latest_turn = pending_turns.last().cloned();
+        const BROKER_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+        const REPLAY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
 
-            const BROKER_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+        if broker_lost {
+            broker_client = None;
+            reported_state = None;
+            reported_title = None;
+            output_tap = false;
+            backoff.reset();
+            reconnect_at = Some(time::Instant::now() + backoff.next_delay());
+            status_line(
+                options.broker_status,
+                "broker connection lost, reconnecting",
+            );
+        }
 
-            if let Some(ref mut broker) = broker_client {
-                for turn in &pending_turns {
-                    match time::timeout(BROKER_IO_TIMEOUT, broker.send_turn(turn)).await {
-                        Ok(Err(e)) => {
-                            tracing::warn!(error = %e, "failed to send turn to broker");
-                        }
-                        Err(_elapsed) => {
-                            tracing::warn!("broker send timed out — skipping");
-                            break;
-                        }
-                        Ok(Ok(())) => {}
-                    }
+        if reconnect_due {
+            // CONTRACT_PTY.md §119: replay retained history on successful
+            // registration. Replay gets a longer bound than single sends
+            // since it may carry many turns.
+            let attempt = async {
+                let mut client = BrokerClient::connect(&registration).await?;
+                client.replay(&history).await?;
+                Ok::<_, PtyError>(client)
+            };
+            let failure = match time::timeout(REPLAY_TIMEOUT, attempt).await {
+                Ok(Ok(client)) => {
+                    tracing::info!("reconnected to broker");
+                    status_line(options.broker_status, "broker connected");
+                    broker_client = Some(client);
+                    backoff.reset();
+                    reconnect_at = None;
+                    None
+                }
+                Ok(Err(PtyError::NameInUse(name))) => {
+                    // Another session took the name while we were away.
+                    tracing::error!(%name, "session name taken — no longer reconnecting");
+                    status_line(
+                        true,
+                        &format!("session name {name:?} is now in use, running standalone"),
+                    );
+                    reconnect_at = None;
+                    None
                 }
-            } else if let Some(ref buffered) = latest_turn {
-                // Broker disconnected — attempt late registration.
-                // CONTRACT_PTY.md §119: retain latest turn and send on
-                // successful registration.
-                let reconnect = async {
-                    let mut client =
-                        BrokerClient::connect(&session_id, child_pid.as_raw() as u32, &pattern)
-                            .await?;
-                    client.send_turn(buffered).await?;
-                    Ok::<_, PtyError>(client)
-                };
-                match time::timeout(BROKER_IO_TIMEOUT, reconnect).await {
-                    Ok(Ok(client)) => {
-                        tracing::info!("late registration — connected to broker");
-                        broker_client = Some(client);
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_elapsed) => Some("timed out".to_string()),
+            };
+            if let Some(error) = failure {
+                let delay = backoff.next_delay();
+                tracing::debug!(%error, ?delay, "broker reconnect failed");
+                reconnect_at = Some(time::Instant::now() + delay);
+            }
+        }
+
+        if input_submitted {
+            let now = crate::turn::epoch_millis();
+            // Further input while the agent is busy belongs to the
+            // same turn.
+            turn_submitted_at.get_or_insert(now);
+            if let Some(ref mut broker) = broker_client
+                && let Ok(Err(e)) =
+                    time::timeout(BROKER_IO_TIMEOUT, broker.send_input_activity(now)).await
+            {
+                tracing::warn!(error = %e, "failed to send input activity to broker");
+            }
+        }
+
+        for turn in pending_turns {
+            if context_tx.send((turn, turn_submitted_at.take())).is_ok() {
+                turns_in_flight += 1;
+            }
+        }
+
+        if let Some((turn, context)) = ready_turn {
+            turns_in_flight -= 1;
+            // Always record in the local history; while disconnected the
+            // reconnect timer replays it on registration.
+            let entry = history.push(turn, context).clone();
+            if let Some(ref mut broker) = broker_client {
+                match time::timeout(BROKER_IO_TIMEOUT, broker.send_turn(&entry, false)).await {
+                    Ok(Err(e)) => {
+                        tracing::warn!(error = %e, "failed to send turn to broker");
                     }
-                    Ok(Err(_)) | Err(_) => {
-                        // Unreachable or timed out — will retry on next turn.
+                    Err(_elapsed) => {
+                        tracing::warn!("broker send timed out — skipping");
                     }
+                    Ok(Ok(())) => {}
                 }
             }
         }
+
+        // Report agent state transitions after any completed turn, so
+        // the broker sees the turn before the session turns idle.
+        let current_state = agent_state(turn_detector.state());
+        if turns_in_flight == 0
+            && reported_state != Some(current_state)
+            && let Some(ref mut broker) = broker_client
+        {
+            match time::timeout(BROKER_IO_TIMEOUT, broker.send_state(current_state)).await {
+                Ok(Ok(())) => reported_state = Some(current_state),
+                Ok(Err(e)) => tracing::warn!(error = %e, "failed to send session state"),
+                Err(_elapsed) => tracing::warn!("session state send timed out"),
+            }
+        }
+
+        if let Some((query, content)) = scrollback_reply
+            && let Some(ref mut broker) = broker_client
+        {
+            match time::timeout(
+                BROKER_IO_TIMEOUT,
+                broker.send_scrollback_lines(query, content),
+            )
+            .await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!(error = %e, "failed to send scrollback lines"),
+                Err(_elapsed) => tracing::warn!("scrollback lines send timed out"),
+            }
+        }
+
+        if let Some(content) = tapped_output
+            && let Some(ref broker) = broker_client
+            && !broker.send_output(content)
+        {
+            // Best-effort: a chunk that cannot be queued is dropped
+            // rather than stalling the child's output.
+            tracing::debug!("output queue full — chunk dropped");
+        }
+
+        if current_title != reported_title
+            && let Some(ref title) = current_title
+            && let Some(ref mut broker) = broker_client
+        {
+            match time::timeout(BROKER_IO_TIMEOUT, broker.send_title(title)).await {
+                Ok(Ok(())) => reported_title = current_title.clone(),
+                Ok(Err(e)) => tracing::warn!(error = %e, "failed to send session title"),
+                Err(_elapsed) => tracing::warn!("session title send timed out"),
+            }
+        }
     };
 
     // -- Post-loop cleanup --
@@ -325,24 +742,30 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
     let events = turn_detector.flush_line();
     for event in events {
         if let TurnEvent::TurnCompleted(turn) = event {
-            latest_turn = Some(turn.clone());
-            if let Some(ref mut broker) = broker_client {
-                let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.send_turn(&turn)).await;
-            }
+            let _ = context_tx.send((turn, turn_submitted_at.take()));
         }
     }
+    // Send the turns still with the context reader, which ends once
+    // it has handed back the last one.
+    drop(context_tx);
+    while let Some((turn, context)) = context_rx.recv().await {
+        let entry = history.push(turn, context).clone();
+        if let Some(ref mut broker) = broker_client {
+            let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.send_turn(&entry, false)).await;
+        }
+    }
+
+    stop_recording(&mut recorder);
 
-    // Suppress unused warning when broker is never connected.
-    drop(latest_turn);
+    // Wait for child exit, so the deregister can report how it ended.
+    let exit = wait_for_exit(child_pid)?;
+    let exit_code = exit.code();
 
     // Deregister from broker.
-    if let Some(ref mut broker) = broker_client {
-        let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.deregister()).await;
+    if let Some(broker) = broker_client.take() {
+        let _ = time::timeout(SHUTDOWN_IO_TIMEOUT, broker.deregister(exit)).await;
     }
 
-    // Wait for child exit.
-    let exit_code = wait_for_exit(child_pid)?;
-
     // Terminal guard drops here → restores terminal.
     drop(terminal_guard);
 
@@ -357,6 +780,157 @@ pub async fn run_session(pattern: String, command: Vec<String>) -> Result<i32, P
 
 // -- Helpers --
 
+/// How long a broker-requested terminate waits after SIGTERM, or
+/// [`hang_up`] after SIGHUP, before sending SIGKILL.
+const KILL_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Longest a completed turn waits for its git context.
+const GIT_CONTEXT_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Print a one-line broker status notice on stderr when enabled.
+///
+/// Written with `\r\n` since the terminal may be in raw mode. This is
+/// out-of-band feedback (CONTRACT_PTY.md §Invariant); nothing is
+/// written to the child's output stream.
+fn status_line(enabled: bool, message: &str) {
+    if enabled {
+        eprint!("\r\n[clippy] {message}\r\n");
+    }
+}
+
+/// Create an asciicast recording of a PTY of the given `(cols, rows)`.
+fn start_recording(
+    path: &Path,
+    (cols, rows): (u16, u16),
+    command: &[String],
+) -> io::Result<FileRecorder> {
+    let header = Header {
+        cols,
+        rows,
+        timestamp: crate::turn::epoch_millis() / 1000,
+        command,
+    };
+    FileRecorder::create(path, &header)
+}
+
+/// Write to the active recording, if any. A write error stops the
+/// recording rather than the session.
+fn record(
+    recorder: &mut Option<FileRecorder>,
+    write: impl FnOnce(&mut FileRecorder) -> io::Result<()>,
+) {
+    if let Some(r) = recorder.as_mut()
+        && let Err(e) = write(r)
+    {
+        tracing::warn!(error = %e, "recording write failed — recording stopped");
+        *recorder = None;
+    }
+}
+
+/// Finish and close the active recording, if any.
+fn stop_recording(recorder: &mut Option<FileRecorder>) {
+    if let Some(r) = recorder.take() {
+        match r.finish() {
+            Ok(()) => tracing::info!("recording stopped"),
+            Err(e) => tracing::warn!(error = %e, "recording finish failed"),
+        }
+    }
+}
+
+/// A completed turn and when the input that started it was submitted.
+type CompletedTurn = (Turn, Option<u64>);
+
+/// Start the task that attaches a [`TurnContext`] to completed turns,
+/// sent with the time their input was submitted.
+///
+/// Turns are handled one at a time, so they come back in the order
+/// they completed.
+fn spawn_context_reader(
+    child_pid: Pid,
+) -> (
+    mpsc::UnboundedSender<CompletedTurn>,
+    mpsc::UnboundedReceiver<(Turn, TurnContext)>,
+) {
+    let (turn_tx, mut turn_rx) = mpsc::unbounded_channel::<CompletedTurn>();
+    let (ready_tx, ready_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some((turn, submitted_at)) = turn_rx.recv().await {
+            let context = turn_context(&turn, submitted_at, child_pid).await;
+            if ready_tx.send((turn, context)).is_err() {
+                break;
+            }
+        }
+    });
+    (turn_tx, ready_rx)
+}
+
+/// Timing and environment of a turn that just completed.
+///
+/// The child's cwd and its git state are read now, at completion, so a
+/// turn records the checkout it was produced against. The git read
+/// stats every tracked file, so it runs on the blocking pool and is
+/// left out if it takes longer than [`GIT_CONTEXT_TIMEOUT`].
+async fn turn_context(turn: &Turn, submitted_at: Option<u64>, child_pid: Pid) -> TurnContext {
+    let cwd = std::fs::read_link(format!("/proc/{child_pid}/cwd")).ok();
+    let git = match cwd.clone() {
+        Some(dir) => {
+            let read = tokio::task::spawn_blocking(move || git::git_context(&dir));
+            match time::timeout(GIT_CONTEXT_TIMEOUT, read).await {
+                Ok(Ok(git)) => git,
+                Ok(Err(e)) => {
+                    tracing::warn!(error = %e, "git context task failed");
+                    None
+                }
+                Err(_elapsed) => {
+                    tracing::debug!("git context timed out — omitted");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    let newlines = turn.content.iter().filter(|&&b| b == b'\n').count();
+    let unterminated = !turn.content.is_empty() && !turn.content.ends_with(b"\n");
+    TurnContext {
+        submitted_at,
+        duration_ms: submitted_at.map(|at| turn.timestamp.saturating_sub(at)),
+        line_count: Some((newlines + unterminated as usize) as u32),
+        git,
+        cwd: cwd.map(|path| path.display().to_string()),
+    }
+}
+
+/// Notify the turn detector when input (typed or injected) submits a
+/// line. Returns whether it did.
+fn submit_input(turn_detector: &mut TurnDetector, input: &[u8]) -> bool {
+    let submitted = input.iter().any(|&b| b == b'\r' || b == b'\n');
+    if submitted {
+        turn_detector.notify_user_input();
+    }
+    submitted
+}
+
+/// Map a turn detector state to the agent state reported to the broker.
+fn agent_state(state: DetectorState) -> AgentState {
+    match state {
+        DetectorState::AwaitingFirstPrompt => AgentState::Starting,
+        DetectorState::AwaitingUserInput => AgentState::Idle,
+        DetectorState::AccumulatingOutput => AgentState::Busy,
+    }
+}
+
+/// Hang up a child the wrapper gives up on at startup, and kill it if
+/// it ignores that for [`KILL_GRACE`], as a broker-requested terminate
+/// does.
+fn hang_up(child_pid: Pid) {
+    let _ = forward_signal(child_pid, Signal::SIGHUP);
+    if let Ok(None) = wait_for_exit_within(child_pid, KILL_GRACE) {
+        tracing::warn!("child still running after SIGHUP — sending SIGKILL");
+        let _ = forward_signal(child_pid, Signal::SIGKILL);
+        let _ = wait_for_exit(child_pid);
+    }
+}
+
 /// Forward a signal to the child's process group.
 fn forward_signal(child_pid: Pid, sig: Signal) -> Result<(), PtyError> {
     // Negative PID → send to process group.
@@ -398,3 +972,62 @@ fn nix_write_all(fd: RawFd, mut data: &[u8]) -> Result<(), PtyError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injected_line_completes_a_turn() {
+        let mut detector = TurnDetector::new("generic").unwrap();
+        detector.feed_output(b"$ \n");
+
+        // Bytes without a line ending submit nothing.
+        assert!(!submit_input(&mut detector, b"echo hi"));
+        assert!(detector.feed_output(b"$ \n").is_empty());
+
+        assert!(submit_input(&mut detector, b"echo hi\r"));
+        let events = detector.feed_output(b"hi\n$ \n");
+        assert!(matches!(
+            events.as_slice(),
+            [TurnEvent::TurnCompleted(turn)] if turn.content == b"hi\n"
+        ));
+    }
+
+    #[test]
+    fn injected_line_reports_busy_until_the_prompt() {
+        let mut detector = TurnDetector::new("generic").unwrap();
+        assert_eq!(agent_state(detector.state()), AgentState::Starting);
+        detector.feed_output(b"$ \n");
+        assert_eq!(agent_state(detector.state()), AgentState::Idle);
+
+        submit_input(&mut detector, b"make\n");
+        assert_eq!(agent_state(detector.state()), AgentState::Busy);
+        detector.feed_output(b"building...\n");
+        assert_eq!(agent_state(detector.state()), AgentState::Busy);
+
+        detector.feed_output(b"$ \n");
+        assert_eq!(agent_state(detector.state()), AgentState::Idle);
+    }
+
+    #[tokio::test]
+    async fn context_reader_keeps_completion_order() {
+        let (tx, mut rx) = spawn_context_reader(Pid::this());
+        for content in [b"first\n", b"later\n"] {
+            let turn = Turn {
+                content: content.to_vec(),
+                interrupted: false,
+                timestamp: 2000,
+            };
+            tx.send((turn, Some(1500))).unwrap();
+        }
+        drop(tx);
+
+        let (turn, context) = rx.recv().await.unwrap();
+        assert_eq!(turn.content, b"first\n");
+        assert_eq!(context.duration_ms, Some(500));
+        assert!(context.cwd.is_some());
+        assert_eq!(rx.recv().await.unwrap().0.content, b"later\n");
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//! Session recording in asciicast v2 format.
+//!
+//! Opt-in only (`wrap --record`, or a broker `record_start`): nothing
+//! is written unless a recording was requested. The file is a JSON
+//! header line followed by one `[time, code, data]` event per line —
+//! `"o"` for child output, `"i"` for input sent to the child (keystrokes
+//! and injects), `"r"` for window size changes. Recordings play back
+//! with `asciinema play` and double as detector replay fixtures.
+//! See CONTRACT_PTY.md §Recording.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// A recording written to a file.
+pub type FileRecorder = Recorder<BufWriter<File>>;
+
+/// Session details for the asciicast header.
+#[derive(Debug, Clone)]
+pub struct Header<'a> {
+    pub cols: u16,
+    pub rows: u16,
+    /// Unix epoch seconds when recording started.
+    pub timestamp: u64,
+    pub command: &'a [String],
+}
+
+/// Asciicast v2 writer. Event times are relative to creation.
+///
+/// Output and input are kept valid UTF-8 per stream: a multi-byte
+/// character split across reads is held until it completes; invalid
+/// bytes become U+FFFD.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    out: W,
+    started: Instant,
+    partial_output: Vec<u8>,
+    partial_input: Vec<u8>,
+}
+
+impl FileRecorder {
+    /// Create (or truncate) `path` and write the header.
+    pub fn create(path: &Path, header: &Header) -> io::Result<Self> {
+        Recorder::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording on `out` by writing the header line.
+    pub fn new(mut out: W, header: &Header) -> io::Result<Self> {
+        let mut line = format!(
+            "{{\"version\":2,\"width\":{},\"height\":{},\"timestamp\":{},\"command\":",
+            header.cols, header.rows, header.timestamp
+        );
+        push_json_string(&mut line, &header.command.join(" "));
+        if let Ok(term) = std::env::var("TERM") {
+            line.push_str(",\"env\":{\"TERM\":");
+            push_json_string(&mut line, &term);
+            line.push('}');
+        }
+        line.push_str("}\n");
+        out.write_all(line.as_bytes())?;
+        out.flush()?;
+        Ok(Self {
+            out,
+            started: Instant::now(),
+            partial_output: Vec::new(),
+            partial_input: Vec::new(),
+        })
+    }
+
+    /// Record a chunk of child output.
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = take_text(&mut self.partial_output, data);
+        self.event("o", &text)
+    }
+
+    /// Record input written to the child.
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        let text = take_text(&mut self.partial_input, data);
+        self.event("i", &text)
+    }
+
+    /// Record a window size change.
+    pub fn resize(&mut self, cols: u16, rows: u16) -> io::Result<()> {
+        self.event("r", &format!("{cols}x{rows}"))
+    }
+
+    /// Write out any held partial characters and flush.
+    pub fn finish(mut self) -> io::Result<()> {
+        let output =
+            String::from_utf8_lossy(&std::mem::take(&mut self.partial_output)).into_owned();
+        self.event("o", &output)?;
+        let input = String::from_utf8_lossy(&std::mem::take(&mut self.partial_input)).into_owned();
+        self.event("i", &input)?;
+        self.out.flush()
+    }
+
+    /// Write one event line. Empty data (only a partial character so
+    /// far) writes nothing. Flushed per event so a crash loses little.
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let mut line = format!("[{elapsed:.6},\"{code}\",");
+        push_json_string(&mut line, data);
+        line.push_str("]\n");
+        self.out.write_all(line.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+/// Append `data` to `pending` and return the longest decodable prefix
+/// as text, leaving an incomplete trailing character in `pending`.
+fn take_text(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let mut text = String::new();
+    let mut rest: &[u8] = pending;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).expect("validated prefix"));
+                match e.error_len() {
+                    Some(bad) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[bad..];
+                    }
+                    // Incomplete character at the end: keep it.
+                    None => {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let consumed = pending.len() - rest.len();
+    pending.drain(..consumed);
+    text
+}
+
+/// Append `s` as a JSON string literal.
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header<'static> {
+        Header {
+            cols: 80,
+            rows: 24,
+            timestamp: 1_700_000_000,
+            command: &[],
+        }
+    }
+
+    fn lines(out: &[u8]) -> Vec<String> {
+        String::from_utf8(out.to_vec())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Strip the event time: `[0.000123,"o","x"]` → `"o","x"]`.
+    fn without_time(line: &str) -> &str {
+        &line[line.find(',').unwrap() + 1..]
+    }
+
+    #[test]
+    fn header_is_asciicast_v2() {
+        let command = [
+            "claude".to_string(),
+            "--model".to_string(),
+            "x\"y".to_string(),
+        ];
+        let h = Header {
+            command: &command,
+            ..header()
+        };
+        let mut out = Vec::new();
+        Recorder::new(&mut out, &h).unwrap();
+        let first = &lines(&out)[0];
+        assert!(first.starts_with(
+            "{\"version\":2,\"width\":80,\"height\":24,\"timestamp\":1700000000,\"command\":\"claude --model x\\\"y\""
+        ));
+        assert!(first.ends_with('}'));
+    }
+
+    #[test]
+    fn records_output_input_and_resize() {
+        let mut out = Vec::new();
+        let mut rec = Recorder::new(&mut out, &header()).unwrap();
+        rec.output(b"\x1b[1mhi\x1b[0m\r\n").unwrap();
+        rec.input(b"ls\r").unwrap();
+        rec.resize(120, 40).unwrap();
+        rec.finish().unwrap();
+        let events = lines(&out);
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            without_time(&events[1]),
+            "\"o\",\"\\u001b[1mhi\\u001b[0m\\r\\n\"]"
+        );
+        assert_eq!(without_time(&events[2]), "\"i\",\"ls\\r\"]");
+        assert_eq!(without_time(&events[3]), "\"r\",\"120x40\"]");
+    }
+
+    #[test]
+    fn split_utf8_is_held_until_complete() {
+        let mut out = Vec::new();
+        let mut rec = Recorder::new(&mut out, &header()).unwrap();
+        let check = "✓".as_bytes();
+        rec.output(&check[..1]).unwrap();
+        rec.output(&check[1..]).unwrap();
+        rec.finish().unwrap();
+        let events = lines(&out);
+        assert_eq!(events.len(), 2);
+        assert_eq!(without_time(&events[1]), "\"o\",\"✓\"]");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let mut pending = Vec::new();
+        assert_eq!(take_text(&mut pending, b"a\xffb"), "a\u{fffd}b");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn finish_flushes_partial_character() {
+        let mut out = Vec::new();
+        let mut rec = Recorder::new(&mut out, &header()).unwrap();
+        rec.output(&"é".as_bytes()[..1]).unwrap();
+        rec.finish().unwrap();
+        let events = lines(&out);
+        assert_eq!(without_time(&events[1]), "\"o\",\"\u{fffd}\"]");
+    }
+}
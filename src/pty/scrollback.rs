@@ -0,0 +1,139 @@
+//! Wrapper-side scrollback — recent output lines for line-based capture.
+//!
+//! Independent of turn detection: keeps the last N lines the child
+//! printed, ANSI-stripped, so a client can capture "the last 80 lines"
+//! even when no turn was detected (a tool's build error, or a session
+//! whose prompt pattern is wrong). See CONTRACT_PTY.md §Scrollback.
+
+use std::collections::VecDeque;
+
+use regex::Regex;
+
+use crate::turn::ansi::AnsiStripper;
+
+/// Longest line kept, in bytes; the rest of a longer line is dropped.
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Bounded ring of ANSI-stripped output lines, oldest first.
+#[derive(Debug)]
+pub struct Scrollback {
+    lines: VecDeque<String>,
+    capacity: usize,
+    /// Current, not yet terminated line.
+    partial: Vec<u8>,
+    /// Saw `\r`; a following `\n` ends the line, anything else
+    /// overwrites it (progress bars, spinners).
+    pending_cr: bool,
+    stripper: AnsiStripper,
+}
+
+impl Scrollback {
+    /// Create an empty scrollback holding at most `capacity` lines
+    /// (minimum 1).
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            lines: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+            partial: Vec::new(),
+            pending_cr: false,
+            stripper: AnsiStripper::new(),
+        }
+    }
+
+    /// Feed a chunk of raw child output.
+    pub fn feed(&mut self, data: &[u8]) {
+        for byte in self.stripper.strip(data) {
+            // `\r\r\n` is common (ONLCR on output that already has `\r\n`).
+            if self.pending_cr && byte != b'\r' {
+                self.pending_cr = false;
+                if byte != b'\n' {
+                    self.partial.clear();
+                }
+            }
+            match byte {
+                b'\n' => self.end_line(),
+                b'\r' => self.pending_cr = true,
+                _ if self.partial.len() < MAX_LINE_BYTES => self.partial.push(byte),
+                _ => {}
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        let line = String::from_utf8_lossy(&self.partial).into_owned();
+        self.lines.push_back(line);
+        self.partial.clear();
+    }
+
+    /// The last `count` lines matching `grep` (all lines when `None`),
+    /// oldest first. The current unterminated line, if non-empty,
+    /// counts as the newest line.
+    pub fn last_lines(&self, count: usize, grep: Option<&Regex>) -> Vec<String> {
+        let partial =
+            (!self.partial.is_empty()).then(|| String::from_utf8_lossy(&self.partial).into_owned());
+        let mut selected: Vec<String> = self
+            .lines
+            .iter()
+            .cloned()
+            .chain(partial)
+            .rev()
+            .filter(|line| grep.is_none_or(|re| re.is_match(line)))
+            .take(count)
+            .collect();
+        selected.reverse();
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lines_and_strips_ansi() {
+        let mut sb = Scrollback::new(10);
+        sb.feed(b"\x1b[31merror\x1b[0m: boom\r\nok\n");
+        assert_eq!(sb.last_lines(10, None), ["error: boom", "ok"]);
+    }
+
+    #[test]
+    fn keeps_only_capacity_lines() {
+        let mut sb = Scrollback::new(2);
+        sb.feed(b"a\nb\nc\n");
+        assert_eq!(sb.last_lines(10, None), ["b", "c"]);
+    }
+
+    #[test]
+    fn carriage_return_overwrites_line() {
+        let mut sb = Scrollback::new(10);
+        sb.feed(b"10%\r50%\r");
+        sb.feed(b"100%\n");
+        assert_eq!(sb.last_lines(10, None), ["100%"]);
+    }
+
+    #[test]
+    fn doubled_carriage_return_ends_line() {
+        let mut sb = Scrollback::new(10);
+        sb.feed(b"line 1\r\r\nline 2\r\r\n");
+        assert_eq!(sb.last_lines(10, None), ["line 1", "line 2"]);
+    }
+
+    #[test]
+    fn includes_partial_line() {
+        let mut sb = Scrollback::new(10);
+        sb.feed(b"done\n$ ");
+        assert_eq!(sb.last_lines(1, None), ["$ "]);
+    }
+
+    #[test]
+    fn grep_filters_before_counting() {
+        let mut sb = Scrollback::new(10);
+        sb.feed(b"error: a\nok\nerror: b\nok\nerror: c\n");
+        let re = Regex::new("^error").unwrap();
+        assert_eq!(sb.last_lines(2, Some(&re)), ["error: b", "error: c"]);
+    }
+}
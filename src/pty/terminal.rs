@@ -156,3 +156,34 @@ pub fn propagate_window_size(pty_master_fd: RawFd) -> Result<(), PtyError> {
     tracing::debug!(rows = ws.ws_row, cols = ws.ws_col, "window resized");
     Ok(())
 }
+
+/// Read the child PTY's current window size as `(cols, rows)`.
+pub fn pty_window_size(pty_master_fd: RawFd) -> Result<(u16, u16), PtyError> {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::ioctl(pty_master_fd, libc::TIOCGWINSZ, &mut ws) } < 0 {
+        return Err(PtyError::Terminal(nix::Error::last()));
+    }
+
+    Ok((ws.ws_col, ws.ws_row))
+}
+
+/// Set an explicit PTY window size (headless sessions).
+///
+/// Like [`propagate_window_size`], the kernel delivers SIGWINCH to
+/// the child.
+pub fn set_window_size(pty_master_fd: RawFd, cols: u16, rows: u16) -> Result<(), PtyError> {
+    let ws = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    if unsafe { libc::ioctl(pty_master_fd, libc::TIOCSWINSZ, &ws) } < 0 {
+        return Err(PtyError::Terminal(nix::Error::last()));
+    }
+
+    tracing::debug!(rows, cols, "window size set");
+    Ok(())
+}
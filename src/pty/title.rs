@@ -0,0 +1,174 @@
+//! Terminal title tracking — OSC 0/2 sequences in child output.
+//!
+//! Observes the output stream only; bytes are forwarded to the user
+//! unmodified by the caller (CONTRACT_PTY.md §I/O Transparency).
+
+/// Maximum OSC payload retained. Longer sequences are ignored rather
+/// than buffered without bound.
+const MAX_OSC_LEN: usize = 4096;
+
+/// Parser states for OSC extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Outside any escape sequence.
+    Ground,
+    /// Saw ESC, waiting for `]`.
+    Escape,
+    /// Inside an OSC sequence, collecting payload until BEL or ST.
+    Osc,
+    /// Inside OSC, saw ESC — expecting `\` for ST.
+    OscEscape,
+}
+
+/// Stateful extractor for window-title OSC sequences.
+///
+/// Recognizes `ESC ] 0 ; title` and `ESC ] 2 ; title`, terminated by
+/// BEL or ST (`ESC \`). Sequences split across chunks are handled.
+/// Other OSC codes (e.g. 1 = icon name, 8 = hyperlink) are ignored.
+#[derive(Debug)]
+pub struct TitleTracker {
+    state: State,
+    payload: Vec<u8>,
+    /// Set when the current payload exceeded [`MAX_OSC_LEN`].
+    overflowed: bool,
+}
+
+impl TitleTracker {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            payload: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// Feed output bytes. Returns the last title set within `data`, if
+    /// any. An empty title is returned as `Some("")` (title cleared).
+    pub fn feed(&mut self, data: &[u8]) -> Option<String> {
+        let mut title = None;
+
+        for &byte in data {
+            match self.state {
+                State::Ground => {
+                    if byte == 0x1B {
+                        self.state = State::Escape;
+                    }
+                }
+                State::Escape => match byte {
+                    b']' => {
+                        self.state = State::Osc;
+                        self.payload.clear();
+                        self.overflowed = false;
+                    }
+                    0x1B => {}
+                    _ => self.state = State::Ground,
+                },
+                State::Osc => match byte {
+                    0x07 => {
+                        title = self.finish().or(title);
+                        self.state = State::Ground;
+                    }
+                    0x1B => self.state = State::OscEscape,
+                    _ => self.push(byte),
+                },
+                State::OscEscape => {
+                    if byte == b'\\' {
+                        title = self.finish().or(title);
+                        self.state = State::Ground;
+                    } else if byte == b']' {
+                        // Unterminated OSC followed by a new one.
+                        self.state = State::Osc;
+                        self.payload.clear();
+                        self.overflowed = false;
+                    } else {
+                        self.state = State::Ground;
+                    }
+                }
+            }
+        }
+
+        title
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.payload.len() < MAX_OSC_LEN {
+            self.payload.push(byte);
+        } else {
+            self.overflowed = true;
+        }
+    }
+
+    /// Complete the current OSC sequence, returning its title if it is
+    /// a title-setting sequence.
+    fn finish(&mut self) -> Option<String> {
+        let payload = std::mem::take(&mut self.payload);
+        if self.overflowed {
+            return None;
+        }
+        let (code, text) = payload.split_at(payload.iter().position(|&b| b == b';')?);
+        match code {
+            b"0" | b"2" => Some(String::from_utf8_lossy(&text[1..]).into_owned()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc_2_with_bel() {
+        let mut t = TitleTracker::new();
+        assert_eq!(t.feed(b"\x1b]2;fix tests\x07"), Some("fix tests".into()));
+    }
+
+    #[test]
+    fn osc_0_with_st() {
+        let mut t = TitleTracker::new();
+        assert_eq!(t.feed(b"\x1b]0;planner\x1b\\"), Some("planner".into()));
+    }
+
+    #[test]
+    fn split_across_chunks() {
+        let mut t = TitleTracker::new();
+        assert_eq!(t.feed(b"text \x1b]2;ref"), None);
+        assert_eq!(t.feed(b"actor"), None);
+        assert_eq!(t.feed(b"\x1b"), None);
+        assert_eq!(t.feed(b"\\more"), Some("refactor".into()));
+    }
+
+    #[test]
+    fn last_title_in_chunk_wins() {
+        let mut t = TitleTracker::new();
+        assert_eq!(
+            t.feed(b"\x1b]2;one\x07output\x1b]2;two\x07"),
+            Some("two".into())
+        );
+    }
+
+    #[test]
+    fn ignores_other_osc_and_csi() {
+        let mut t = TitleTracker::new();
+        assert_eq!(t.feed(b"\x1b]1;icon\x07"), None);
+        assert_eq!(t.feed(b"\x1b]8;;https://x\x07link\x1b]8;;\x07"), None);
+        assert_eq!(t.feed(b"\x1b[31mred\x1b[0m"), None);
+    }
+
+    #[test]
+    fn empty_title_clears() {
+        let mut t = TitleTracker::new();
+        assert_eq!(t.feed(b"\x1b]2;\x07"), Some(String::new()));
+    }
+
+    #[test]
+    fn oversized_payload_ignored() {
+        let mut t = TitleTracker::new();
+        let mut data = b"\x1b]2;".to_vec();
+        data.extend(std::iter::repeat_n(b'x', MAX_OSC_LEN + 10));
+        data.push(0x07);
+        assert_eq!(t.feed(&data), None);
+        // Parser recovers for the next sequence.
+        assert_eq!(t.feed(b"\x1b]2;ok\x07"), Some("ok".into()));
+    }
+}
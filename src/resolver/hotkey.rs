@@ -29,6 +29,8 @@ pub enum HotkeyEvent {
     Paste,
     /// Capture to system clipboard.
     Clipboard,
+    /// Restore the relay buffer's previous content.
+    Undo,
 }
 
 /// Result of a successful `HotkeyProvider::register()` call.
@@ -63,13 +65,14 @@ pub trait HotkeyProvider {
     /// and spawns an event thread/task that classifies raw events into
     /// `HotkeyEvent` values on the returned channel.
     ///
-    /// `clipboard` is optional — if `None`, only capture and paste
-    /// bindings are registered.
+    /// `clipboard` and `undo` are optional — if `None`, that binding
+    /// is not registered.
     fn register(
         &mut self,
         capture: &KeyBinding,
         paste: &KeyBinding,
         clipboard: Option<&KeyBinding>,
+        undo: Option<&KeyBinding>,
     ) -> Result<HotkeyRegistration, ResolverError>;
 
     /// Release all grabbed key bindings and stop the event thread.
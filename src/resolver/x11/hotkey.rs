@@ -108,6 +108,38 @@ impl X11HotkeyProvider {
         }
     }
 
+    /// Parse and grab an optional binding (`what` names it in logs),
+    /// counting it in `bindings_ok` if the grab succeeds.
+    fn grab_optional(
+        &self,
+        key: Option<&KeyBinding>,
+        what: &str,
+        bindings_ok: &mut u32,
+    ) -> Result<Option<Binding>, ResolverError> {
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let binding = keybinding::parse_binding(&key.spec, &*self.conn, self.conn.setup())
+            .map_err(|e| ResolverError::Hotkey(format!("parse {what} binding: {e}")))?;
+
+        match self.grab_key(&binding) {
+            Ok(true) => {
+                *bindings_ok += 1;
+                tracing::info!(binding = %binding.raw, "{what} hotkey grabbed");
+            }
+            Ok(false) => {
+                eprintln!(
+                    "warning: {what} hotkey {} could not be grabbed (conflict)",
+                    binding.raw
+                );
+            }
+            Err(e) => {
+                tracing::error!(binding = %binding.raw, error = %e, "grab failed");
+            }
+        }
+        Ok(Some(binding))
+    }
+
     /// Lock-mask combinations for grab registration.
     fn lock_masks(&self) -> [u16; 4] {
         [
@@ -125,6 +157,7 @@ impl crate::resolver::HotkeyProvider for X11HotkeyProvider {
         capture: &KeyBinding,
         paste: &KeyBinding,
         clipboard: Option<&KeyBinding>,
+        undo: Option<&KeyBinding>,
     ) -> Result<HotkeyRegistration, ResolverError> {
         // 1. Parse bindings.
         let capture_binding =
@@ -177,35 +210,13 @@ impl crate::resolver::HotkeyProvider for X11HotkeyProvider {
             }
         }
 
-        let clipboard_binding = match clipboard {
-            Some(key) => {
-                let binding = keybinding::parse_binding(&key.spec, &*self.conn, self.conn.setup())
-                    .map_err(|e| ResolverError::Hotkey(format!("parse clipboard binding: {e}")))?;
-
-                match self.grab_key(&binding) {
-                    Ok(true) => {
-                        bindings_ok += 1;
-                        tracing::info!(binding = %binding.raw, "clipboard hotkey grabbed");
-                    }
-                    Ok(false) => {
-                        eprintln!(
-                            "warning: clipboard hotkey {} could not be grabbed (conflict)",
-                            binding.raw
-                        );
-                    }
-                    Err(e) => {
-                        tracing::error!(binding = %binding.raw, error = %e, "grab failed");
-                    }
-                }
-                Some(binding)
-            }
-            None => None,
-        };
+        let clipboard_binding = self.grab_optional(clipboard, "clipboard", &mut bindings_ok)?;
+        let undo_binding = self.grab_optional(undo, "undo", &mut bindings_ok)?;
 
         // Store bindings for ungrab on shutdown.
         self.bindings.push(capture_binding.clone());
         self.bindings.push(paste_binding.clone());
-        if let Some(ref b) = clipboard_binding {
+        for b in clipboard_binding.iter().chain(&undo_binding) {
             self.bindings.push(b.clone());
         }
 
@@ -229,14 +240,20 @@ impl crate::resolver::HotkeyProvider for X11HotkeyProvider {
         let cap = capture_binding;
         let pst = paste_binding;
         let clip = clipboard_binding;
+        let undo = undo_binding;
 
         let bridge = std::thread::Builder::new()
             .name("x11-hotkey-bridge".into())
             .spawn(move || {
                 while let Some(event) = raw_rx.blocking_recv() {
-                    if let Some(hotkey_event) =
-                        classify_event(&event, &cap, &pst, clip.as_ref(), numlock_mask)
-                        && event_tx.send(hotkey_event).is_err()
+                    if let Some(hotkey_event) = classify_event(
+                        &event,
+                        &cap,
+                        &pst,
+                        clip.as_ref(),
+                        undo.as_ref(),
+                        numlock_mask,
+                    ) && event_tx.send(hotkey_event).is_err()
                     {
                         // Receiver dropped — shut down.
                         return;
@@ -290,6 +307,7 @@ fn classify_event(
     capture_binding: &Binding,
     paste_binding: &Binding,
     clipboard_binding: Option<&Binding>,
+    undo_binding: Option<&Binding>,
     numlock_mask: u16,
 ) -> Option<HotkeyEvent> {
     let key_event = match event {
@@ -304,12 +322,14 @@ fn classify_event(
         Some(HotkeyEvent::Capture)
     } else if keybinding::event_matches_binding(keycode, state, paste_binding, numlock_mask) {
         Some(HotkeyEvent::Paste)
-    } else if let Some(binding) = clipboard_binding {
-        if keybinding::event_matches_binding(keycode, state, binding, numlock_mask) {
-            Some(HotkeyEvent::Clipboard)
-        } else {
-            None
-        }
+    } else if clipboard_binding
+        .is_some_and(|b| keybinding::event_matches_binding(keycode, state, b, numlock_mask))
+    {
+        Some(HotkeyEvent::Clipboard)
+    } else if undo_binding
+        .is_some_and(|b| keybinding::event_matches_binding(keycode, state, b, numlock_mask))
+    {
+        Some(HotkeyEvent::Undo)
     } else {
         None
     }
@@ -55,9 +55,12 @@ pub enum TurnEvent {
     TurnCompleted(Turn),
 }
 
-/// Internal state of the turn detector state machine.
+/// State of the turn detector state machine.
+///
+/// Exposed via [`TurnDetector::state`] so the wrapper can report
+/// transitions to the broker.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum DetectorState {
+pub enum DetectorState {
     /// Waiting for the agent to show its first prompt.
     AwaitingFirstPrompt,
     /// A prompt was shown — waiting for the user to submit input.
@@ -160,6 +163,11 @@ impl TurnDetector {
         events
     }
 
+    /// Current state of the detector.
+    pub fn state(&self) -> DetectorState {
+        self.state
+    }
+
     /// Notify the detector that the user has submitted input.
     ///
     /// Transitions from `AwaitingUserInput` to `AccumulatingOutput`.